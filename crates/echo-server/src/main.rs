@@ -19,6 +19,8 @@ use clap::Parser;
 use little_tokio::{
     self,
     net::tcp::{Listener as TcpListener, Stream as TcpStream},
+    signal,
+    stream::StreamExt,
 };
 
 #[derive(Parser, Debug)]
@@ -30,14 +32,20 @@ struct Cli {
 
 fn main() {
     let args = Cli::parse();
-    little_tokio::block_on(async move {
-        let mut listener = TcpListener::bind(format!("0.0.0.0:{}", args.port)).unwrap();
-        println!("server listening on: {}", listener.local_addr().unwrap());
-        loop {
-            let (connection, _) = listener.accept().await.unwrap();
-            little_tokio::spawn(handle(connection));
-        }
-    });
+    little_tokio::block_on_with_shutdown(
+        async move {
+            let mut listener = TcpListener::bind(format!("0.0.0.0:{}", args.port)).unwrap();
+            println!("server listening on: {}", listener.local_addr().unwrap());
+            let mut incoming = listener.incoming();
+            while let Some(connection) = incoming.next().await {
+                little_tokio::spawn(handle(connection.unwrap()));
+            }
+        },
+        async {
+            signal::ctrl_c().await;
+            println!("server shutting down");
+        },
+    );
 }
 
 async fn handle(mut connection: TcpStream) {
@@ -46,6 +54,6 @@ async fn handle(mut connection: TcpStream) {
         if count == 0 {
             return;
         }
-        connection.write(&buffer[..count]).await.unwrap();
+        connection.write_all(&buffer[..count]).await.unwrap();
     }
 }
@@ -0,0 +1,104 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This file contains a benchmark comparing the channels of the Little Tokio runtime, where a producer task sends
+//! a number of values to a consumer task through `sync::mpsc`, `sync::spsc::channel` and `sync::spsc::local_channel`.
+//!
+//! Run it with `cargo bench -p little-tokio --bench channels`.
+
+use little_tokio::sync::{mpsc, spsc};
+use std::time;
+
+/// The number of values the producer sends to the consumer.
+const VALUES: usize = 1_000_000;
+/// The capacity of the bounded channels.
+const CAPACITY: usize = 1024;
+
+fn main() {
+    report("mpsc::channel", bench_mpsc());
+    report("spsc::channel", bench_spsc());
+    report("spsc::local_channel", bench_local_spsc());
+}
+
+/// Prints the throughput of the channel labeled with the given `name` which has transferred `VALUES` values in
+/// the given `elapsed` time.
+fn report(name: &str, elapsed: time::Duration) {
+    println!(
+        "{}: {} values in {:?}: {:.0} values/sec",
+        name,
+        VALUES,
+        elapsed,
+        VALUES as f64 / elapsed.as_secs_f64(),
+    );
+}
+
+/// Transfers `VALUES` values through `mpsc::channel`. Since the buffer is unbounded, the producer never waits for
+/// the consumer.
+fn bench_mpsc() -> time::Duration {
+    let started = time::Instant::now();
+    little_tokio::block_on(async {
+        let (sender, mut receiver) = mpsc::channel();
+        little_tokio::spawn(async move {
+            for value in 0..VALUES {
+                sender.send(value).unwrap();
+            }
+        });
+        let mut sum = 0;
+        while let Some(value) = receiver.recv().await {
+            sum += value;
+        }
+        assert_eq!(sum, VALUES * (VALUES - 1) / 2);
+    });
+    started.elapsed()
+}
+
+/// Transfers `VALUES` values through `spsc::channel` buffering at most `CAPACITY` values.
+fn bench_spsc() -> time::Duration {
+    let started = time::Instant::now();
+    little_tokio::block_on(async {
+        let (mut sender, mut receiver) = spsc::channel(CAPACITY);
+        little_tokio::spawn(async move {
+            for value in 0..VALUES {
+                sender.send(value).await.unwrap();
+            }
+        });
+        let mut sum = 0;
+        while let Some(value) = receiver.recv().await {
+            sum += value;
+        }
+        assert_eq!(sum, VALUES * (VALUES - 1) / 2);
+    });
+    started.elapsed()
+}
+
+/// Transfers `VALUES` values through `spsc::local_channel` buffering at most `CAPACITY` values.
+fn bench_local_spsc() -> time::Duration {
+    let started = time::Instant::now();
+    little_tokio::block_on(async {
+        let (mut sender, mut receiver) = spsc::local_channel(CAPACITY);
+        little_tokio::spawn_local(async move {
+            for value in 0..VALUES {
+                sender.send(value).await.unwrap();
+            }
+        });
+        little_tokio::spawn_local(async move {
+            let mut sum = 0;
+            while let Some(value) = receiver.recv().await {
+                sum += value;
+            }
+            assert_eq!(sum, VALUES * (VALUES - 1) / 2);
+        });
+    });
+    started.elapsed()
+}
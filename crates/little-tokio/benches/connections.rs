@@ -0,0 +1,94 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This file contains a benchmark measuring the connections and the bytes the Little Tokio runtime serves per
+//! second, where an echo server running on the runtime is hammered by short-lived connections from client threads.
+//!
+//! Run it with `cargo bench -p little-tokio --bench connections`.
+//!
+//! The baseline, measured as the median of three runs on a single-core Intel Xeon virtual machine running Linux
+//! 6.18, is 4000 connections in 192ms, i.e., about 20800 connections/sec and 170 MB/sec (170331316 bytes/sec).
+
+use little_tokio::net::tcp::{Listener, Stream};
+use std::io::{Read as _, Write as _};
+use std::{net, sync, thread, time};
+
+/// The number of client threads connecting to the server concurrently.
+const CLIENTS: usize = 8;
+/// The number of short-lived connections each client thread makes.
+const CONNECTIONS_PER_CLIENT: usize = 500;
+/// The size of the payload each connection sends and receives back.
+const PAYLOAD_SIZE: usize = 4096;
+
+fn main() {
+    let total = CLIENTS * CONNECTIONS_PER_CLIENT;
+    let (addr_tx, addr_rx) = sync::mpsc::channel();
+    let clients = thread::spawn(move || {
+        let addr: net::SocketAddr = addr_rx.recv().unwrap();
+        let handles: Vec<_> = (0..CLIENTS)
+            .map(|_| thread::spawn(move || run_client(addr)))
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    });
+    let started = time::Instant::now();
+    little_tokio::block_on(async move {
+        let mut listener = Listener::bind("127.0.0.1:0").unwrap();
+        addr_tx.send(listener.local_addr().unwrap()).unwrap();
+        for _ in 0..total {
+            let (connection, _) = listener.accept().await.unwrap();
+            little_tokio::spawn(handle(connection));
+        }
+    });
+    let elapsed = started.elapsed();
+    clients.join().unwrap();
+    let seconds = elapsed.as_secs_f64();
+    println!(
+        "{} connections in {:?}: {:.0} connections/sec, {:.0} bytes/sec",
+        total,
+        elapsed,
+        total as f64 / seconds,
+        (total * PAYLOAD_SIZE * 2) as f64 / seconds,
+    );
+}
+
+/// Makes `CONNECTIONS_PER_CLIENT` short-lived connections to the server at `addr`, each of which sends the payload
+/// and waits until it is echoed back.
+fn run_client(addr: net::SocketAddr) {
+    let payload = [0x2au8; PAYLOAD_SIZE];
+    let mut echoed = [0u8; PAYLOAD_SIZE];
+    for _ in 0..CONNECTIONS_PER_CLIENT {
+        let mut connection = net::TcpStream::connect(addr).unwrap();
+        connection.write_all(&payload).unwrap();
+        connection.read_exact(&mut echoed).unwrap();
+    }
+}
+
+/// Echoes back everything received from the `connection` until the peer closes it.
+async fn handle(mut connection: Stream) {
+    let mut buffer = [0u8; 1024];
+    while let Ok(count) = connection.read(&mut buffer).await {
+        if count == 0 {
+            return;
+        }
+        let mut written = 0;
+        while written < count {
+            match connection.write(&buffer[written..count]).await {
+                Ok(size) => written += size,
+                Err(_) => return,
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This file contains a benchmark measuring the tasks the Little Tokio runtime spawns and runs per second, where
+//! every task is trivially ready, i.e., completes on its first poll.
+//!
+//! Run it with `cargo bench -p little-tokio --bench spawn`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{sync, time};
+
+/// The number of trivially ready tasks spawned.
+const TASKS: usize = 200_000;
+
+fn main() {
+    let completed = sync::Arc::new(AtomicUsize::new(0));
+    let counter = completed.clone();
+    let started = time::Instant::now();
+    little_tokio::block_on(async move {
+        for _ in 0..TASKS {
+            let counter = counter.clone();
+            little_tokio::spawn(async move {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+    });
+    let elapsed = started.elapsed();
+    assert_eq!(completed.load(Ordering::Relaxed), TASKS);
+    println!(
+        "{} tasks in {:?}: {:.0} tasks/sec",
+        TASKS,
+        elapsed,
+        TASKS as f64 / elapsed.as_secs_f64(),
+    );
+}
@@ -0,0 +1,73 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the framing of the Little Tokio runtime, which turns a byte stream into a `Stream` of
+//! frames and a `Sink` of frames, so that a protocol only has to tell how a frame is laid out in bytes.
+//!
+//! A protocol implements `Codec`, and `Framed` drives it over any `AsyncRead`/`AsyncWrite`, e.g., a line-delimited
+//! echo over an in-memory duplex stream:
+//!
+//! ```
+//! use little_tokio::codec::{Framed, LinesCodec};
+//! use little_tokio::sink::SinkExt;
+//! use little_tokio::stream::StreamExt;
+//!
+//! little_tokio::block_on(async {
+//!     let (one, two) = little_tokio::io::duplex(64);
+//!     let mut one = Framed::new(one, LinesCodec::new());
+//!     let mut two = Framed::new(two, LinesCodec::new());
+//!     one.send("hello".to_string()).await.unwrap();
+//!     assert_eq!(two.next().await.unwrap().unwrap(), "hello");
+//! });
+//! ```
+
+mod framed;
+mod lines;
+
+pub use self::framed::Framed;
+pub use self::lines::LinesCodec;
+
+use std::io;
+
+/// Tells how the frames of a protocol are laid out in bytes, i.e., decodes the frames from the bytes read so far and
+/// encodes the frames into the bytes to write.
+pub trait Codec {
+    /// Represents the frames of the protocol.
+    type Frame;
+
+    /// Decodes the next frame from the front of the given `buffer`, removing the bytes of the frame from it, or
+    /// returns `None` if the `buffer` does not hold a complete frame yet, in which case `decode` is called again
+    /// once more bytes have been appended.
+    fn decode(&mut self, buffer: &mut Vec<u8>) -> io::Result<Option<Self::Frame>>;
+
+    /// Decodes the next frame once the source has read the EOF, i.e., no more bytes are going to be appended to the
+    /// given `buffer`. Returning `None` ends the stream of frames.
+    ///
+    /// # Note:
+    /// The default implementation calls `decode`, and fails with `io::ErrorKind::UnexpectedEof` if the bytes left
+    /// in the `buffer` do not make a complete frame.
+    fn decode_eof(&mut self, buffer: &mut Vec<u8>) -> io::Result<Option<Self::Frame>> {
+        match self.decode(buffer)? {
+            Some(frame) => Ok(Some(frame)),
+            None if buffer.is_empty() => Ok(None),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "bytes remaining on the stream",
+            )),
+        }
+    }
+
+    /// Encodes the given `frame`, appending its bytes to the given `buffer`.
+    fn encode(&mut self, frame: Self::Frame, buffer: &mut Vec<u8>) -> io::Result<()>;
+}
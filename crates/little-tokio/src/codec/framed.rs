@@ -0,0 +1,320 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of `Framed`, which drives a `Codec` over a byte stream.
+
+use crate::codec::Codec;
+use crate::io::{AsyncRead, AsyncWrite};
+use crate::sink::Sink;
+use crate::stream::Stream;
+use std::{fmt, io, pin, task};
+
+/// The number of bytes read from the inner stream at once.
+const READ_CHUNK: usize = 8 * 1024;
+
+/// The number of the encoded bytes which makes `poll_ready` write them into the inner stream before accepting
+/// another frame.
+const BACKPRESSURE_BOUNDARY: usize = 8 * 1024;
+
+/// Represents a byte stream framed by a `Codec`, which is a `Stream` of the frames decoded from the bytes read and a
+/// `Sink` of the frames encoded into the bytes written.
+///
+/// # Note:
+/// The bytes read but not decoded yet, and the bytes encoded but not written yet, are kept in the buffers of
+/// `Framed`, i.e., they are lost if it gets dropped; close the `Sink` before dropping it. The read buffer grows
+/// until the codec decodes a frame, i.e., a codec bounding the length of its frames, e.g.,
+/// `LinesCodec::with_max_length`, is what bounds the memory a peer never completing a frame can take.
+pub struct Framed<T, C> {
+    /// Holds the inner byte stream.
+    inner: T,
+    /// Holds the codec of the frames.
+    codec: C,
+    /// Holds the bytes read but not decoded yet.
+    read_buffer: Vec<u8>,
+    /// Holds the bytes encoded but not written yet.
+    write_buffer: Vec<u8>,
+    /// Specifies whether `read_buffer` may hold a frame which has not been decoded yet.
+    decodable: bool,
+    /// Specifies whether the inner stream has read the EOF.
+    eof: bool,
+}
+
+impl<T, C> Framed<T, C> {
+    /// Creates a new `Framed` which drives the given `codec` over the given `inner` byte stream.
+    pub fn new(inner: T, codec: C) -> Self {
+        Self {
+            inner,
+            codec,
+            read_buffer: Vec::new(),
+            write_buffer: Vec::new(),
+            decodable: false,
+            eof: false,
+        }
+    }
+
+    /// Returns the reference to the inner byte stream.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the mutable reference to the inner byte stream.
+    ///
+    /// # Note:
+    /// Reading from or writing into the inner byte stream directly bypasses the buffered bytes.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Returns the reference to the codec.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Returns the mutable reference to the codec.
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Returns the bytes read but not decoded yet.
+    pub fn read_buffer(&self) -> &[u8] {
+        &self.read_buffer
+    }
+
+    /// Consumes this `Framed`, returning the inner byte stream and the codec.
+    ///
+    /// # Note:
+    /// The bytes read but not decoded yet, and the bytes encoded but not written yet, are lost.
+    pub fn into_parts(self) -> (T, C) {
+        (self.inner, self.codec)
+    }
+}
+
+impl<T, C> Framed<T, C>
+where
+    T: AsyncWrite + Unpin,
+{
+    /// Writes the encoded bytes into the inner byte stream until the write buffer gets empty.
+    fn poll_write_buffer(&mut self, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+        while !self.write_buffer.is_empty() {
+            match pin::Pin::new(&mut self.inner).poll_write(cx, &self.write_buffer) {
+                task::Poll::Ready(Ok(0)) => {
+                    return task::Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the frame",
+                    )))
+                }
+                task::Poll::Ready(Ok(size)) => {
+                    self.write_buffer.drain(..size);
+                }
+                task::Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+        }
+        task::Poll::Ready(Ok(()))
+    }
+}
+
+impl<T, C> Stream for Framed<T, C>
+where
+    T: AsyncRead + Unpin,
+    C: Codec + Unpin,
+{
+    type Item = io::Result<C::Frame>;
+
+    fn poll_next(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.eof {
+                return task::Poll::Ready(this.codec.decode_eof(&mut this.read_buffer).transpose());
+            }
+            if this.decodable {
+                match this.codec.decode(&mut this.read_buffer) {
+                    Ok(Some(frame)) => return task::Poll::Ready(Some(Ok(frame))),
+                    Ok(None) => this.decodable = false,
+                    Err(e) => return task::Poll::Ready(Some(Err(e))),
+                }
+            }
+            let filled = this.read_buffer.len();
+            this.read_buffer.resize(filled + READ_CHUNK, 0);
+            let poll =
+                pin::Pin::new(&mut this.inner).poll_read(cx, &mut this.read_buffer[filled..]);
+            let size = match poll {
+                task::Poll::Ready(Ok(size)) => size,
+                _ => 0,
+            };
+            this.read_buffer.truncate(filled + size);
+            match poll {
+                task::Poll::Ready(Ok(0)) => this.eof = true,
+                task::Poll::Ready(Ok(_)) => this.decodable = true,
+                task::Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                task::Poll::Ready(Err(e)) => return task::Poll::Ready(Some(Err(e))),
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<T, C> Sink<C::Frame> for Framed<T, C>
+where
+    T: AsyncWrite + Unpin,
+    C: Codec + Unpin,
+{
+    type Error = io::Error;
+
+    fn poll_ready(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if this.write_buffer.len() >= BACKPRESSURE_BOUNDARY {
+            return this.poll_write_buffer(cx);
+        }
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: pin::Pin<&mut Self>, frame: C::Frame) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        this.codec.encode(frame, &mut this.write_buffer)
+    }
+
+    fn poll_flush(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        match this.poll_write_buffer(cx) {
+            task::Poll::Ready(Ok(())) => pin::Pin::new(&mut this.inner).poll_flush(cx),
+            poll => poll,
+        }
+    }
+
+    fn poll_close(
+        mut self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        match self.as_mut().poll_flush(cx) {
+            task::Poll::Ready(Ok(())) => pin::Pin::new(&mut self.get_mut().inner).poll_shutdown(cx),
+            poll => poll,
+        }
+    }
+}
+
+impl<T, C> fmt::Debug for Framed<T, C>
+where
+    T: fmt::Debug,
+    C: fmt::Debug,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "Framed {{ inner: {:?}, codec: {:?}, read: {}, write: {} }}",
+            self.inner,
+            self.codec,
+            self.read_buffer.len(),
+            self.write_buffer.len()
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::LinesCodec;
+    use crate::net::tcp;
+    use crate::sink::SinkExt;
+    use crate::stream::StreamExt;
+    use std::future;
+
+    #[test]
+    fn frames_round_trip_over_a_loopback_connection() {
+        let _serial = crate::tests::serial();
+        let ret = crate::block_on_timeout(
+            async {
+                let mut listener = tcp::Listener::bind("127.0.0.1:0").unwrap();
+                let addr = listener.local_addr().unwrap();
+                let server = crate::spawn_with_output(async move {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let mut framed = Framed::new(stream, LinesCodec::new());
+                    while let Some(line) = framed.next().await {
+                        framed.send(line.unwrap().to_uppercase()).await.unwrap();
+                    }
+                });
+                let stream = tcp::Stream::connect(addr).await.unwrap();
+                let mut framed = Framed::new(stream, LinesCodec::new());
+                for line in ["hello", "", "framed world"] {
+                    framed.send(line.to_string()).await.unwrap();
+                    let echoed = framed.next().await.unwrap().unwrap();
+                    assert_eq!(echoed, line.to_uppercase());
+                }
+                future::poll_fn(|cx| pin::Pin::new(&mut framed).poll_close(cx))
+                    .await
+                    .unwrap();
+                assert!(framed.next().await.is_none());
+                server.await.unwrap();
+            },
+            std::time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+    }
+
+    #[test]
+    fn huge_frames_fail_with_the_length_error() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let (mut one, two) = crate::io::duplex(4096);
+            crate::spawn(async move {
+                let payload = vec![b'a'; 1024];
+                while one.write(&payload).await.is_ok() {}
+            });
+            let mut reader = Framed::new(two, LinesCodec::with_max_length(64 * 1024));
+            let e = reader.next().await.unwrap().unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(e.to_string(), "line too long");
+            assert!(reader.read_buffer().len() <= 64 * 1024 + READ_CHUNK);
+        });
+    }
+
+    #[test]
+    fn frames_split_across_reads_are_reassembled() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            // Note:
+            // The duplex buffer is smaller than the frames, so that each frame arrives in several reads.
+            let (one, two) = crate::io::duplex(3);
+            let mut writer = Framed::new(one, LinesCodec::new());
+            let mut reader = Framed::new(two, LinesCodec::new());
+            let sent = vec!["a line longer than the duplex buffer".to_string(); 10];
+            let expected = sent.clone();
+            let sender = crate::spawn_with_output(async move {
+                for line in sent {
+                    writer.send(line).await.unwrap();
+                }
+                future::poll_fn(|cx| pin::Pin::new(&mut writer).poll_close(cx))
+                    .await
+                    .unwrap();
+            });
+            let mut received = Vec::new();
+            while let Some(line) = reader.next().await {
+                received.push(line.unwrap());
+            }
+            sender.await.unwrap();
+            assert_eq!(received, expected);
+        });
+    }
+}
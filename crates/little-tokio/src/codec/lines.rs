@@ -0,0 +1,209 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of a codec of line-delimited frames.
+
+use crate::codec::Codec;
+use std::io;
+
+/// Represents a `Codec` of the frames delimited by a newline, i.e., `b'\n'`, where a frame is the line without the
+/// newline, nor the carriage return preceding it if any. Encoding appends a newline to the frame.
+#[derive(Debug)]
+pub struct LinesCodec {
+    /// Holds the number of the buffered bytes already searched for a newline.
+    searched: usize,
+    /// Holds the maximal length of a line, not counting the newline.
+    max_length: usize,
+}
+
+impl LinesCodec {
+    /// Creates a new `LinesCodec` decoding the lines of any length.
+    ///
+    /// # Note:
+    /// A peer never sending a newline makes the decoder buffer the bytes without bound; use `with_max_length` for
+    /// the peers not trusted.
+    pub fn new() -> Self {
+        Self::with_max_length(usize::MAX)
+    }
+
+    /// Creates a new `LinesCodec` which fails to decode with `io::ErrorKind::InvalidData` once a line, not counting
+    /// the newline, gets longer than the given `max_length`, e.g., once more than `max_length` bytes have been
+    /// buffered without a newline.
+    pub fn with_max_length(max_length: usize) -> Self {
+        Self {
+            searched: 0,
+            max_length,
+        }
+    }
+
+    /// Returns the maximal length of a line, not counting the newline.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Removes the first `size` bytes from the given `buffer` and returns them as a line without the trailing
+    /// newline and carriage return.
+    fn take_line(&mut self, buffer: &mut Vec<u8>, size: usize) -> io::Result<String> {
+        let mut line: Vec<u8> = buffer.drain(..size).collect();
+        self.searched = 0;
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        String::from_utf8(line).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            )
+        })
+    }
+}
+
+impl Default for LinesCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the error telling that a line is longer than the maximal length.
+fn too_long() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "line too long")
+}
+
+impl Codec for LinesCodec {
+    type Frame = String;
+
+    fn decode(&mut self, buffer: &mut Vec<u8>) -> io::Result<Option<Self::Frame>> {
+        // Note:
+        // Only the bytes appended since the last call are searched, so that a long line arriving in small chunks is
+        // not searched over and over again.
+        match buffer[self.searched..].iter().position(|&b| b == b'\n') {
+            Some(index) if self.searched + index > self.max_length => Err(too_long()),
+            Some(index) => {
+                let size = self.searched + index + 1;
+                self.take_line(buffer, size).map(Some)
+            }
+            None if buffer.len() > self.max_length => Err(too_long()),
+            None => {
+                self.searched = buffer.len();
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, buffer: &mut Vec<u8>) -> io::Result<Option<Self::Frame>> {
+        match self.decode(buffer)? {
+            Some(line) => Ok(Some(line)),
+            None if buffer.is_empty() => Ok(None),
+            None => self.take_line(buffer, buffer.len()).map(Some),
+        }
+    }
+
+    fn encode(&mut self, frame: Self::Frame, buffer: &mut Vec<u8>) -> io::Result<()> {
+        buffer.reserve(frame.len() + 1);
+        buffer.extend_from_slice(frame.as_bytes());
+        buffer.push(b'\n');
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_waits_for_a_complete_line() {
+        let mut codec = LinesCodec::new();
+        let mut buffer = b"hel".to_vec();
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        buffer.extend_from_slice(b"lo\r\nwor");
+        assert_eq!(codec.decode(&mut buffer).unwrap().unwrap(), "hello");
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        buffer.extend_from_slice(b"ld\n\n");
+        assert_eq!(codec.decode(&mut buffer).unwrap().unwrap(), "world");
+        assert_eq!(codec.decode(&mut buffer).unwrap().unwrap(), "");
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn decode_eof_yields_the_unterminated_line() {
+        let mut codec = LinesCodec::new();
+        let mut buffer = b"last".to_vec();
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert_eq!(codec.decode_eof(&mut buffer).unwrap().unwrap(), "last");
+        assert_eq!(codec.decode_eof(&mut buffer).unwrap(), None);
+    }
+
+    #[test]
+    fn decode_rejects_invalid_utf8() {
+        let mut codec = LinesCodec::new();
+        let mut buffer = b"\xff\xfe\n".to_vec();
+        let e = codec.decode(&mut buffer).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decode_enforces_the_max_length() {
+        let mut codec = LinesCodec::with_max_length(4);
+        let mut buffer = b"1234\n".to_vec();
+        assert_eq!(codec.decode(&mut buffer).unwrap().unwrap(), "1234");
+        buffer.extend_from_slice(b"12345\n");
+        let e = codec.decode(&mut buffer).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(e.to_string(), "line too long");
+    }
+
+    #[test]
+    fn decode_fails_on_a_huge_payload_without_a_newline() {
+        let mut codec = LinesCodec::with_max_length(1024);
+        let mut buffer = Vec::new();
+        for _ in 0..1024 {
+            buffer.extend_from_slice(&[b'a'; 1024]);
+            match codec.decode(&mut buffer) {
+                Ok(None) => continue,
+                Ok(Some(line)) => panic!("unexpected line of {} bytes", line.len()),
+                Err(e) => {
+                    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+                    assert!(buffer.len() <= 2 * 1024);
+                    return;
+                }
+            }
+        }
+        panic!("decoder should have failed once the line exceeded the max length");
+    }
+
+    #[test]
+    fn encode_appends_a_newline() {
+        let mut codec = LinesCodec::new();
+        let mut buffer = Vec::new();
+        codec.encode("one".to_string(), &mut buffer).unwrap();
+        codec.encode("two".to_string(), &mut buffer).unwrap();
+        assert_eq!(buffer, b"one\ntwo\n");
+    }
+
+    #[test]
+    fn decode_keeps_a_character_split_across_reads() {
+        let mut codec = LinesCodec::new();
+        let encoded = "naïve\n".as_bytes();
+        // Note:
+        // The first chunk ends in the middle of the two-byte `ï`, which is not valid UTF-8 on its own.
+        let mut buffer = encoded[..3].to_vec();
+        assert!(std::str::from_utf8(&buffer).is_err());
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        buffer.extend_from_slice(&encoded[3..]);
+        assert_eq!(codec.decode(&mut buffer).unwrap().unwrap(), "naïve");
+    }
+}
@@ -15,41 +15,59 @@
 //! This module contains the implementation of a `Interest` which represents the interest of
 //! in either `Read` or `Write` events.
 
-use std::{fmt, num, ops};
+use std::{fmt, ops};
 
 /// Represents interest in either Read or Write events. This struct is created by using one of
-/// the two constants:
+/// the three constants:
 ///
+/// - Interest::NONE
 /// - Interest::READABLE
 /// - Interest::WRITABLE
+///
+/// The empty `Interest::NONE` expresses that a file descriptor is not monitored for the moment, e.g., while the
+/// reads from it are suspended for backpressure. Registering it into a `Selector` does nothing.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub(crate) struct Interest(num::NonZeroU8);
+pub(crate) struct Interest(u8);
 
 const READABLE: u8 = 0b0001;
 
 const WRITABLE: u8 = 0b0010;
 
 impl Interest {
+    /// Returns a `Interest` set representing no interests.
+    pub const NONE: Interest = Interest(0);
+
     /// Returns a `Interest` set representing readable interests.
-    pub const READABLE: Interest = Interest(unsafe { num::NonZeroU8::new_unchecked(READABLE) });
+    pub const READABLE: Interest = Interest(READABLE);
 
     /// Returns a `Interest` set representing writable interests.
-    pub const WRITABLE: Interest = Interest(unsafe { num::NonZeroU8::new_unchecked(WRITABLE) });
+    pub const WRITABLE: Interest = Interest(WRITABLE);
 
     /// Adds together two `Interest`. This does the same thing as the `BitOr` implementation, but is a
     /// constant function.
     pub const fn add(self, other: Interest) -> Interest {
-        Interest(unsafe { num::NonZeroU8::new_unchecked(self.0.get() | other.0.get()) })
+        Interest(self.0 | other.0)
+    }
+
+    /// Removes `other` `Interest` from `self`. Returns `Interest::NONE` if the set is empty after
+    /// removing `other`.
+    pub fn remove(self, other: Interest) -> Interest {
+        Interest(self.0 & !other.0)
+    }
+
+    /// Returns true if the value includes no readiness.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
     }
 
     /// Returns true if the value includes readable readiness.
     pub fn is_readable(self) -> bool {
-        (self.0.get() & READABLE) != 0
+        (self.0 & READABLE) != 0
     }
 
     /// Returns true if the value includes writable readiness.
     pub fn is_writable(self) -> bool {
-        (self.0.get() & WRITABLE) != 0
+        (self.0 & WRITABLE) != 0
     }
 }
 
@@ -71,6 +89,9 @@ impl ops::BitOrAssign for Interest {
 
 impl fmt::Debug for Interest {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(fmt, "NONE");
+        }
         let mut is_flagged = false;
         if self.is_readable() {
             if is_flagged {
@@ -84,9 +105,25 @@ impl fmt::Debug for Interest {
                 write!(fmt, " | ")?
             }
             write!(fmt, "WRITABLE")?;
-            is_flagged = true
         }
-        debug_assert!(is_flagged, "printing empty interests");
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removing_every_interest_leaves_the_empty_set() {
+        let both = Interest::READABLE | Interest::WRITABLE;
+        assert_eq!(both.remove(Interest::READABLE), Interest::WRITABLE);
+        let none = both.remove(Interest::READABLE).remove(Interest::WRITABLE);
+        assert_eq!(none, Interest::NONE);
+        assert!(none.is_empty());
+        assert!(!none.is_readable() && !none.is_writable());
+        assert_eq!(none | Interest::READABLE, Interest::READABLE);
+        assert_eq!(format!("{:?}", none), "NONE");
+        assert_eq!(format!("{:?}", both), "READABLE | WRITABLE");
+    }
+}
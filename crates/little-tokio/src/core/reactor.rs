@@ -15,11 +15,15 @@
 //! This module contains the implementation of a single threaded `Future` reactor.
 
 use crate::core::interest::Interest;
+use crate::core::registration::Registration;
 use crate::core::token::Token;
-use crate::sys::unix::kqueue::Events;
-use crate::sys::unix::kqueue::Selector;
+use crate::sys::unix::{Events, Selector};
 use once_cell::sync::Lazy;
-use std::{collections, io, os, sync, task};
+use std::{collections, io, os, sync, task, time};
+
+/// The number of turns between the audits of the registrations.
+#[cfg(feature = "trace")]
+const AUDIT_INTERVAL: usize = 1024;
 
 /// Provides the interface to access a `Reactor` singleton instance. Since the runtime is
 /// designed solely for single-threaded environments, all access to the runtime needs to occur
@@ -39,123 +43,713 @@ impl Singleton {
     }
 }
 
+/// Represents the number of live `Registration`s per `Interest` of a file descriptor, which allows several
+/// `Registration`s to share the same file descriptor without tearing down each other's monitoring.
+#[derive(Default, Clone, Copy)]
+struct Registered {
+    /// Holds the number of live `Registration`s including readable interests.
+    readable: usize,
+    /// Holds the number of live `Registration`s including writable interests.
+    writable: usize,
+}
+
+impl Registered {
+    /// Returns the union of the `Interest`s currently registered, or `Interest::NONE` if there are no
+    /// registrations.
+    fn interest(&self) -> Interest {
+        let mut interest = Interest::NONE;
+        if self.readable > 0 {
+            interest |= Interest::READABLE;
+        }
+        if self.writable > 0 {
+            interest |= Interest::WRITABLE;
+        }
+        interest
+    }
+
+    /// Counts up the registrations specified by the `interest`.
+    fn increment(&mut self, interest: Interest) {
+        if interest.is_readable() {
+            self.readable += 1;
+        }
+        if interest.is_writable() {
+            self.writable += 1;
+        }
+    }
+
+    /// Counts down the registrations specified by the `interest`.
+    fn decrement(&mut self, interest: Interest) {
+        if interest.is_readable() {
+            self.readable = self.readable.saturating_sub(1);
+        }
+        if interest.is_writable() {
+            self.writable = self.writable.saturating_sub(1);
+        }
+    }
+}
+
+/// Represents the wakers of the tasks blocked on a file descriptor per `Interest`, so that a task waiting for the
+/// file descriptor to become readable and another task waiting for it to become writable do not clobber each
+/// other's wakers.
+struct Blocked {
+    /// Holds the waker of the task waiting for the file descriptor to become readable.
+    readable: Option<task::Waker>,
+    /// Holds the waker of the task waiting for the file descriptor to become writable.
+    writable: Option<task::Waker>,
+    /// Holds the `Interest`s the file descriptor has been signaled ready for since the tasks blocked on them.
+    ready: Interest,
+}
+
+impl Default for Blocked {
+    fn default() -> Self {
+        Self {
+            readable: None,
+            writable: None,
+            ready: Interest::NONE,
+        }
+    }
+}
+
+impl Blocked {
+    /// Sets up the given `waker` for the given `interest`, clearing the readiness signaled for the `interest` so
+    /// far.
+    fn insert(&mut self, interest: Interest, waker: task::Waker) {
+        self.ready = self.ready.remove(interest);
+        if interest.is_readable() {
+            self.readable = Some(waker.clone());
+        }
+        if interest.is_writable() {
+            self.writable = Some(waker);
+        }
+    }
+
+    /// Returns `true` if the file descriptor has been signaled ready for any of the given `interest` since the tasks
+    /// blocked on it.
+    fn is_ready(&self, interest: Interest) -> bool {
+        interest.is_readable() && self.ready.is_readable()
+            || interest.is_writable() && self.ready.is_writable()
+    }
+
+    /// Wakes up the tasks waiting for the given `interest`, marking the file descriptor as ready for it. A task
+    /// waiting for both of the `Interest`s is woken up only once.
+    fn wake(&mut self, interest: Interest) {
+        self.ready |= interest;
+        let readable = self.readable.as_ref().filter(|_| interest.is_readable());
+        let writable = self.writable.as_ref().filter(|_| interest.is_writable());
+        if let Some(waker) = readable {
+            waker.wake_by_ref();
+        }
+        match (readable, writable) {
+            (Some(readable), Some(writable)) if readable.will_wake(writable) => {}
+            (_, Some(writable)) => writable.wake_by_ref(),
+            (_, None) => {}
+        }
+    }
+}
+
+/// Represents a listener waiting for a signal.
+#[derive(Default)]
+struct Listener {
+    /// Specifies whether the signal has been delivered since the listener has been registered.
+    delivered: bool,
+    /// Holds the waker of the task waiting for the signal.
+    waker: Option<task::Waker>,
+}
+
 /// The Little Tokio reactor which is responsible for I/O multiplexing.
 #[derive(Default)]
 pub(crate) struct Reactor {
-    /// Holds the `libc::kqueue` based IO demultiplexer.
-    selector: Selector,
-    /// Holds the correspondence between blocked file descriptors' tokens and their corresponding wakers, which
-    /// the runtime utilizes to wake up tasks.
-    blocked_fds: collections::HashMap<Token, task::Waker>,
+    /// Holds the `libc::kqueue` or `libc::epoll` based IO demultiplexer, which is shared with the turn of the event
+    /// loop waiting for the next events without locking the singleton instance.
+    selector: sync::Arc<Selector>,
+    /// Holds the correspondence between blocked file descriptors' tokens and their corresponding wakers per
+    /// `Interest`, which the runtime utilizes to wake up tasks.
+    blocked_fds: collections::HashMap<Token, Blocked>,
+    /// Holds the correspondence between pending timers' tokens and the wakers of the tasks waiting for them.
+    timers: collections::HashMap<Token, task::Waker>,
+    /// Holds the identifier which will be assigned to the next timer.
+    next_timer: u32,
+    /// Holds the correspondence between registered signals' tokens and the listeners waiting for them, keyed by the
+    /// listeners' identifiers.
+    signals: collections::HashMap<Token, collections::HashMap<u64, Listener>>,
+    /// Holds the identifier which will be assigned to the next signal listener.
+    next_listener: u64,
+    /// Holds the correspondence between registered file descriptors' tokens and their live registrations, so
+    /// that deregistration only removes what is no longer registered by anyone.
+    registrations: collections::HashMap<Token, Registered>,
+    /// Holds the correspondence between file descriptors' tokens and their current generations, which get
+    /// incremented whenever the file descriptors are entirely deregistered, so that stale events are not delivered
+    /// to the tasks using the file descriptors reusing the same numbers.
+    generations: collections::HashMap<Token, u32>,
+    /// Holds the maximal number of events processed per turn, or `None` if the number is unbounded.
+    event_budget: Option<usize>,
+    /// Holds the tokens of the events, together with the `Interest`s they are ready for, which have been selected
+    /// but not processed yet due to the `event_budget`.
+    carried_events: collections::VecDeque<(Token, Interest)>,
+    /// Holds the buffer of the selected events, which is taken out while waiting for the next events and put back
+    /// afterwards, so that the buffer is allocated only once rather than on every turn.
+    events: Option<Events>,
+    /// Holds the number of turns performed so far, which is utilized to audit the registrations periodically.
+    #[cfg(feature = "trace")]
+    turns: usize,
 }
 
 impl Reactor {
-    /// Performs one iteration of the I/O event loop.
+    /// Tries to perform one iteration of the I/O event loop, waiting for the next events at most for the given
+    /// `timeout`, or indefinitely if the `timeout` is `None`. An interruption by a signal is not an error but an
+    /// iteration without any events.
+    pub(crate) fn try_turn(timeout: Option<time::Duration>) -> io::Result<()> {
+        // Note:
+        // The singleton instance is unlocked while waiting for the next events, so that the other threads can wake
+        // up the `selector` via `wake` in the meantime.
+        let (selector, events) = {
+            let mut reactor = Singleton::instance();
+            (reactor.get_selector_to_wait(), reactor.events.take())
+        };
+        let mut events = events.unwrap_or_default();
+        events.clear();
+        if let Some(selector) = selector {
+            match selector.try_select(&mut events, timeout) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => events.clear(),
+                Err(e) => return Err(e),
+            }
+        }
+        let mut reactor = Singleton::instance();
+        reactor.do_turn(&events);
+        reactor.events = Some(events);
+        Ok(())
+    }
+
+    /// Wakes up the reactor waiting for the next events, which may be called from any thread.
+    pub(crate) fn wake() {
+        let selector = sync::Arc::clone(&Singleton::instance().selector);
+        selector
+            .try_wake()
+            .expect("should wake up the selector properly")
+    }
+
+    /// Sets the maximal number of events processed per turn. The remaining events are carried over to the next
+    /// turn, so that the runtime can interleave the task execution with the event processing.
+    pub(crate) fn set_event_budget(budget: Option<usize>) {
+        Singleton::instance().event_budget = budget;
+    }
+
+    /// Tries to register the given `fd` into the `selector` to monitor IO events, which is specified by the
+    /// `interest`, and returns the `Registration` which deregisters the `fd` when it gets dropped.
     ///
     /// # Note:
-    /// We should provide a proper error handling here, e.g., implementing a `Turn` structure which is responsible
+    /// We should provide a proper error handling here, e.g., implementing a `Registry` structure which is responsible
     /// for recovering, but this is an educational purpose implementation so that conducting over-engineering
     /// was avoided.
-    pub(crate) fn turn() {
+    pub(crate) fn register<Fd>(fd: &Fd, interest: Interest) -> Registration
+    where
+        Fd: os::fd::AsRawFd,
+    {
+        #[cfg(feature = "trace")]
+        crate::trace::event(format_args!(
+            "register fd {} for {:?}",
+            fd.as_raw_fd(),
+            interest
+        ));
         Singleton::instance()
-            .try_turn()
-            .expect("should turn the event loop properly")
+            .try_register(fd.as_raw_fd(), interest)
+            .expect("should register the given file descriptor properly");
+        Registration::new(fd.as_raw_fd(), interest)
     }
 
-    /// Tries to register the given `fd` into the `selector` to monitor IO events, which is specified by the
-    /// `interest`.
+    /// Tries to change the `Interest` of the given `fd` registered in the `selector` from `from` into `to`.
     ///
     /// # Note:
     /// We should provide a proper error handling here, e.g., implementing a `Registry` structure which is responsible
     /// for recovering, but this is an educational purpose implementation so that conducting over-engineering
     /// was avoided.
-    pub(crate) fn register<Fd>(fd: &Fd, interest: Interest)
-    where
-        Fd: os::fd::AsFd + os::fd::AsRawFd,
-    {
-        Singleton::instance()
-            .try_register(fd, interest)
-            .expect("should register the given file descriptor properly")
+    pub(crate) fn reregister(fd: os::fd::RawFd, from: Interest, to: Interest) {
+        let mut reactor = Singleton::instance();
+        reactor
+            .try_register(fd, to)
+            .expect("should register the given file descriptor properly");
+        reactor
+            .try_deregister(fd, from)
+            .expect("should deregister the given file descriptor properly")
     }
 
-    /// Tries to deregister the given `fd` from the `selector`.
+    /// Tries to deregister the given `fd` from the `selector` to stop monitoring IO events, which is specified by
+    /// the `interest`.
     ///
     /// # Note:
     /// We should provide a proper error handling here, e.g., implementing a `Registry` structure which is responsible
     /// for recovering, but this is an educational purpose implementation so that conducting over-engineering
     /// was avoided.
-    pub(crate) fn deregister<Fd>(fd: &Fd)
-    where
-        Fd: os::fd::AsFd + os::fd::AsRawFd,
-    {
+    pub(crate) fn deregister(fd: os::fd::RawFd, interest: Interest) {
         Singleton::instance()
-            .try_deregister(fd)
+            .try_deregister(fd, interest)
             .expect("should deregister the given file descriptor properly")
     }
 
-    /// Blocks when the given `fd` is not ready to use yet and setup the given `waker` to wake up the corresponding
-    /// downstream task to poll later.
-    pub(crate) fn block<Fd>(fd: &Fd, waker: task::Waker)
-    where
-        Fd: os::fd::AsFd + os::fd::AsRawFd,
-    {
-        Singleton::instance().do_block(fd, waker);
+    /// Returns `true` if the reactor has anything which may wake up a task, i.e., registered file descriptors,
+    /// pending timers, listened signals, or the events carried over from the previous turn.
+    pub(crate) fn has_registrations() -> bool {
+        Singleton::instance().get_has_registrations()
     }
-}
 
-impl Reactor {
-    /// Performs one iteration of the I/O event loop.
+    /// Returns the current generation of the given `fd`, i.e., the number of times it has been entirely
+    /// deregistered.
+    #[cfg(test)]
+    pub(crate) fn generation(fd: os::fd::RawFd) -> u32 {
+        Singleton::instance()
+            .generations
+            .get(&fd.into())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Resets the reactor into the initial state, dropping all the registrations and wakers and re-creating the
+    /// `selector`. The configuration set up by `runtime::Builder`, i.e., the event budget, is kept.
+    pub(crate) fn reset() {
+        let reactor = {
+            let mut reactor = Singleton::instance();
+            let initial = Self {
+                event_budget: reactor.event_budget,
+                ..Self::default()
+            };
+            std::mem::replace(&mut *reactor, initial)
+        };
+        drop(reactor);
+    }
+
+    /// Audits the registrations and returns the number of stale ones, i.e., the registrations whose file descriptors
+    /// have been closed without being deregistered. Each stale registration is reported as a warning.
+    ///
+    /// # Note:
+    /// A stale registration is typically left by a custom `Future` which registers a file descriptor but forgets to
+    /// drop its `Registration` before closing the file descriptor.
+    #[cfg(feature = "trace")]
+    pub(crate) fn audit_registrations() -> usize {
+        Singleton::instance().do_audit_registrations()
+    }
+
+    /// Tries to register a one-shot timer into the `selector`, which wakes up the given `waker` once the given
+    /// `duration` elapses, and returns the `Token` identifying the timer.
     ///
     /// # Note:
-    /// We should provide a proper error handling here, e.g., implementing a `Turn` structure which is responsible
+    /// We should provide a proper error handling here, e.g., implementing a `Registry` structure which is responsible
     /// for recovering, but this is an educational purpose implementation so that conducting over-engineering
     /// was avoided.
-    fn try_turn(&mut self) -> io::Result<()> {
-        let mut events = Events::default();
-        self.selector.try_select(&mut events, None)?;
-        for event in events.iter() {
-            if let Some(waker) = self.blocked_fds.get(&Token::from_ptr(event.udata as _)) {
-                waker.wake_by_ref();
+    pub(crate) fn register_timer(duration: time::Duration, waker: task::Waker) -> Token {
+        Singleton::instance()
+            .try_register_timer(duration, waker)
+            .expect("should register the timer properly")
+    }
+
+    /// Replaces the waker of the timer identified by the given `token`. Returns `false` if the timer has already
+    /// fired, or has been deregistered.
+    pub(crate) fn block_timer(token: Token, waker: task::Waker) -> bool {
+        match Singleton::instance().timers.get_mut(&token) {
+            Some(blocked) => {
+                blocked.clone_from(&waker);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Tries to register the signal identified by the given `signum` into the `selector`, and returns the `Token`
+    /// identifying the signal together with the identifier of a new listener, which observes the deliveries of the
+    /// signal from now on.
+    ///
+    /// # Note:
+    /// We should provide a proper error handling here, e.g., implementing a `Registry` structure which is responsible
+    /// for recovering, but this is an educational purpose implementation so that conducting over-engineering
+    /// was avoided.
+    pub(crate) fn register_signal(signum: libc::c_int) -> (Token, u64) {
+        Singleton::instance()
+            .try_register_signal(signum)
+            .expect("should register the signal properly")
+    }
+
+    /// Replaces the waker of the signal listener identified by the given `token` and `id`. Returns `true` if the
+    /// signal has been delivered, in which case the listener gets removed.
+    pub(crate) fn block_signal(token: Token, id: u64, waker: task::Waker) -> bool {
+        let mut reactor = Singleton::instance();
+        let Some(listeners) = reactor.signals.get_mut(&token) else {
+            return false;
+        };
+        let listener = listeners.entry(id).or_default();
+        if listener.delivered {
+            listeners.remove(&id);
+            return true;
+        }
+        listener.waker = Some(waker);
+        false
+    }
+
+    /// Removes the signal listener identified by the given `token` and `id`. The signal itself stays registered, so
+    /// that the signal arriving while no task is listening does not fall back to its default disposition.
+    pub(crate) fn deregister_signal(token: Token, id: u64) {
+        if let Some(listeners) = Singleton::instance().signals.get_mut(&token) {
+            listeners.remove(&id);
+        }
+    }
+
+    /// Tries to deregister the timer identified by the given `token` from the `selector`.
+    ///
+    /// # Note:
+    /// We should provide a proper error handling here, e.g., implementing a `Registry` structure which is responsible
+    /// for recovering, but this is an educational purpose implementation so that conducting over-engineering
+    /// was avoided.
+    pub(crate) fn deregister_timer(token: Token) {
+        let mut reactor = Singleton::instance();
+        reactor.timers.remove(&token);
+        reactor
+            .selector
+            .try_deregister_timer(token)
+            .expect("should deregister the timer properly")
+    }
+
+    /// Returns `true` if the given `fd` has been signaled ready for any of the given `interest` since a task blocked
+    /// on it for the `interest`, i.e., the task has been woken up by the reactor rather than spuriously.
+    pub(crate) fn is_ready(fd: os::fd::RawFd, interest: Interest) -> bool {
+        Singleton::instance()
+            .blocked_fds
+            .get(&fd.into())
+            .is_some_and(|blocked| blocked.is_ready(interest))
+    }
+
+    /// Blocks when the given `fd` is not ready to use yet for the given `interest` and setup the given `waker` to
+    /// wake up the corresponding downstream task to poll later.
+    pub(crate) fn block(fd: os::fd::RawFd, interest: Interest, waker: task::Waker) {
+        #[cfg(feature = "trace")]
+        crate::trace::event(format_args!(
+            "block task {:?} on fd {} for {:?}",
+            crate::core::scheduler::Scheduler::current(),
+            fd,
+            interest
+        ));
+        Singleton::instance().do_block(fd, interest, waker);
+    }
+}
+
+impl Reactor {
+    /// Returns `true` if the reactor has anything which may wake up a task.
+    fn get_has_registrations(&self) -> bool {
+        !self.registrations.is_empty()
+            || !self.blocked_fds.is_empty()
+            || !self.timers.is_empty()
+            || self.signals.values().any(|listeners| !listeners.is_empty())
+            || !self.carried_events.is_empty()
+    }
+
+    /// Returns the `selector` to wait for the next events, or `None` if there are still events carried over from
+    /// the previous turn.
+    fn get_selector_to_wait(&mut self) -> Option<sync::Arc<Selector>> {
+        #[cfg(feature = "trace")]
+        {
+            self.turns = self.turns.wrapping_add(1);
+            if self.turns % AUDIT_INTERVAL == 0 {
+                self.do_audit_registrations();
+            }
+        }
+        // Note:
+        // The selector is consulted only when all the events of the previous turn have been processed. Since
+        // `EV_CLEAR`, or `EPOLLET`, latches the readiness until it gets consumed, the carried events are still valid.
+        self.carried_events
+            .is_empty()
+            .then(|| sync::Arc::clone(&self.selector))
+    }
+
+    /// Processes the given `events` together with the events carried over from the previous turn, waking up the
+    /// tasks blocked on them up to the `event_budget`.
+    fn do_turn(&mut self, events: &Events) {
+        self.carried_events.extend(
+            events
+                .readiness()
+                .filter(|(token, _)| *token != Token::WAKE),
+        );
+        let budget = self.event_budget.unwrap_or(usize::MAX);
+        for _ in 0..budget {
+            let Some((token, interest)) = self.carried_events.pop_front() else {
+                break;
+            };
+            if token.is_timer() {
+                if let Some(waker) = self.timers.remove(&token) {
+                    waker.wake();
+                }
+                continue;
+            }
+            if token.is_signal() {
+                for listener in self
+                    .signals
+                    .get_mut(&token)
+                    .into_iter()
+                    .flat_map(|l| l.values_mut())
+                {
+                    listener.delivered = true;
+                    if let Some(waker) = listener.waker.take() {
+                        waker.wake();
+                    }
+                }
+                continue;
+            }
+            if !self.is_live(token) {
+                continue;
+            }
+            let token = token.source();
+            #[cfg(feature = "test-util")]
+            crate::test::record_event(token);
+            if let Some(blocked) = self.blocked_fds.get_mut(&token) {
+                blocked.wake(interest);
             }
         }
-        Ok(())
     }
 
     /// Tries to register the given `fd` into the `selector` to monitor IO events, which is specified by the
-    /// `interest`.
+    /// `interest`. The `selector` is updated only when the `interest` has not been registered yet.
     ///
     /// # Note:
     /// We should provide a proper error handling here, e.g., implementing a `Registry` structure which is responsible
     /// for recovering, but this is an educational purpose implementation so that conducting over-engineering
     /// was avoided.
-    fn try_register<Fd>(&mut self, fd: &Fd, interest: Interest) -> io::Result<()>
-    where
-        Fd: os::fd::AsFd + os::fd::AsRawFd,
-    {
-        self.selector
-            .try_register(fd.as_raw_fd(), fd.as_raw_fd().into(), interest)
+    fn try_register(&mut self, fd: os::fd::RawFd, interest: Interest) -> io::Result<()> {
+        // Note:
+        // An empty `interest` neither reaches the `selector` nor keeps the `fd` registered, so that a suspended
+        // `Registration` does not hold the `fd` live.
+        if interest.is_empty() {
+            return Ok(());
+        }
+        let token = fd.into();
+        let generation = self.generations.get(&token).copied().unwrap_or_default();
+        let registered = self.registrations.entry(token).or_default();
+        let added = interest.remove(registered.interest());
+        if !added.is_empty() {
+            self.selector
+                .try_register(fd, Token::with_generation(fd, generation), added)?;
+        }
+        registered.increment(interest);
+        Ok(())
     }
 
-    /// Tries to deregister the given `fd` from the `selector`.
+    /// Tries to deregister the given `fd` from the `selector` to stop monitoring IO events, which is specified by
+    /// the `interest`. The `selector` is updated only when no other registrations are left for the `interest`, and
+    /// the associated waker is released only when no registrations are left for the `fd`.
     ///
     /// # Note:
     /// We should provide a proper error handling here, e.g., implementing a `Registry` structure which is responsible
     /// for recovering, but this is an educational purpose implementation so that conducting over-engineering
     /// was avoided.
-    fn try_deregister<Fd>(&mut self, fd: &Fd) -> io::Result<()>
-    where
-        Fd: os::fd::AsFd + os::fd::AsRawFd,
-    {
-        self.blocked_fds.remove(&fd.as_raw_fd().into());
-        self.selector.try_deregister(fd.as_raw_fd())
+    fn try_deregister(&mut self, fd: os::fd::RawFd, interest: Interest) -> io::Result<()> {
+        let token = fd.into();
+        let Some(registered) = self.registrations.get_mut(&token) else {
+            return Ok(());
+        };
+        let before = registered.interest();
+        registered.decrement(interest);
+        let after = registered.interest();
+        let removed = before.remove(after);
+        if after.is_empty() {
+            self.registrations.remove(&token);
+            self.blocked_fds.remove(&token);
+            let generation = self.generations.entry(token).or_default();
+            *generation = generation.wrapping_add(1);
+        }
+        if removed.is_empty() {
+            return Ok(());
+        }
+        self.selector.try_deregister(fd, removed)
     }
 
-    /// Blocks when the given `fd` is not ready to use yet and setup the given `waker` to wake up the corresponding
-    /// downstream task to poll later.
-    fn do_block<Fd>(&mut self, fd: &Fd, waker: task::Waker)
-    where
-        Fd: os::fd::AsFd + os::fd::AsRawFd,
-    {
-        self.blocked_fds.insert(fd.as_raw_fd().into(), waker);
+    /// Tries to register a one-shot timer into the `selector`, which wakes up the given `waker` once the given
+    /// `duration` elapses.
+    fn try_register_timer(
+        &mut self,
+        duration: time::Duration,
+        waker: task::Waker,
+    ) -> io::Result<Token> {
+        let token = Token::timer(self.next_timer);
+        self.next_timer = self.next_timer.wrapping_add(1);
+        self.selector.try_register_timer(token, duration)?;
+        self.timers.insert(token, waker);
+        Ok(token)
+    }
+
+    /// Tries to register the signal identified by the given `signum` into the `selector` unless it has been
+    /// registered already, and adds a new listener of the signal.
+    fn try_register_signal(&mut self, signum: libc::c_int) -> io::Result<(Token, u64)> {
+        let token = Token::signal(signum);
+        if !self.signals.contains_key(&token) {
+            self.selector.try_register_signal(signum, token)?;
+        }
+        let id = self.next_listener;
+        self.next_listener = self.next_listener.wrapping_add(1);
+        self.signals
+            .entry(token)
+            .or_default()
+            .insert(id, Listener::default());
+        Ok((token, id))
+    }
+
+    /// Returns `true` if the event tagged with the given `token` was generated for the current registration of the
+    /// file descriptor, i.e., the file descriptor has not been deregistered since the event was selected.
+    fn is_live(&self, token: Token) -> bool {
+        let source = token.source();
+        self.registrations.contains_key(&source)
+            && Token::with_generation(
+                i64::from(source) as os::fd::RawFd,
+                self.generations.get(&source).copied().unwrap_or_default(),
+            ) == token
+    }
+
+    /// Audits the registrations and returns the number of stale ones.
+    #[cfg(feature = "trace")]
+    fn do_audit_registrations(&self) -> usize {
+        let mut stale = 0;
+        for token in self.registrations.keys() {
+            let fd = i64::from(*token) as os::fd::RawFd;
+            if !crate::sys::unix::is_open(fd) {
+                crate::trace::warn(format_args!(
+                    "file descriptor {} has been closed without being deregistered",
+                    fd
+                ));
+                stale += 1;
+            }
+        }
+        stale
+    }
+
+    /// Blocks when the given `fd` is not ready to use yet for the given `interest` and setup the given `waker` to
+    /// wake up the corresponding downstream task to poll later.
+    fn do_block(&mut self, fd: os::fd::RawFd, interest: Interest, waker: task::Waker) {
+        self.blocked_fds
+            .entry(fd.into())
+            .or_default()
+            .insert(interest, waker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_keeps_the_configuration() {
+        let _serial = crate::tests::serial();
+        Reactor::set_event_budget(Some(7));
+        Reactor::register_timer(
+            time::Duration::from_secs(60),
+            crate::core::task::Id::default().into(),
+        );
+        assert!(Reactor::has_registrations());
+        Reactor::reset();
+        assert!(!Reactor::has_registrations());
+        assert_eq!(Singleton::instance().event_budget, Some(7));
+    }
+
+    #[test]
+    fn try_turn_reuses_the_events_buffer() {
+        let _serial = crate::tests::serial();
+        Reactor::try_turn(Some(time::Duration::ZERO)).unwrap();
+        let buffer = Singleton::instance()
+            .events
+            .as_ref()
+            .map(|events| events.as_ptr());
+        assert!(buffer.is_some());
+        for _ in 0..3 {
+            Reactor::try_turn(Some(time::Duration::ZERO)).unwrap();
+            assert_eq!(
+                Singleton::instance()
+                    .events
+                    .as_ref()
+                    .map(|events| events.as_ptr()),
+                buffer
+            );
+        }
+    }
+
+    /// Represents a waker counting how many times it has been woken up.
+    struct Counter(sync::atomic::AtomicUsize);
+
+    impl task::Wake for Counter {
+        fn wake(self: sync::Arc<Self>) {
+            self.0.fetch_add(1, sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn event_budget_carries_the_remaining_events_over_to_the_next_turns() {
+        let _serial = crate::tests::serial();
+        Reactor::set_event_budget(Some(2));
+        let counter = sync::Arc::new(Counter(sync::atomic::AtomicUsize::new(0)));
+        let mut pairs = Vec::new();
+        let mut registrations = Vec::new();
+        for _ in 0..5 {
+            let (one, two) = os::unix::net::UnixStream::pair().unwrap();
+            one.set_nonblocking(true).unwrap();
+            let mut registration = Reactor::register(&one, Interest::READABLE);
+            registration.block(task::Waker::from(counter.clone()));
+            io::Write::write_all(&mut &two, b"x").unwrap();
+            registrations.push(registration);
+            pairs.push((one, two));
+        }
+        let woken = || counter.0.load(sync::atomic::Ordering::SeqCst);
+        Reactor::try_turn(Some(time::Duration::from_millis(100))).unwrap();
+        assert_eq!(woken(), 2);
+        // Note:
+        // The carried events are processed without consulting the selector, i.e., the tasks woken so far get polled
+        // in between the turns.
+        assert_eq!(Singleton::instance().carried_events.len(), 3);
+        Reactor::try_turn(Some(time::Duration::ZERO)).unwrap();
+        assert_eq!(woken(), 4);
+        Reactor::try_turn(Some(time::Duration::ZERO)).unwrap();
+        assert_eq!(woken(), 5);
+        assert!(Singleton::instance().carried_events.is_empty());
+        drop(registrations);
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn audit_registrations_flags_the_descriptors_closed_out_of_band() {
+        let _serial = crate::tests::serial();
+        let (alive, closed) = os::unix::net::UnixStream::pair().unwrap();
+        let _alive = Reactor::register(&alive, Interest::READABLE);
+        // Note:
+        // The `Registration` is leaked on purpose, i.e., it is never deregistered before its socket is closed.
+        std::mem::forget(Reactor::register(&closed, Interest::READABLE));
+        let fd = os::fd::AsRawFd::as_raw_fd(&closed);
+        crate::trace::take_warnings();
+        assert_eq!(Reactor::audit_registrations(), 0);
+        drop(closed);
+        assert_eq!(Reactor::audit_registrations(), 1);
+        assert_eq!(
+            crate::trace::take_warnings(),
+            [format!(
+                "file descriptor {} has been closed without being deregistered",
+                fd
+            )]
+        );
+        Reactor::reset();
+    }
+
+    #[test]
+    fn events_selected_for_a_reused_descriptor_number_are_stale() {
+        let _serial = crate::tests::serial();
+        let (closed, _peer) = os::unix::net::UnixStream::pair().unwrap();
+        let fd = os::fd::AsRawFd::as_raw_fd(&closed);
+        let current = || {
+            let generations = &Singleton::instance().generations;
+            Token::with_generation(fd, generations.get(&fd.into()).copied().unwrap_or_default())
+        };
+        let registration = Reactor::register(&closed, Interest::READABLE);
+        let stale = current();
+        drop(registration);
+        drop(closed);
+        let (reused, _peer) = os::unix::net::UnixStream::pair().unwrap();
+        // Note:
+        // The lowest free descriptor number is handed out, i.e., the one just closed.
+        assert_eq!(os::fd::AsRawFd::as_raw_fd(&reused), fd);
+        let _registration = Reactor::register(&reused, Interest::READABLE);
+        let live = current();
+        assert_ne!(stale, live);
+        assert!(!Singleton::instance().is_live(stale));
+        assert!(Singleton::instance().is_live(live));
     }
 }
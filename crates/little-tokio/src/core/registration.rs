@@ -0,0 +1,188 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of a `Registration` which represents the lifecycle of
+//! a file descriptor registered to the `Reactor`.
+
+use crate::core::interest::Interest;
+use crate::core::reactor::Reactor;
+use std::{fmt, os, task};
+
+/// Represents a file descriptor registered to the `Reactor` with a certain `Interest`. This struct is
+/// created by `Reactor::register` and deregisters the file descriptor exactly once when it gets dropped,
+/// so that the owner must ensure that the file descriptor outlives the `Registration`.
+pub(crate) struct Registration {
+    fd: os::fd::RawFd,
+    interest: Interest,
+    /// Holds the number of consecutive blocks without any progress, which is used to detect busy loops.
+    #[cfg(feature = "trace")]
+    blocked: usize,
+}
+
+/// The number of consecutive blocks without any progress considered as a busy loop, e.g., the readiness has
+/// been consumed elsewhere so that the task keeps being woken up only to get `WouldBlock` again.
+#[cfg(feature = "trace")]
+const BUSY_LOOP_THRESHOLD: usize = 256;
+
+impl Registration {
+    /// Creates a new `Registration` instance from the specified `fd` and `interest`. The `fd` is expected
+    /// to be registered to the `Reactor` already.
+    pub(crate) fn new(fd: os::fd::RawFd, interest: Interest) -> Self {
+        Self {
+            fd,
+            interest,
+            #[cfg(feature = "trace")]
+            blocked: 0,
+        }
+    }
+
+    /// Returns the `Interest` the file descriptor is currently registered with.
+    pub(crate) fn interest(&self) -> Interest {
+        self.interest
+    }
+
+    /// Changes the `Interest` the file descriptor waits on. Changing it into `Interest::NONE` suspends the monitoring
+    /// of the file descriptor, e.g., for backpressure, until another `Interest` is set. The file descriptor is
+    /// entirely deregistered in the meantime unless it is shared with the other `Registration`s, i.e., the tasks
+    /// blocked on it are not woken up anymore.
+    pub(crate) fn set_interest(&mut self, interest: Interest) {
+        if self.interest == interest {
+            return;
+        }
+        Reactor::reregister(self.fd, self.interest, interest);
+        self.interest = interest;
+    }
+
+    /// Blocks when the file descriptor is not ready to use yet for any of the `Interest`s it is registered with,
+    /// and setup the given `waker` to wake up the corresponding downstream task to poll later.
+    pub(crate) fn block(&mut self, waker: task::Waker) {
+        self.block_for(self.interest, waker);
+    }
+
+    /// Blocks when the file descriptor is not ready to use yet for the given `interest`, and setup the given
+    /// `waker` to wake up the corresponding downstream task to poll later. The tasks blocked for the other
+    /// `Interest` keep their wakers.
+    pub(crate) fn block_for(&mut self, interest: Interest, waker: task::Waker) {
+        #[cfg(feature = "trace")]
+        {
+            self.blocked += 1;
+            if self.blocked == BUSY_LOOP_THRESHOLD {
+                crate::trace::warn(format_args!(
+                    "fd {} with {:?} has blocked {} times in a row without any progress",
+                    self.fd, self.interest, self.blocked
+                ));
+            }
+        }
+        Reactor::block(self.fd, interest, waker);
+    }
+
+    /// Returns `true` if the reactor has signaled the file descriptor ready for any of the given `interest` since
+    /// the last block for the `interest`, which tells the wake by the reactor apart from a spurious one.
+    pub(crate) fn is_ready(&self, interest: Interest) -> bool {
+        Reactor::is_ready(self.fd, interest)
+    }
+
+    /// Notifies that the IO operation on the file descriptor has made a progress, which resets the detection of
+    /// busy loops.
+    pub(crate) fn progress(&mut self) {
+        #[cfg(feature = "trace")]
+        {
+            self.blocked = 0;
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        Reactor::deregister(self.fd, self.interest);
+    }
+}
+
+impl fmt::Debug for Registration {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Registration({}, {:?})", self.fd, self.interest)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn drop_deregisters_the_file_descriptor() {
+        let _serial = crate::tests::serial();
+        let (one, _two) = UnixStream::pair().unwrap();
+        let registration = Reactor::register(&one, Interest::READABLE);
+        assert_eq!(registration.interest(), Interest::READABLE);
+        assert!(Reactor::has_registrations());
+        drop(registration);
+        assert!(!Reactor::has_registrations());
+    }
+
+    #[test]
+    fn file_descriptor_stays_registered_until_the_last_registration_is_dropped() {
+        let _serial = crate::tests::serial();
+        let (one, _two) = UnixStream::pair().unwrap();
+        let readable = Reactor::register(&one, Interest::READABLE);
+        let writable = Reactor::register(&one, Interest::WRITABLE);
+        drop(readable);
+        assert!(Reactor::has_registrations());
+        drop(writable);
+        assert!(!Reactor::has_registrations());
+    }
+
+    #[test]
+    fn empty_interest_suspends_the_monitoring() {
+        let _serial = crate::tests::serial();
+        let (one, _two) = UnixStream::pair().unwrap();
+        let mut registration = Reactor::register(&one, Interest::READABLE);
+        registration.set_interest(Interest::NONE);
+        assert!(!Reactor::has_registrations());
+        registration.set_interest(Interest::WRITABLE);
+        assert!(Reactor::has_registrations());
+        drop(registration);
+        assert!(!Reactor::has_registrations());
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn spurious_wake_loop_is_reported_as_a_busy_loop() {
+        use std::os::fd::AsRawFd;
+        let _serial = crate::tests::serial();
+        let (one, _two) = UnixStream::pair().unwrap();
+        one.set_nonblocking(true).unwrap();
+        let mut registration = Reactor::register(&one, Interest::READABLE);
+        crate::trace::take_warnings();
+        // Note:
+        // Each iteration simulates a task woken up only to get `WouldBlock` again, re-blocking without progress.
+        for _ in 0..BUSY_LOOP_THRESHOLD - 1 {
+            registration.block(crate::core::task::Id::default().into());
+        }
+        assert!(crate::trace::take_warnings().is_empty());
+        registration.block(crate::core::task::Id::default().into());
+        let warnings = crate::trace::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with(&format!("fd {} ", one.as_raw_fd())));
+        // Note:
+        // A progress resets the detection, so that the blocks interleaved with progresses are never reported.
+        registration.progress();
+        for _ in 0..2 * BUSY_LOOP_THRESHOLD {
+            registration.block(crate::core::task::Id::default().into());
+            registration.progress();
+        }
+        assert!(crate::trace::take_warnings().is_empty());
+    }
+}
@@ -13,10 +13,43 @@
 // limitations under the License.
 
 //! This module contains the implementation of a single threaded `Future` scheduler.
+//!
+//! The order in which tasks get polled is deterministic:
+//!  - A spawned task is polled for the first time immediately on `spawn`, i.e., the first polls happen in the
+//!    spawn order.
+//!  - A woken task is polled in the FIFO order of the wakes, where a task woken several times before it gets
+//!    polled keeps the position of its first wake.
+//!  - Tasks spawned from the other threads are polled for the first time at the beginning of the next iteration
+//!    of the runtime, in the order they have been spawned.
+//!
+//! The scheduling is fair in the following sense:
+//!  - The woken tasks are polled in batches of at most `SCHEDULE_BATCH` tasks taken from the front of the queue,
+//!    and a task woken while a batch is being polled, e.g., by waking itself, is queued behind the batch, i.e., each
+//!    task is polled at most once per batch.
+//!  - The event loop is turned without waiting between the batches, so that the tasks ready for IO are queued
+//!    behind the tasks already woken rather than starved by them.
+//!  - The scheduling is cooperative, i.e., a task which never returns `Poll::Pending` from a single poll blocks
+//!    the whole runtime.
 
 use crate::core::task::{Id as TaskId, Task};
 use once_cell::sync::Lazy;
-use std::{collections, fmt, iter, mem, sync, task};
+#[cfg(feature = "trace")]
+use std::time;
+use std::{cell, cmp, collections, fmt, iter, mem, sync, task, thread};
+
+/// The wall-clock time spent in a single poll considered as blocking the runtime, e.g., the task runs a big
+/// synchronous computation which should be offloaded from the runtime.
+#[cfg(feature = "trace")]
+const LONG_POLL_THRESHOLD: time::Duration = time::Duration::from_millis(50);
+
+/// The maximal number of scheduled tasks polled per iteration of the runtime, so that the runtime can interleave
+/// the task execution with the event processing.
+const SCHEDULE_BATCH: usize = 64;
+
+thread_local! {
+    /// Holds the identifier of the task being polled on the current thread.
+    static CURRENT: cell::Cell<Option<TaskId>> = const { cell::Cell::new(None) };
+}
 
 /// Provides the interface to access a `Scheduler` singleton instance. Since the runtime is
 /// designed solely for single-threaded environments, all access to the runtime needs to occur
@@ -43,7 +76,8 @@ pub(crate) enum Status {
     RunningTasks,
     /// Specifies when the executer is turning the event loop and waiting for the next events.
     WaitingForEvents,
-    /// Specifies when all operations of the runtime have completed.
+    /// Specifies when all operations of the runtime have completed, or when the runtime shutting down has no
+    /// task scheduled anymore.
     Done,
 }
 
@@ -65,8 +99,29 @@ pub(crate) struct Scheduler {
     next_id: TaskId,
     /// Holds the `Task`s to be polled on the Little Tokio runtime.
     pending_tasks: collections::HashMap<TaskId, Task>,
-    /// Holds the identifiers of `Task`s ready to be polled.
-    scheduled_ids: Vec<TaskId>,
+    /// Holds the identifiers of `Task`s ready to be polled in the order they have been notified.
+    scheduled_ids: collections::VecDeque<TaskId>,
+    /// Holds the identifiers held by `scheduled_ids`, so that a `Task` notified several times before it gets polled
+    /// is scheduled only once.
+    scheduled: collections::HashSet<TaskId>,
+    /// Holds the `Task`s spawned from the other threads, which are scheduled by the runtime on its next iteration.
+    injected_tasks: Vec<Task>,
+    /// Holds the maximal number of connections accepted per iteration of the runtime, or `None` if the number is
+    /// unbounded.
+    accept_budget: Option<usize>,
+    /// Holds the number of connections accepted in the current iteration of the runtime.
+    accepted: usize,
+    /// Holds the maximal number of pending tasks up to which the `Listener`s accept connections, or `None` if the
+    /// number is unbounded.
+    task_limit: Option<usize>,
+    /// Holds the wakers of the accepting tasks throttled by the `task_limit`, which are woken up once the pending
+    /// tasks drain below it.
+    throttled: Vec<task::Waker>,
+    /// Specifies whether the runtime is shutting down, in which case no more tasks are accepted and the runtime
+    /// completes once no task is scheduled.
+    shutting_down: bool,
+    /// Holds the identifier of the thread running the runtime, or `None` if the runtime is not running.
+    thread: Option<thread::ThreadId>,
 }
 
 impl Scheduler {
@@ -75,35 +130,211 @@ impl Scheduler {
         Singleton::instance().get_status()
     }
 
-    /// Returns the scheduled tasks ids to perform further execution.
+    /// Returns the scheduled tasks ids to perform further execution, which are at most `SCHEDULE_BATCH` ids in the
+    /// order they have been notified.
     pub(crate) fn scheduled_ids() -> impl iter::IntoIterator<Item = TaskId> {
         Singleton::instance().get_scheduled_ids()
     }
 
-    /// Schedules the `task` associated with the given `id` to the scheduler.
+    /// Schedules the `task` to the scheduler. The `task` gets polled immediately, and it is kept by the
+    /// scheduler only when it is still pending, i.e., a task which completes synchronously never touches the
+    /// scheduler's internal maps.
     pub(crate) fn schedule(task: Task) {
-        Singleton::instance().do_schedule(task);
+        let id = {
+            let mut scheduler = Singleton::instance();
+            if scheduler.shutting_down {
+                None
+            } else {
+                Some(scheduler.get_next_id())
+            }
+        };
+        // Note:
+        // The task rejected by the runtime shutting down is dropped after the singleton instance gets unlocked.
+        match id {
+            Some(id) => Self::run(id, task),
+            None => drop(task),
+        }
     }
 
-    /// Notifies the runtime that the `Task` associated with the given `id` is ready to poll.
-    pub(crate) fn notify(id: TaskId) {
-        Singleton::instance().do_notify(id);
+    /// Starts shutting down the runtime, i.e., the tasks spawned from now on are dropped without being polled, and
+    /// the runtime completes once the currently scheduled tasks have been polled.
+    pub(crate) fn shut_down() {
+        Singleton::instance().shutting_down = true;
+    }
+
+    /// Returns `true` if the runtime is shutting down.
+    pub(crate) fn is_shutting_down() -> bool {
+        Singleton::instance().shutting_down
+    }
+
+    /// Sets the maximal number of connections accepted per iteration of the runtime, so that the accepting tasks
+    /// cannot flood the runtime with new connections faster than they are handled.
+    pub(crate) fn set_accept_budget(budget: Option<usize>) {
+        Singleton::instance().accept_budget = budget;
+    }
+
+    /// Consumes the accept budget of the current iteration of the runtime. Returns `false` if the budget has been
+    /// exhausted, in which case the caller should yield to the other tasks until the next iteration.
+    pub(crate) fn consume_accept_budget() -> bool {
+        Singleton::instance().do_consume_accept_budget()
+    }
+
+    /// Sets the maximal number of pending tasks up to which the `Listener`s accept connections, so that a flood of
+    /// connections cannot exhaust the resources by spawning the handler tasks unboundedly.
+    pub(crate) fn set_task_limit(limit: Option<usize>) {
+        Singleton::instance().task_limit = limit;
+    }
+
+    /// Returns the number of pending tasks, i.e., the tasks spawned but not completed yet except the one being
+    /// polled at the moment.
+    pub(crate) fn pending_len() -> usize {
+        Singleton::instance().pending_tasks.len()
+    }
+
+    /// Checks whether a connection can be accepted under the task limit. Returns `false` if the number of pending
+    /// tasks has reached the limit, in which case the given `waker` is woken up once the pending tasks drain below
+    /// the limit. A task throttled several times before the pending tasks drain is woken up only once.
+    pub(crate) fn admit_connection(waker: &task::Waker) -> bool {
+        let mut scheduler = Singleton::instance();
+        match scheduler.task_limit {
+            Some(limit) if scheduler.pending_tasks.len() >= limit => {
+                if !scheduler.throttled.iter().any(|w| w.will_wake(waker)) {
+                    scheduler.throttled.push(waker.clone());
+                }
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Injects the `task` spawned from another thread into the scheduler. The `task` is not polled on the calling
+    /// thread but scheduled by the runtime on its next iteration, i.e., the caller needs to wake up the runtime.
+    pub(crate) fn inject(task: Task) {
+        let mut scheduler = Singleton::instance();
+        if scheduler.shutting_down {
+            drop(scheduler);
+            drop(task);
+            return;
+        }
+        scheduler.injected_tasks.push(task);
+    }
+
+    /// Schedules the `Task`s injected from the other threads so far.
+    pub(crate) fn schedule_injected() {
+        let tasks = mem::take(&mut Singleton::instance().injected_tasks);
+        for task in tasks {
+            Self::schedule(task);
+        }
+    }
+
+    /// Notifies the runtime that the `Task` associated with the given `id` is ready to poll. Returns `true` if the
+    /// notification comes from a thread other than the one running the runtime, in which case the caller needs to
+    /// wake up the runtime which may be waiting for the next events.
+    pub(crate) fn notify(id: TaskId) -> bool {
+        let mut scheduler = Singleton::instance();
+        scheduler.do_notify(id);
+        scheduler
+            .thread
+            .is_some_and(|thread| thread != thread::current().id())
+    }
+
+    /// Marks the current thread as the one running the runtime.
+    pub(crate) fn enter() {
+        Singleton::instance().thread = Some(thread::current().id());
+    }
+
+    /// Marks the runtime as not running on any thread.
+    pub(crate) fn exit() {
+        Singleton::instance().thread = None;
+    }
+
+    /// Returns the identifier of the task being polled on the current thread, if any.
+    pub(crate) fn current() -> Option<TaskId> {
+        CURRENT.with(|current| current.get())
     }
 
     /// Polls the `Task` associated with a given `id`.
     pub(crate) fn poll(id: TaskId) {
         let task = Singleton::instance().get_task(&id);
-        let Some(mut task) = task else {
+        let Some(task) = task else {
             return;
         };
-        match task
+        Self::run(id, task);
+    }
+
+    /// Resets the scheduler into the initial state, dropping all the pending tasks. The configuration set up by
+    /// `runtime::Builder`, i.e., the accept budget and the task limit, is kept.
+    ///
+    /// # Note:
+    /// The pending tasks are dropped after the singleton instance gets unlocked, since dropping them may wake the
+    /// other tasks, e.g., by closing the channels shared with them.
+    pub(crate) fn reset() {
+        // Note:
+        // The tasks are dropped before the rest of the state gets reset, so that they still observe whether the
+        // runtime is shutting down, e.g., to complete their `JoinHandle`s with `Error::Shutdown`.
+        let tasks = {
+            let mut scheduler = Singleton::instance();
+            (
+                mem::take(&mut scheduler.pending_tasks),
+                mem::take(&mut scheduler.injected_tasks),
+            )
+        };
+        drop(tasks);
+        // Note:
+        // The identifiers notified while dropping the pending tasks refer to the tasks which no longer exist.
+        let scheduler = {
+            let mut scheduler = Singleton::instance();
+            let initial = Self {
+                accept_budget: scheduler.accept_budget,
+                task_limit: scheduler.task_limit,
+                ..Self::default()
+            };
+            mem::replace(&mut *scheduler, initial)
+        };
+        drop(scheduler);
+    }
+
+    /// Polls the given `task` associated with the given `id` and pends it to the scheduler if it is not ready yet.
+    /// The singleton instance must not be locked while polling since the `task` may spawn or wake other tasks.
+    fn run(id: TaskId, mut task: Task) {
+        #[cfg(feature = "test-util")]
+        crate::test::record_poll(id);
+        #[cfg(feature = "trace")]
+        let started = time::Instant::now();
+        // Note:
+        // The identifier being replaced is restored after the poll, since a task spawned from another task is
+        // polled for the first time within the poll of the spawning task.
+        let outer = CURRENT.with(|current| current.replace(Some(id)));
+        let poll = task
             .as_mut()
-            .poll(&mut task::Context::from_waker(&id.into()))
+            .poll(&mut task::Context::from_waker(&id.into()));
+        CURRENT.with(|current| current.set(outer));
+        #[cfg(feature = "trace")]
         {
+            let elapsed = started.elapsed();
+            crate::trace::event(format_args!(
+                "poll task {:?}: {} in {:?}",
+                id,
+                if poll.is_ready() { "ready" } else { "pending" },
+                elapsed
+            ));
+            if elapsed >= LONG_POLL_THRESHOLD {
+                crate::trace::warn(format_args!(
+                    "task {:?} took {:?} in a single poll, which blocks the runtime",
+                    id, elapsed
+                ));
+            }
+        }
+        match poll {
             task::Poll::Pending => {
                 Singleton::instance().do_pend(id, task);
             }
-            task::Poll::Ready(()) => {}
+            task::Poll::Ready(()) => {
+                let throttled = Singleton::instance().take_throttled();
+                for waker in throttled {
+                    waker.wake();
+                }
+            }
         }
     }
 }
@@ -111,7 +342,11 @@ impl Scheduler {
 impl Scheduler {
     /// Returns the current `Status` of the Little Tokio runtime.
     fn get_status(&self) -> Status {
-        if self.pending_tasks.is_empty() {
+        if !self.injected_tasks.is_empty() {
+            Status::RunningTasks
+        } else if self.pending_tasks.is_empty()
+            || self.shutting_down && self.scheduled_ids.is_empty()
+        {
             Status::Done
         } else if self.scheduled_ids.is_empty() {
             Status::WaitingForEvents
@@ -122,7 +357,13 @@ impl Scheduler {
 
     /// Returns the scheduled tasks ids to perform further execution.
     fn get_scheduled_ids(&mut self) -> impl iter::IntoIterator<Item = TaskId> {
-        mem::take(&mut self.scheduled_ids)
+        self.accepted = 0;
+        let count = cmp::min(self.scheduled_ids.len(), SCHEDULE_BATCH);
+        let ids: Vec<TaskId> = self.scheduled_ids.drain(..count).collect();
+        for id in &ids {
+            self.scheduled.remove(id);
+        }
+        ids
     }
 
     /// Returns the next scheduled `Task` to perform further execution.
@@ -130,11 +371,9 @@ impl Scheduler {
         self.pending_tasks.remove(id)
     }
 
-    /// Schedules the `task` to the scheduler.
-    fn do_schedule(&mut self, task: Task) {
-        let id = self.next_id.increment();
-        self.pending_tasks.insert(id, task);
-        self.scheduled_ids.push(id);
+    /// Returns the `Id` which will be assigned to the next `Task`.
+    fn get_next_id(&mut self) -> TaskId {
+        self.next_id.increment()
     }
 
     /// Pends the `task` associated with the given `id` to the scheduler.
@@ -142,8 +381,215 @@ impl Scheduler {
         self.pending_tasks.insert(id, task);
     }
 
+    /// Takes the wakers of the throttled accepting tasks if the pending tasks have drained below the task limit.
+    fn take_throttled(&mut self) -> Vec<task::Waker> {
+        match self.task_limit {
+            Some(limit) if self.pending_tasks.len() < limit => mem::take(&mut self.throttled),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Consumes the accept budget of the current iteration of the runtime.
+    fn do_consume_accept_budget(&mut self) -> bool {
+        match self.accept_budget {
+            Some(budget) if self.accepted >= budget => false,
+            _ => {
+                self.accepted += 1;
+                true
+            }
+        }
+    }
+
     /// Notifies the runtime that the `Task` associated with the given `id` is ready to poll.
     fn do_notify(&mut self, id: TaskId) {
-        self.scheduled_ids.push(id);
+        if self.scheduled.insert(id) {
+            self.scheduled_ids.push_back(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_keeps_the_configuration() {
+        let _serial = crate::tests::serial();
+        Scheduler::set_accept_budget(Some(3));
+        Scheduler::set_task_limit(Some(5));
+        Scheduler::schedule(Box::pin(std::future::pending()));
+        assert_eq!(Scheduler::pending_len(), 1);
+        Scheduler::reset();
+        assert_eq!(Scheduler::pending_len(), 0);
+        let scheduler = Singleton::instance();
+        assert_eq!(scheduler.accept_budget, Some(3));
+        assert_eq!(scheduler.task_limit, Some(5));
+    }
+
+    #[test]
+    fn throttled_wakers_are_deduplicated() {
+        let _serial = crate::tests::serial();
+        Scheduler::set_task_limit(Some(1));
+        Scheduler::schedule(Box::pin(std::future::pending()));
+        let first: task::Waker = TaskId::default().into();
+        let second: task::Waker = {
+            let mut id = TaskId::default();
+            id.increment();
+            id.increment().into()
+        };
+        for _ in 0..3 {
+            assert!(!Scheduler::admit_connection(&first));
+            assert!(!Scheduler::admit_connection(&second));
+        }
+        assert_eq!(Singleton::instance().throttled.len(), 2);
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn long_polls_are_reported() {
+        let _serial = crate::tests::serial();
+        crate::trace::take_warnings();
+        crate::block_on(async {
+            crate::spawn(async {});
+        });
+        assert!(crate::trace::take_warnings().is_empty());
+        crate::block_on(async {
+            // Note:
+            // A synchronous sleep stands for a computation which should have been moved into `spawn_blocking`.
+            std::thread::sleep(LONG_POLL_THRESHOLD + time::Duration::from_millis(10));
+        });
+        let warnings = crate::trace::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("task "));
+        assert!(warnings[0].ends_with("in a single poll, which blocks the runtime"));
+    }
+
+    #[test]
+    fn woken_tasks_are_polled_in_fifo_order() {
+        let _serial = crate::tests::serial();
+        let log = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let tasks = log.clone();
+        crate::block_on(async move {
+            for name in ['a', 'b', 'c'] {
+                let log = tasks.clone();
+                crate::spawn(async move {
+                    for round in 0..3 {
+                        log.lock().unwrap().push((name, round));
+                        crate::tests::yield_now().await;
+                    }
+                });
+            }
+        });
+        let expected: Vec<_> = (0..3)
+            .flat_map(|round| ['a', 'b', 'c'].map(|name| (name, round)))
+            .collect();
+        assert_eq!(*log.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn tasks_are_polled_in_the_order_they_are_woken() {
+        let _serial = crate::tests::serial();
+        let log = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let tasks = log.clone();
+        crate::block_on(async move {
+            let wakers = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            for name in ['a', 'b', 'c'] {
+                let log = tasks.clone();
+                let wakers = wakers.clone();
+                crate::spawn(async move {
+                    log.lock().unwrap().push(name);
+                    let mut parked = false;
+                    std::future::poll_fn(|cx| {
+                        if parked {
+                            return task::Poll::Ready(());
+                        }
+                        parked = true;
+                        wakers.lock().unwrap().push(cx.waker().clone());
+                        task::Poll::Pending
+                    })
+                    .await;
+                    log.lock().unwrap().push(name);
+                });
+            }
+            let wakers = std::mem::take(&mut *wakers.lock().unwrap());
+            for index in [2, 0, 1] {
+                wakers[index].wake_by_ref();
+            }
+        });
+        assert_eq!(*log.lock().unwrap(), ['a', 'b', 'c', 'c', 'a', 'b']);
+    }
+
+    #[test]
+    fn tasks_completing_on_the_first_poll_are_never_kept_pending() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let polled = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let flag = polled.clone();
+            crate::spawn(async move {
+                flag.store(true, std::sync::atomic::Ordering::SeqCst);
+            });
+            assert!(polled.load(std::sync::atomic::Ordering::SeqCst));
+            assert_eq!(Scheduler::pending_len(), 0);
+            let (sender, mut receiver) = crate::sync::channel::<()>();
+            crate::spawn(async move {
+                receiver.recv().await;
+            });
+            assert_eq!(Scheduler::pending_len(), 1);
+            drop(sender);
+        });
+        assert_eq!(Scheduler::pending_len(), 0);
+    }
+
+    #[test]
+    fn self_waking_tasks_neither_starve_each_other_nor_the_io() {
+        let _serial = crate::tests::serial();
+        let ret = crate::block_on_timeout(
+            async {
+                let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+                let spinners: Vec<_> = (0..3)
+                    .map(|_| {
+                        let stop = stop.clone();
+                        crate::spawn_with_output(async move {
+                            let mut polls = 0usize;
+                            std::future::poll_fn(|cx| {
+                                polls += 1;
+                                if stop.load(std::sync::atomic::Ordering::SeqCst) {
+                                    return task::Poll::Ready(());
+                                }
+                                cx.waker().wake_by_ref();
+                                task::Poll::Pending
+                            })
+                            .await;
+                            polls
+                        })
+                    })
+                    .collect();
+                let (reader, mut writer) = std::os::unix::net::UnixStream::pair().unwrap();
+                reader.set_nonblocking(true).unwrap();
+                std::thread::spawn(move || {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    std::io::Write::write_all(&mut writer, b"x").unwrap();
+                });
+                let reader = crate::io::AsyncFd::new(reader);
+                let mut buffer = [0u8; 1];
+                loop {
+                    let mut guard = reader.readable().await.unwrap();
+                    if let Ok(ret) =
+                        guard.try_io(|reader| std::io::Read::read(&mut &*reader, &mut buffer))
+                    {
+                        assert_eq!(ret.unwrap(), 1);
+                        break;
+                    }
+                }
+                stop.store(true, std::sync::atomic::Ordering::SeqCst);
+                crate::task::join_handles(spinners).await.unwrap()
+            },
+            std::time::Duration::from_secs(5),
+        );
+        // Note:
+        // The spinners are polled once per batch each, i.e., equally often up to the batch in which they stop.
+        let polls = ret.expect("the IO should complete while the spinners are running");
+        let (min, max) = (polls.iter().min().unwrap(), polls.iter().max().unwrap());
+        assert!(max - min <= 1, "{:?}", polls);
     }
 }
@@ -15,19 +15,69 @@
 //! This module contains the implementation of a `Task` which represents the unit of
 //! comupation (state machine) of the `Runtime`, i.e., a `Future` implementation.
 
-use crate::core::token::Token;
-use crate::core::waker::VTABLE;
-use std::{fmt, future, marker, pin, task};
+use crate::core::waker;
+use std::{fmt, future, marker, mem, pin, task, thread};
 
 /// Represents a `Task` of `Runtime` is defined as a heap-allocated and `Pin`ned instance of the `Future`.
 pub(crate) type Task = pin::Pin<Box<dyn future::Future<Output = ()> + marker::Send + 'static>>;
 
+/// Represents a `Future` which is not `Send` but carried by a `Task`, which is `Send`. The `Future` is pinned to
+/// the thread it has been spawned on, i.e., it is polled and dropped only on that thread.
+pub(crate) struct Local<F> {
+    future: mem::ManuallyDrop<pin::Pin<Box<F>>>,
+    thread: thread::ThreadId,
+}
+
+impl<F> Local<F> {
+    /// Creates a new `Local` instance pinned to the current thread.
+    pub(crate) fn new(future: F) -> Self {
+        Self {
+            future: mem::ManuallyDrop::new(Box::pin(future)),
+            thread: thread::current().id(),
+        }
+    }
+}
+
+// Safety:
+// The inner `Future` is accessed only on the thread it has been spawned on, i.e., `poll` panics and `drop` leaks
+// the inner `Future` on any other thread, so that moving a `Local` across threads never moves the access to it.
+unsafe impl<F> marker::Send for Local<F> {}
+
+impl<F> future::Future for Local<F>
+where
+    F: future::Future,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        assert!(
+            self.thread == thread::current().id(),
+            "task spawned by `spawn_local` should be polled on the thread it has been spawned on"
+        );
+        self.future.as_mut().poll(cx)
+    }
+}
+
+impl<F> Drop for Local<F> {
+    fn drop(&mut self) {
+        if self.thread == thread::current().id() {
+            // Safety:
+            // The inner `Future` is dropped exactly once, on the thread it has been spawned on.
+            unsafe { mem::ManuallyDrop::drop(&mut self.future) }
+        }
+    }
+}
+
 /// Specifies the identifier of a `Task`, which is defined as an `usize` number. In theory, tasks can
 /// have arbitrary data types which will be used for the future usage of a `Future` runtime. However,
 /// the `Runtime` of this crate assumes that only `Id` values are allowed for the data since this crate
 /// is for self-studying purpose.
+///
+/// # Note:
+/// `Id`s are exposed via `task::current_id` for introspection, e.g., logging which task is blocked on which file
+/// descriptor, and are formatted as the number which `test::Trace` records for the task.
 #[derive(Default, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub(crate) struct Id(i64);
+pub struct Id(i64);
 
 impl Id {
     /// Returns the copy of the current `Id` and increments the internal `usize` value.
@@ -52,9 +102,9 @@ impl Id {
     }
 }
 
-impl From<Token> for Id {
-    fn from(token: Token) -> Self {
-        Self::from_ptr(token.to_ptr())
+impl From<Id> for i64 {
+    fn from(id: Id) -> Self {
+        id.0
     }
 }
 
@@ -63,7 +113,7 @@ impl From<Id> for task::Waker {
         // SAFETY:
         // Given that the implementation of this runtime aims to provide a single-threaded version of
         // an I/O multiplexer, this restriction is lifted
-        unsafe { Self::from_raw(task::RawWaker::new(id.to_ptr(), &VTABLE)) }
+        unsafe { Self::from_raw(waker::raw(id)) }
     }
 }
 
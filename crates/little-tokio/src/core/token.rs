@@ -15,14 +15,48 @@
 //! This module contains the implementation of a `Token` which represents the user defined `udata`
 //! of the `kevent` system call.
 
-use crate::core::task::Id as TaskId;
 use std::{fmt, os};
 
-/// Identifies a file descriptor to track which data source generated the event.
+/// The bit tagging the `Token`s of signals.
+const SIGNAL_TAG: i64 = 1 << 32;
+
+/// The mask of the generations tagged to the `Token`s of file descriptors, which keeps the sign bit cleared so that
+/// the `Token`s of file descriptors never collide with the `Token`s of timers and signals, nor with `Token::WAKE`.
+const GENERATION_MASK: u32 = (1 << 31) - 1;
+
+/// Identifies a file descriptor to track which data source generated the event. `Token`s and task `Id`s live in
+/// separate namespaces and are never converted into each other, i.e., a `Token` only identifies a registration.
 #[derive(Default, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) struct Token(i64);
 
 impl Token {
+    /// The `Token` of the events which wake up the runtime from another thread. Since file descriptors are never
+    /// negative, this never collides with the `Token`s of file descriptors.
+    pub(crate) const WAKE: Self = Self(-1);
+
+    /// Returns the `Token` of the timer identified by the given `id`. The sign bit is set so that the `Token`s of
+    /// timers never collide with the `Token`s of file descriptors, and the lower bits never make up `Token::WAKE`.
+    pub(crate) fn timer(id: u32) -> Self {
+        Self(i64::MIN | i64::from(id))
+    }
+
+    /// Returns `true` if the `Token` identifies a timer.
+    pub(crate) fn is_timer(self) -> bool {
+        self.0 < 0 && self != Self::WAKE && !self.is_signal()
+    }
+
+    /// Returns the `Token` of the events delivering the signal identified by the given `signum`. The sign bit is set
+    /// as well as the `SIGNAL_TAG`, so that the `Token`s of signals collide with neither the `Token`s of timers, of
+    /// which the higher bits are cleared, nor `Token::WAKE`, of which the lower bits are all set.
+    pub(crate) fn signal(signum: libc::c_int) -> Self {
+        Self(i64::MIN | SIGNAL_TAG | i64::from(signum as u32))
+    }
+
+    /// Returns `true` if the `Token` identifies a signal.
+    pub(crate) fn is_signal(self) -> bool {
+        self.0 & !i64::from(u32::MAX) == i64::MIN | SIGNAL_TAG
+    }
+
     /// According to the document [kevent(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kevent.2.html),
     /// the `udata` field in `kevent` is an opaque user defined data field which can be utilized by
     /// the user. We use this field for `Token` to identify the event source.
@@ -36,11 +70,18 @@ impl Token {
     pub(crate) fn from_ptr(value: *const ()) -> Self {
         Self(value as _)
     }
-}
 
-impl From<TaskId> for Token {
-    fn from(id: TaskId) -> Self {
-        Self::from_ptr(id.to_ptr())
+    /// Returns the `Token` of the given `fd` tagged with the given `generation`, which is utilized as the `udata`
+    /// so that the events delivered for a since-closed file descriptor can be told apart from the events for the
+    /// file descriptor reusing the same number. Only the lower 31 bits of the `generation` are tagged, i.e., the
+    /// generations wrap around at `2^31`.
+    pub(crate) fn with_generation(fd: os::fd::RawFd, generation: u32) -> Self {
+        Self((i64::from(generation & GENERATION_MASK) << 32) | i64::from(fd as u32))
+    }
+
+    /// Returns the `Token` of the file descriptor without the generation tag.
+    pub(crate) fn source(self) -> Self {
+        Self::from(self.0 as u32 as os::fd::RawFd)
     }
 }
 
@@ -50,9 +91,61 @@ impl From<os::fd::RawFd> for Token {
     }
 }
 
+impl From<Token> for i64 {
+    fn from(token: Token) -> Self {
+        token.0
+    }
+}
+
 impl fmt::Debug for Token {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(fmt, "{}", self.0)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fd_tokens_stay_apart_from_timers_and_signals_across_the_generation_wrap() {
+        for generation in [
+            0,
+            1,
+            GENERATION_MASK - 1,
+            GENERATION_MASK,
+            1 << 31,
+            u32::MAX,
+        ] {
+            let token = Token::with_generation(7, generation);
+            assert!(i64::from(token) >= 0, "generation {}", generation);
+            assert!(!token.is_timer(), "generation {}", generation);
+            assert!(!token.is_signal(), "generation {}", generation);
+            assert_ne!(token, Token::WAKE);
+            assert_eq!(token.source(), Token::from(7));
+        }
+    }
+
+    #[test]
+    fn generations_wrap_around_at_two_to_the_thirty_one() {
+        assert_eq!(
+            Token::with_generation(3, 1 << 31),
+            Token::with_generation(3, 0)
+        );
+        assert_ne!(
+            Token::with_generation(3, GENERATION_MASK),
+            Token::with_generation(3, 0)
+        );
+    }
+
+    #[test]
+    fn timer_and_signal_tokens_are_classified() {
+        assert!(Token::timer(u32::MAX).is_timer());
+        assert!(!Token::timer(u32::MAX).is_signal());
+        assert!(Token::signal(libc::SIGINT).is_signal());
+        assert!(!Token::signal(libc::SIGINT).is_timer());
+        assert!(!Token::WAKE.is_timer());
+        assert!(!Token::WAKE.is_signal());
+    }
+}
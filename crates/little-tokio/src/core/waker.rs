@@ -13,17 +13,26 @@
 // limitations under the License.
 
 //! This module contains the implementation of a vtable for dispatching methods on `Waker`.
+//!
+//! # Note:
+//! The data associated with a `Waker` is the `Id` of the task itself rather than a pointer to anything, and waking
+//! it notifies the `Mutex` guarded `Scheduler`, so that cloning a `Waker` and waking it from another thread is sound.
 
+use crate::core::reactor::Reactor;
 use crate::core::scheduler::Scheduler;
 use crate::core::task::Id as TaskId;
+use std::sync::atomic::{self, AtomicUsize};
 use std::task;
 
+/// Holds the number of `Waker`s alive, wherever they are held, e.g., by a channel, by the reactor or by another
+/// thread, which the runtime utilizes to tell a deadlock apart from a task waiting for a `Waker` held elsewhere.
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
 /// The current design of the [`Waker`](https://doc.rust-lang.org/std/task/struct.Waker.html)
 /// is focused on performance and embedded-like scenarios. Hence, This wake-related vtable
 /// functions will be associated with a data which will be required when `Scheduler` schedules
 /// a `Task`.
-pub(crate) static VTABLE: task::RawWakerVTable =
-    task::RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+static VTABLE: task::RawWakerVTable = task::RawWakerVTable::new(clone, wake, wake_by_ref, drop);
 
 /// This function will be called when the 'Waker' gets cloned and creates a new `RawWaker` from
 /// the provided data pointer, i.e., an `Id`, and vtable.
@@ -33,7 +42,7 @@ pub(crate) static VTABLE: task::RawWakerVTable =
 /// Given that the implementation of this runtime aims to provide a single-threaded version of
 /// an I/O multiplexer, this restriction is lifted
 unsafe fn clone(id: *const ()) -> task::RawWaker {
-    task::RawWaker::new(id, &VTABLE)
+    raw(TaskId::from_ptr(id))
 }
 
 /// This function will be called when `wake` is called on the `Waker` and schedules the `Task`
@@ -45,17 +54,26 @@ unsafe fn clone(id: *const ()) -> task::RawWaker {
 /// an I/O multiplexer, this restriction is lifted
 unsafe fn wake(id: *const ()) {
     wake_by_ref(id);
+    drop(id);
 }
 
 /// This function will be called when `wake_by_ref` is called on the `Waker` and schedules the `Task`
-/// associated with a give `id`.
+/// associated with a give `id`. The `Waker` may be woken from any thread, e.g., a thread which completes a blocking
+/// operation, in which case the runtime gets woken up as well, since it may be waiting for the next events.
 ///
 /// # Safety
 ///
 /// Given that the implementation of this runtime aims to provide a single-threaded version of
 /// an I/O multiplexer, this restriction is lifted
 unsafe fn wake_by_ref(id: *const ()) {
-    Scheduler::notify(TaskId::from_ptr(id));
+    // Note:
+    // The runtime thread must not wake up the `Reactor` here, since the `Reactor` wakes the tasks up while its
+    // singleton instance is locked.
+    #[cfg(feature = "trace")]
+    crate::trace::event(format_args!("wake task {:?}", TaskId::from_ptr(id)));
+    if Scheduler::notify(TaskId::from_ptr(id)) {
+        Reactor::wake();
+    }
 }
 
 /// This function gets called when a `Waker` gets dropped.
@@ -65,5 +83,50 @@ unsafe fn wake_by_ref(id: *const ()) {
 /// Given that the implementation of this runtime aims to provide a single-threaded version of
 /// an I/O multiplexer, this restriction is lifted
 unsafe fn drop(_id: *const ()) {
-    // Do nothing.
+    LIVE.fetch_sub(1, atomic::Ordering::AcqRel);
+}
+
+/// Returns a new `RawWaker` associated with the `Task` of the given `id`, which is counted as alive until it gets
+/// dropped or woken by value.
+pub(crate) fn raw(id: TaskId) -> task::RawWaker {
+    LIVE.fetch_add(1, atomic::Ordering::AcqRel);
+    task::RawWaker::new(id.to_ptr(), &VTABLE)
+}
+
+/// Returns the number of `Waker`s alive, i.e., created or cloned but neither dropped nor woken by value yet.
+pub(crate) fn live() -> usize {
+    LIVE.load(atomic::Ordering::Acquire)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{future, sync};
+
+    #[test]
+    fn cloned_waker_reschedules_the_task_once_the_original_is_dropped() {
+        let _serial = crate::tests::serial();
+        let polls = sync::Arc::new(AtomicUsize::new(0));
+        let counter = polls.clone();
+        crate::block_on(async move {
+            let slot = sync::Arc::new(sync::Mutex::new(None));
+            let parked = slot.clone();
+            let task = crate::spawn_with_output(async move {
+                future::poll_fn(|cx| {
+                    if counter.fetch_add(1, atomic::Ordering::SeqCst) > 0 {
+                        return task::Poll::Ready(());
+                    }
+                    let original = cx.waker().clone();
+                    *parked.lock().unwrap() = Some(original.clone());
+                    std::mem::drop(original);
+                    task::Poll::Pending
+                })
+                .await
+            });
+            let waker: task::Waker = slot.lock().unwrap().take().unwrap();
+            waker.wake();
+            task.await.unwrap();
+        });
+        assert_eq!(polls.load(atomic::Ordering::SeqCst), 2);
+    }
 }
@@ -0,0 +1,92 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of the `Error` which represents the failures of the Little
+//! Tokio runtime. The TCP primitives stay on `io::Result`, whereas the combinator layer reports this
+//! richer type so that the runtime's own failures can be distinguished from genuine OS errors.
+
+use std::{any, error, fmt, io, result};
+
+/// Represents a `Result` whose failure is the Little Tokio `Error`.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents the failures of the Little Tokio runtime.
+pub enum Error {
+    /// Specifies when a genuine IO error has been reported by the OS.
+    Io(io::Error),
+    /// Specifies when the task has been cancelled before its completion.
+    Cancelled,
+    /// Specifies when the deadline has elapsed before the completion.
+    Elapsed,
+    /// Specifies when the runtime is shutting down.
+    Shutdown,
+    /// Specifies when the task has panicked, holding the message of the panic.
+    Panicked(String),
+}
+
+impl Error {
+    /// Creates a new `Error::Panicked` from the given `payload` of a panic caught by `panic::catch_unwind`. The
+    /// message is taken from the payload if it is a string, as the default panic hook does.
+    pub(crate) fn panicked(payload: Box<dyn any::Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "Box<dyn Any>".to_string(),
+            },
+        };
+        Self::Panicked(message)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(fmt, "Error::Io({:?})", e)?,
+            Self::Cancelled => write!(fmt, "Error::Cancelled")?,
+            Self::Elapsed => write!(fmt, "Error::Elapsed")?,
+            Self::Shutdown => write!(fmt, "Error::Shutdown")?,
+            Self::Panicked(message) => write!(fmt, "Error::Panicked({:?})", message)?,
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(fmt, "{}", e)?,
+            Self::Cancelled => write!(fmt, "task has been cancelled")?,
+            Self::Elapsed => write!(fmt, "deadline has elapsed")?,
+            Self::Shutdown => write!(fmt, "runtime is shutting down")?,
+            Self::Panicked(message) => write!(fmt, "task has panicked: {}", message)?,
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
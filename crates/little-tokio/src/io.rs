@@ -0,0 +1,165 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the poll based IO traits of the Little Tokio runtime, which allow users to layer
+//! their own transports, e.g., a TLS session driven by a sans-IO state machine, over the runtime's streams.
+//!
+//! A layered transport holds a `net::tcp::Stream` and drives its state machine with the non-blocking
+//! `try_read`/`try_write` primitives, awaiting `readable()`/`writable()` whenever the stream would block:
+//!
+//! ```ignore
+//! struct TlsStream {
+//!     stream: little_tokio::net::tcp::Stream,
+//!     session: MySansIoSession,
+//! }
+//!
+//! impl TlsStream {
+//!     async fn handshake(&mut self) -> std::io::Result<()> {
+//!         while self.session.is_handshaking() {
+//!             if self.session.wants_write() {
+//!                 self.stream.writable().await?;
+//!                 match self.stream.try_write(self.session.outgoing()) {
+//!                     Ok(size) => self.session.consume_outgoing(size),
+//!                     Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+//!                     Err(e) => return Err(e),
+//!                 }
+//!             } else {
+//!                 self.stream.readable().await?;
+//!                 let mut buffer = [0u8; 4096];
+//!                 match self.stream.try_read(&mut buffer) {
+//!                     Ok(size) => self.session.feed_incoming(&buffer[..size])?,
+//!                     Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+//!                     Err(e) => return Err(e),
+//!                 }
+//!             }
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//! ```
+//!
+//! Implementing `AsyncRead` and `AsyncWrite` for such a wrapper makes it an `AsyncReadWrite`, i.e., it can be
+//! used wherever the runtime's own `Stream` is accepted. File descriptors other than sockets can be driven in the
+//! same manner by wrapping them into an `AsyncFd`. Line-delimited protocols can be read by wrapping any `AsyncRead`
+//! into a `BufReader`, and small writes can be coalesced by wrapping any `AsyncWrite` into a `BufWriter`. A proxy
+//! forwards two connections into each other with `copy_bidirectional`.
+
+mod async_fd;
+mod buf_reader;
+mod buf_writer;
+mod copy;
+mod duplex;
+
+pub use self::async_fd::{AsyncFd, Readiness, ReadinessOutput, ReadyGuard, TryIoError};
+pub use self::buf_reader::{BufReader, ReadLineOutput, ReadUntilOutput};
+pub use self::buf_writer::{BufWriter, CloseOutput, FlushOutput, WriteAllOutput, WriteOutput};
+pub use self::copy::{copy, copy_bidirectional, CopyBidirectionalOutput, CopyOutput};
+pub use self::duplex::{duplex, DuplexStream};
+
+use std::{io, pin, task};
+
+/// Reads bytes from a source asynchronously. An implementation returns `Poll::Pending` and arranges the
+/// current task to be woken up when the source would block.
+pub trait AsyncRead {
+    /// Attempts to read from the source into `buffer`, returning the number of bytes read.
+    fn poll_read(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &mut [u8],
+    ) -> task::Poll<io::Result<usize>>;
+}
+
+/// Writes bytes into a sink asynchronously. An implementation returns `Poll::Pending` and arranges the
+/// current task to be woken up when the sink would block.
+pub trait AsyncWrite {
+    /// Attempts to write `buffer` into the sink, returning the number of bytes written.
+    fn poll_write(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &[u8],
+    ) -> task::Poll<io::Result<usize>>;
+
+    /// Attempts to write all the bytes buffered in the user space into the underlying sink.
+    ///
+    /// # Note:
+    /// The default implementation resolves immediately, which suits the sinks buffering nothing in the user space,
+    /// e.g., sockets.
+    fn poll_flush(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let _ = cx;
+        task::Poll::Ready(Ok(()))
+    }
+
+    /// Attempts to shut down the sink, e.g., the writing half of a connection, so that the reader on the other side
+    /// reads the EOF once it has read all the bytes written so far.
+    ///
+    /// # Note:
+    /// The default implementation resolves immediately without shutting anything down, which suits the sinks
+    /// having no notion of the EOF.
+    fn poll_shutdown(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let _ = cx;
+        task::Poll::Ready(Ok(()))
+    }
+}
+
+/// Represents a duplex transport which is both `AsyncRead` and `AsyncWrite`. This trait is implemented
+/// automatically for any such a type.
+pub trait AsyncReadWrite: AsyncRead + AsyncWrite {}
+
+impl<T> AsyncReadWrite for T where T: AsyncRead + AsyncWrite + ?Sized {}
+
+impl<T> AsyncRead for &mut T
+where
+    T: AsyncRead + Unpin + ?Sized,
+{
+    fn poll_read(
+        mut self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &mut [u8],
+    ) -> task::Poll<io::Result<usize>> {
+        pin::Pin::new(&mut **self).poll_read(cx, buffer)
+    }
+}
+
+impl<T> AsyncWrite for &mut T
+where
+    T: AsyncWrite + Unpin + ?Sized,
+{
+    fn poll_write(
+        mut self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &[u8],
+    ) -> task::Poll<io::Result<usize>> {
+        pin::Pin::new(&mut **self).poll_write(cx, buffer)
+    }
+
+    fn poll_flush(
+        mut self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        pin::Pin::new(&mut **self).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        pin::Pin::new(&mut **self).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of an `AsyncFd` which integrates arbitrary file descriptors, e.g.,
+//! pipes or ttys, into the Little Tokio runtime based on their readiness.
+
+use crate::core::interest::Interest;
+use crate::core::reactor::Reactor;
+use crate::core::registration::Registration;
+use std::{fmt, future, io, os, pin, sync, task};
+
+/// Represents the state of an `AsyncFd` shared by its readiness futures and guards.
+struct State {
+    /// Holds the `Registration` of the file descriptor, which is created when a task blocks on it first.
+    registration: Option<Registration>,
+    /// Holds the readiness the file descriptor is assumed to have, or `Interest::NONE` if it is not ready at all.
+    /// The file descriptor is assumed to be ready initially, so that the first IO operation is attempted without
+    /// waiting.
+    ready: Interest,
+}
+
+impl State {
+    /// Returns `true` if the file descriptor is assumed to be ready for the given `interest`.
+    fn is_ready(&self, interest: Interest) -> bool {
+        (interest.is_readable() && self.ready.is_readable())
+            || (interest.is_writable() && self.ready.is_writable())
+    }
+
+    /// Assumes the file descriptor to be ready for the given `interest`.
+    fn set_ready(&mut self, interest: Interest) {
+        self.ready |= interest;
+    }
+
+    /// Assumes the file descriptor not to be ready for the given `interest`.
+    fn clear_ready(&mut self, interest: Interest) {
+        self.ready = self.ready.remove(interest);
+    }
+}
+
+/// Represents the Little Tokio wrapper around an arbitrary file descriptor, which has to be in non-blocking mode.
+/// This struct exposes the readiness of the file descriptor via `readable` and `writable`, and the user drives the
+/// IO operations with the `ReadyGuard`s returned by them, e.g.:
+///
+/// ```
+/// use little_tokio::io::AsyncFd;
+/// use std::io::{Read, Write};
+///
+/// let (reader, mut writer) = std::os::unix::net::UnixStream::pair().unwrap();
+/// reader.set_nonblocking(true).unwrap();
+/// little_tokio::block_on(async move {
+///     std::thread::spawn(move || writer.write_all(b"hello"));
+///     let pipe = AsyncFd::new(reader);
+///     let mut buffer = [0u8; 1024];
+///     let size = loop {
+///         let mut guard = pipe.readable().await.unwrap();
+///         match guard.try_io(|reader| (&*reader).read(&mut buffer)) {
+///             Ok(result) => break result.unwrap(),
+///             Err(_would_block) => continue,
+///         }
+///     };
+///     assert_eq!(&buffer[..size], b"hello");
+/// });
+/// ```
+///
+/// # Note:
+/// The runtime keeps a waker per `Interest` of a file descriptor, i.e., a task waiting for an `AsyncFd` to become
+/// readable and another task waiting for it to become writable do not clobber each other's wakers, but only one
+/// task at a time should wait for each of the `Interest`s.
+pub struct AsyncFd<T>
+where
+    T: os::fd::AsRawFd,
+{
+    // Note:
+    // The `state` must be declared before the `inner` so that the registration gets dropped, i.e., deregistered,
+    // while the file descriptor is still open.
+    state: sync::Mutex<State>,
+    inner: T,
+}
+
+impl<T> AsyncFd<T>
+where
+    T: os::fd::AsRawFd,
+{
+    /// Creates a new `AsyncFd` wrapping the given `inner`, whose file descriptor has to be in non-blocking mode.
+    pub fn new(inner: T) -> Self {
+        Self {
+            state: sync::Mutex::new(State {
+                registration: None,
+                ready: Interest::READABLE | Interest::WRITABLE,
+            }),
+            inner,
+        }
+    }
+
+    /// Returns the shared reference to the wrapped `inner`.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the mutable reference to the wrapped `inner`.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Deregisters the file descriptor from the runtime and returns the wrapped `inner`.
+    pub fn into_inner(self) -> T {
+        let Self { state, inner } = self;
+        drop(state);
+        inner
+    }
+
+    /// Waits for the file descriptor to become readable and returns a `Readiness` struct, which offers an
+    /// abstraction over IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio runtime.
+    pub fn readable(&self) -> Readiness<'_, T> {
+        Readiness::new(self, Interest::READABLE)
+    }
+
+    /// Waits for the file descriptor to become writable and returns a `Readiness` struct, which offers an
+    /// abstraction over IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio runtime.
+    pub fn writable(&self) -> Readiness<'_, T> {
+        Readiness::new(self, Interest::WRITABLE)
+    }
+
+    /// Returns the `State` guarded by the `Mutex`.
+    fn state(&self) -> sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("`MutexGuard` of the `AsyncFd` should be locked properly")
+    }
+
+    /// Blocks the current task until the file descriptor gets ready for the given `interest`, registering the
+    /// file descriptor to the runtime as needed. The registration lives as long as this `AsyncFd`.
+    fn block(&self, interest: Interest, waker: task::Waker) {
+        let mut state = self.state();
+        match state.registration.as_mut() {
+            Some(registration) => {
+                registration.set_interest(registration.interest() | interest);
+                registration.block_for(interest, waker);
+            }
+            None => {
+                let mut registration = Reactor::register(&self.inner, interest);
+                registration.block(waker);
+                state.registration = Some(registration);
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for AsyncFd<T>
+where
+    T: os::fd::AsRawFd,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "AsyncFd({})", self.inner.as_raw_fd())
+    }
+}
+
+/// Represents the readiness of an `AsyncFd`, abstracting the IO demultiplexing of the Little Tokio runtime. It
+/// resolves immediately if the file descriptor is assumed to be ready, and otherwise once the runtime gets notified
+/// of an event on the file descriptor.
+pub struct Readiness<'fd, T>
+where
+    T: os::fd::AsRawFd,
+{
+    async_fd: &'fd AsyncFd<T>,
+    interest: Interest,
+    blocked: bool,
+}
+
+impl<'fd, T> Readiness<'fd, T>
+where
+    T: os::fd::AsRawFd,
+{
+    /// Creates a new `Readiness` instance from the specified `async_fd` and `interest`.
+    fn new(async_fd: &'fd AsyncFd<T>, interest: Interest) -> Self {
+        Self {
+            async_fd,
+            interest,
+            blocked: false,
+        }
+    }
+}
+
+pub type ReadinessOutput<'fd, T> = io::Result<ReadyGuard<'fd, T>>;
+
+impl<'fd, T> future::Future for Readiness<'fd, T>
+where
+    T: os::fd::AsRawFd,
+{
+    type Output = ReadinessOutput<'fd, T>;
+
+    fn poll(mut self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let guard = ReadyGuard {
+            async_fd: self.async_fd,
+            interest: self.interest,
+        };
+        // Note:
+        // Being woken up after blocking is considered as the readiness, which might be spurious. The spurious
+        // readiness gets cleared by the subsequent `ReadyGuard::try_io` which results in `WouldBlock`.
+        if self.blocked {
+            self.async_fd.state().set_ready(self.interest);
+            return task::Poll::Ready(Ok(guard));
+        }
+        if self.async_fd.state().is_ready(self.interest) {
+            return task::Poll::Ready(Ok(guard));
+        }
+        self.async_fd.block(self.interest, cx.waker().clone());
+        self.blocked = true;
+        task::Poll::Pending
+    }
+}
+
+/// Represents the error returned by `ReadyGuard::try_io` when the IO operation would block, i.e., the readiness
+/// has been cleared and the caller should wait for the readiness again.
+pub struct TryIoError(());
+
+impl fmt::Debug for TryIoError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "TryIoError")
+    }
+}
+
+/// Represents the readiness of an `AsyncFd` for a certain interest, which is returned by `AsyncFd::readable` and
+/// `AsyncFd::writable`.
+pub struct ReadyGuard<'fd, T>
+where
+    T: os::fd::AsRawFd,
+{
+    async_fd: &'fd AsyncFd<T>,
+    interest: Interest,
+}
+
+impl<'fd, T> ReadyGuard<'fd, T>
+where
+    T: os::fd::AsRawFd,
+{
+    /// Returns the shared reference to the wrapped `inner` of the `AsyncFd`.
+    pub fn get_ref(&self) -> &'fd T {
+        &self.async_fd.inner
+    }
+
+    /// Clears the readiness, so that the next `readable` or `writable` waits for an event on the file descriptor.
+    pub fn clear_ready(&mut self) {
+        self.async_fd.state().clear_ready(self.interest);
+    }
+
+    /// Performs the given IO operation `f` on the wrapped `inner`, and clears the readiness if it results in
+    /// `WouldBlock`, in which case `TryIoError` is returned instead.
+    pub fn try_io<R>(
+        &mut self,
+        f: impl FnOnce(&'fd T) -> io::Result<R>,
+    ) -> Result<io::Result<R>, TryIoError> {
+        match f(&self.async_fd.inner) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                self.clear_ready();
+                Err(TryIoError(()))
+            }
+            result => {
+                if let Some(registration) = self.async_fd.state().registration.as_mut() {
+                    registration.progress();
+                }
+                Ok(result)
+            }
+        }
+    }
+}
+
+impl<'fd, T> fmt::Debug for ReadyGuard<'fd, T>
+where
+    T: os::fd::AsRawFd,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "ReadyGuard({:?}, {:?})", self.async_fd, self.interest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read as _, Write as _};
+    use std::os::fd::FromRawFd as _;
+    use std::{fs, thread, time};
+
+    /// Creates a pipe whose both ends are in non-blocking mode.
+    fn pipe() -> (fs::File, fs::File) {
+        let mut fds = [0; 2];
+        // Safety:
+        // `fds` is valid for the two file descriptors written by `pipe(2)`, which are owned by the returned `File`s.
+        unsafe {
+            assert_eq!(libc::pipe(fds.as_mut_ptr()), 0);
+            for fd in fds {
+                let flags = libc::fcntl(fd, libc::F_GETFL);
+                assert_eq!(libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK), 0);
+            }
+            (fs::File::from_raw_fd(fds[0]), fs::File::from_raw_fd(fds[1]))
+        }
+    }
+
+    #[test]
+    fn readable_resolves_once_a_pipe_gets_written() {
+        let _serial = crate::tests::serial();
+        let (reader, mut writer) = pipe();
+        crate::block_on(async move {
+            let pipe = AsyncFd::new(reader);
+            let mut buffer = [0u8; 16];
+            let mut guard = pipe.readable().await.unwrap();
+            assert!(guard.try_io(|reader| (&*reader).read(&mut buffer)).is_err());
+            let mut readable = pin::pin!(pipe.readable());
+            let pending = future::poll_fn(|cx| {
+                task::Poll::Ready(future::Future::poll(readable.as_mut(), cx).is_pending())
+            })
+            .await;
+            assert!(pending);
+            assert!(Reactor::has_registrations());
+            thread::spawn(move || {
+                thread::sleep(time::Duration::from_millis(50));
+                writer.write_all(b"ping").unwrap();
+            });
+            // Note:
+            // The readiness resolved after blocking might be spurious, i.e., it is verified by the read.
+            let mut guard = readable.await.unwrap();
+            let size = loop {
+                if let Ok(result) = guard.try_io(|reader| (&*reader).read(&mut buffer)) {
+                    break result.unwrap();
+                }
+                guard = pipe.readable().await.unwrap();
+            };
+            assert_eq!(&buffer[..size], b"ping");
+        });
+    }
+
+    #[test]
+    fn readable_and_writable_keep_their_own_wakers() {
+        let _serial = crate::tests::serial();
+        let (stream, mut peer) = os::unix::net::UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        peer.set_nonblocking(true).unwrap();
+        while (&stream).write(&[0u8; 4096]).is_ok() {}
+        // Note:
+        // The connection is kept open until the writer task completes, which would fail with `EPIPE` otherwise.
+        let _open = peer.try_clone().unwrap();
+        let async_fd = sync::Arc::new(AsyncFd::new(stream));
+        let ret = crate::block_on_timeout(
+            async move {
+                let reading = async_fd.clone();
+                crate::spawn(async move {
+                    let mut buffer = [0u8; 16];
+                    let size = loop {
+                        let mut guard = reading.readable().await.unwrap();
+                        if let Ok(result) = guard.try_io(|inner| (&*inner).read(&mut buffer)) {
+                            break result.unwrap();
+                        }
+                    };
+                    assert_eq!(&buffer[..size], b"ping");
+                });
+                let writing = async_fd.clone();
+                crate::spawn(async move {
+                    loop {
+                        let mut guard = writing.writable().await.unwrap();
+                        if let Ok(result) = guard.try_io(|inner| (&*inner).write(b"pong")) {
+                            assert_eq!(result.unwrap(), 4);
+                            break;
+                        }
+                    }
+                });
+                thread::spawn(move || {
+                    thread::sleep(time::Duration::from_millis(50));
+                    let mut buffer = [0u8; 4096];
+                    while peer.read(&mut buffer).is_ok() {}
+                    peer.write_all(b"ping").unwrap();
+                });
+            },
+            time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+    }
+}
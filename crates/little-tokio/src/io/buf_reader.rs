@@ -0,0 +1,423 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of a buffered reader, which wraps an `AsyncRead` into an in-memory
+//! buffer, so that line-delimited protocols can be read without issuing a read per byte.
+
+use crate::io::{AsyncRead, AsyncWrite};
+use std::{cmp, fmt, future, io, pin, str, task};
+
+/// The default capacity of the buffer of a `BufReader`.
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// Represents a buffered reader, which reads from the inner source in chunks of up to the buffer capacity and
+/// serves the subsequent reads from the buffer. The source is read again only when the buffer gets exhausted.
+pub struct BufReader<R> {
+    /// Holds the inner source.
+    inner: R,
+    /// Holds the buffered bytes.
+    buffer: Box<[u8]>,
+    /// Holds the position of the first byte not consumed yet.
+    pos: usize,
+    /// Holds the number of bytes filled in the buffer.
+    filled: usize,
+}
+
+impl<R> BufReader<R> {
+    /// Creates a new `BufReader` with the default capacity of 8 KiB.
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Creates a new `BufReader` with the given `capacity`.
+    ///
+    /// # Panics:
+    /// Panics if the given `capacity` is zero.
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        assert!(
+            capacity > 0,
+            "capacity of the buffered reader should be positive"
+        );
+        Self {
+            inner,
+            buffer: vec![0u8; capacity].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    /// Returns the reference to the inner source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the mutable reference to the inner source.
+    ///
+    /// # Note:
+    /// Reading from the inner source directly skips the buffered bytes.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the bytes buffered but not consumed yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer[self.pos..self.filled]
+    }
+
+    /// Consumes this `BufReader`, returning the inner source.
+    ///
+    /// # Note:
+    /// The buffered bytes not consumed yet are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Marks the given `amount` of the buffered bytes consumed.
+    fn consume(&mut self, amount: usize) {
+        self.pos = cmp::min(self.pos + amount, self.filled);
+    }
+}
+
+impl<R> BufReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Returns the buffered bytes, reading from the inner source first if the buffer is exhausted. An empty slice
+    /// means the EOF.
+    fn poll_fill_buf(&mut self, cx: &mut task::Context<'_>) -> task::Poll<io::Result<&[u8]>> {
+        if self.pos >= self.filled {
+            let size = match pin::Pin::new(&mut self.inner).poll_read(cx, &mut self.buffer) {
+                task::Poll::Ready(Ok(size)) => size,
+                task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+                task::Poll::Pending => return task::Poll::Pending,
+            };
+            self.pos = 0;
+            self.filled = size;
+        }
+        task::Poll::Ready(Ok(self.buffer()))
+    }
+
+    /// Moves the bytes up to and including the `byte` from the buffer into the given `buffer`, accumulating the
+    /// number of bytes moved into `read`, until the `byte` or the EOF is found, or fails with `InvalidData` once
+    /// `limit` bytes have been moved without finding the `byte`.
+    fn poll_read_until(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        byte: u8,
+        buffer: &mut Vec<u8>,
+        read: &mut usize,
+        limit: usize,
+    ) -> task::Poll<io::Result<usize>> {
+        loop {
+            let remaining = limit - *read;
+            let (done, used) = match self.poll_fill_buf(cx) {
+                task::Poll::Ready(Ok(available)) => {
+                    let window = &available[..cmp::min(available.len(), remaining)];
+                    match window.iter().position(|&b| b == byte) {
+                        Some(index) => {
+                            buffer.extend_from_slice(&window[..=index]);
+                            (true, index + 1)
+                        }
+                        None => {
+                            buffer.extend_from_slice(window);
+                            (available.is_empty(), window.len())
+                        }
+                    }
+                }
+                task::Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => continue,
+                task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+                task::Poll::Pending => return task::Poll::Pending,
+            };
+            self.consume(used);
+            *read += used;
+            if done {
+                return task::Poll::Ready(Ok(*read));
+            }
+            if *read == limit {
+                return task::Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "line too long",
+                )));
+            }
+        }
+    }
+
+    /// Reads until the given `byte` or the EOF, appending the read bytes, including the `byte` if found, to the
+    /// given `buffer`, and returns the number of bytes appended. Zero bytes appended means the EOF.
+    ///
+    /// # Note:
+    /// The bytes are appended as soon as they are consumed from the buffer, so that the `buffer` holds all the
+    /// bytes read so far, and nothing else, even if the future gets dropped before the `byte` is found.
+    pub fn read_until<'reader, 'buffer>(
+        &'reader mut self,
+        byte: u8,
+        buffer: &'buffer mut Vec<u8>,
+    ) -> impl future::Future<Output = ReadUntilOutput> + 'reader
+    where
+        'buffer: 'reader,
+    {
+        let mut read = 0;
+        future::poll_fn(move |cx| self.poll_read_until(cx, byte, buffer, &mut read, usize::MAX))
+    }
+
+    /// Reads until the given `byte` or the EOF as `read_until` does, except that it fails with `InvalidData` if
+    /// more than `max_length` bytes, not counting the `byte`, are read without finding the `byte`, which protects
+    /// the memory from a peer never sending the `byte`.
+    ///
+    /// # Note:
+    /// The bytes read before the failure are appended to the `buffer`, and the bytes after them are left unread.
+    pub fn read_until_bounded<'reader, 'buffer>(
+        &'reader mut self,
+        byte: u8,
+        buffer: &'buffer mut Vec<u8>,
+        max_length: usize,
+    ) -> impl future::Future<Output = ReadUntilOutput> + 'reader
+    where
+        'buffer: 'reader,
+    {
+        let mut read = 0;
+        let limit = max_length.saturating_add(1);
+        future::poll_fn(move |cx| self.poll_read_until(cx, byte, buffer, &mut read, limit))
+    }
+
+    /// Reads until a newline, i.e., `b'\n'`, or the EOF, appending the read line, including the newline if found,
+    /// to the given `buffer`, and returns the number of bytes appended. Zero bytes appended means the EOF.
+    ///
+    /// # Note:
+    /// The line is appended only after the whole line has been read and validated as UTF-8, so that a multi-byte
+    /// character split across reads is never rejected, and the `buffer` is left untouched if the line is not valid
+    /// UTF-8, in which case an `InvalidData` error is returned. As a consequence, the bytes of a partially read line
+    /// are lost if the future gets dropped before the newline is found; use `read_until` when that matters.
+    pub fn read_line<'reader, 'buffer>(
+        &'reader mut self,
+        buffer: &'buffer mut String,
+    ) -> impl future::Future<Output = ReadLineOutput> + 'reader
+    where
+        'buffer: 'reader,
+    {
+        self.read_line_bounded(buffer, usize::MAX)
+    }
+
+    /// Reads until a newline or the EOF as `read_line` does, except that it fails with `InvalidData` if more than
+    /// `max_length` bytes, not counting the newline, are read without finding the newline, in which case the
+    /// `buffer` is left untouched.
+    pub fn read_line_bounded<'reader, 'buffer>(
+        &'reader mut self,
+        buffer: &'buffer mut String,
+        max_length: usize,
+    ) -> impl future::Future<Output = ReadLineOutput> + 'reader
+    where
+        'buffer: 'reader,
+    {
+        async move {
+            let mut line = Vec::new();
+            let size = self
+                .read_until_bounded(b'\n', &mut line, max_length)
+                .await?;
+            match str::from_utf8(&line) {
+                Ok(line) => {
+                    buffer.push_str(line);
+                    Ok(size)
+                }
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                )),
+            }
+        }
+    }
+}
+
+/// The output type of `BufReader::read_until` and `BufReader::read_until_bounded`.
+pub type ReadUntilOutput = io::Result<usize>;
+
+/// The output type of `BufReader::read_line` and `BufReader::read_line_bounded`.
+pub type ReadLineOutput = io::Result<usize>;
+
+impl<R> AsyncRead for BufReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &mut [u8],
+    ) -> task::Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        // Note:
+        // Bypasses the internal buffer when it is empty and the given buffer is at least as large as it, since
+        // buffering would only add a copy.
+        if this.pos >= this.filled && buffer.len() >= this.buffer.len() {
+            return pin::Pin::new(&mut this.inner).poll_read(cx, buffer);
+        }
+        let size = match this.poll_fill_buf(cx) {
+            task::Poll::Ready(Ok(available)) => {
+                let size = cmp::min(available.len(), buffer.len());
+                buffer[..size].copy_from_slice(&available[..size]);
+                size
+            }
+            task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+            task::Poll::Pending => return task::Poll::Pending,
+        };
+        this.consume(size);
+        task::Poll::Ready(Ok(size))
+    }
+}
+
+impl<R> AsyncWrite for BufReader<R>
+where
+    R: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &[u8],
+    ) -> task::Poll<io::Result<usize>> {
+        pin::Pin::new(&mut self.get_mut().inner).poll_write(cx, buffer)
+    }
+
+    fn poll_flush(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        pin::Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        pin::Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+impl<R> fmt::Debug for BufReader<R>
+where
+    R: fmt::Debug,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "BufReader {{ inner: {:?}, buffered: {}/{} }}",
+            self.inner,
+            self.filled - self.pos,
+            self.buffer.len()
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spawns a task writing the given `payload` into the returned `BufReader` with the given `capacity`, which
+    /// stops writing once the reader gets dropped.
+    fn reader_of(payload: Vec<u8>, capacity: usize) -> BufReader<crate::io::DuplexStream> {
+        let (mut one, two) = crate::io::duplex(4096);
+        crate::spawn(async move {
+            let mut written = 0;
+            while written < payload.len() {
+                match one.write(&payload[written..]).await {
+                    Ok(size) => written += size,
+                    Err(_) => return,
+                }
+            }
+        });
+        BufReader::with_capacity(capacity, two)
+    }
+
+    #[test]
+    fn read_until_splits_on_the_delimiter() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut reader = reader_of(b"one\ntwo\nthree".to_vec(), 2);
+            let mut buffer = Vec::new();
+            assert_eq!(reader.read_until(b'\n', &mut buffer).await.unwrap(), 4);
+            assert_eq!(reader.read_until(b'\n', &mut buffer).await.unwrap(), 4);
+            assert_eq!(reader.read_until(b'\n', &mut buffer).await.unwrap(), 5);
+            assert_eq!(reader.read_until(b'\n', &mut buffer).await.unwrap(), 0);
+            assert_eq!(buffer, b"one\ntwo\nthree");
+        });
+    }
+
+    #[test]
+    fn read_line_splits_lines_and_keeps_split_characters() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            // Note:
+            // The capacity of one byte splits the multi-byte characters across reads.
+            let mut reader = reader_of("héllo\nwörld\n".as_bytes().to_vec(), 1);
+            let mut line = String::new();
+            assert_eq!(reader.read_line(&mut line).await.unwrap(), 7);
+            assert_eq!(line, "héllo\n");
+            line.clear();
+            reader.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "wörld\n");
+            line.clear();
+            assert_eq!(reader.read_line(&mut line).await.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn read_line_rejects_invalid_utf8() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut reader = reader_of(b"\xff\xfe\n".to_vec(), 8);
+            let mut line = String::new();
+            let e = reader.read_line(&mut line).await.unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            assert!(line.is_empty());
+        });
+    }
+
+    #[test]
+    fn read_until_bounded_accepts_lines_up_to_the_max_length() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut reader = reader_of(b"1234\n12345\n".to_vec(), 3);
+            let mut buffer = Vec::new();
+            assert_eq!(
+                reader
+                    .read_until_bounded(b'\n', &mut buffer, 4)
+                    .await
+                    .unwrap(),
+                5
+            );
+            let e = reader
+                .read_until_bounded(b'\n', &mut buffer, 4)
+                .await
+                .unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(e.to_string(), "line too long");
+            assert_eq!(buffer, b"1234\n12345");
+        });
+    }
+
+    #[test]
+    fn read_line_bounded_fails_on_a_huge_payload_without_a_newline() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut reader = reader_of(vec![b'a'; 8 * 1024 * 1024], 8 * 1024);
+            let mut line = String::new();
+            let e = reader
+                .read_line_bounded(&mut line, 64 * 1024)
+                .await
+                .unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(e.to_string(), "line too long");
+            assert!(line.is_empty());
+        });
+    }
+}
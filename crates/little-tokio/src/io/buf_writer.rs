@@ -0,0 +1,370 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of a buffered writer, which coalesces small writes into an in-memory
+//! buffer, so that a protocol writing a few bytes at a time does not issue a system call per write.
+//!
+//! Since Rust has no asynchronous `Drop`, a `BufWriter` cannot flush its buffer when it gets dropped. Call `close`,
+//! or at least `flush`, before dropping it:
+//!
+//! ```
+//! little_tokio::block_on(async {
+//!     let (one, mut two) = little_tokio::io::duplex(64);
+//!     let mut writer = little_tokio::io::BufWriter::new(one);
+//!     writer.write_all(b"hello, ").await.unwrap();
+//!     writer.write_all(b"world").await.unwrap();
+//!     // Flushes the buffered bytes and shuts the writing down, i.e., the other end reads the EOF.
+//!     writer.close().await.unwrap();
+//!     let mut buffer = [0u8; 64];
+//!     let size = two.read(&mut buffer).await.unwrap();
+//!     assert_eq!(&buffer[..size], b"hello, world");
+//!     assert_eq!(two.read(&mut buffer).await.unwrap(), 0);
+//! });
+//! ```
+
+use crate::io::{AsyncRead, AsyncWrite};
+use std::{fmt, future, io, pin, task};
+
+/// The default capacity of the buffer of a `BufWriter`.
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// Represents a buffered writer, which accumulates the written bytes in the buffer and writes them into the inner
+/// sink once the buffer gets full, or it gets flushed. A write at least as large as the buffer bypasses it.
+///
+/// # Note:
+/// The bytes still buffered when a `BufWriter` gets dropped are lost, which is reported as a warning when the
+/// `trace` feature is enabled. Await `close` before dropping it.
+pub struct BufWriter<W> {
+    /// Holds the inner sink.
+    inner: W,
+    /// Holds the buffered bytes.
+    buffer: Vec<u8>,
+    /// Holds the number of the buffered bytes already written into the inner sink.
+    written: usize,
+    /// Holds the capacity of the buffer.
+    capacity: usize,
+}
+
+impl<W> BufWriter<W> {
+    /// Creates a new `BufWriter` with the default capacity of 8 KiB.
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Creates a new `BufWriter` with the given `capacity`.
+    ///
+    /// # Panics:
+    /// Panics if the given `capacity` is zero.
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        assert!(
+            capacity > 0,
+            "capacity of the buffered writer should be positive"
+        );
+        Self {
+            inner,
+            buffer: Vec::with_capacity(capacity),
+            written: 0,
+            capacity,
+        }
+    }
+
+    /// Returns the reference to the inner sink.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns the mutable reference to the inner sink.
+    ///
+    /// # Note:
+    /// Writing into the inner sink directly bypasses, and thus reorders the bytes relative to, the buffered bytes.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the bytes buffered but not written into the inner sink yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer[self.written..]
+    }
+}
+
+impl<W> BufWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Writes the buffered bytes into the inner sink until the buffer gets empty.
+    fn poll_flush_buf(&mut self, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+        while self.written < self.buffer.len() {
+            match pin::Pin::new(&mut self.inner).poll_write(cx, &self.buffer[self.written..]) {
+                task::Poll::Ready(Ok(0)) => {
+                    return task::Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the buffered data",
+                    )))
+                }
+                task::Poll::Ready(Ok(size)) => self.written += size,
+                task::Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+        }
+        self.buffer.clear();
+        self.written = 0;
+        task::Poll::Ready(Ok(()))
+    }
+
+    /// Writes the given `buffer`, returning the number of bytes written, which may be less than the length of the
+    /// `buffer`.
+    pub fn write<'writer, 'buffer>(
+        &'writer mut self,
+        buffer: &'buffer [u8],
+    ) -> impl future::Future<Output = WriteOutput> + 'writer
+    where
+        'buffer: 'writer,
+    {
+        future::poll_fn(move |cx| pin::Pin::new(&mut *self).poll_write(cx, buffer))
+    }
+
+    /// Writes the whole of the given `buffer`.
+    ///
+    /// # Note:
+    /// The future may get dropped after writing a part of the `buffer`, in which case it is unknown how many
+    /// bytes have been written.
+    pub fn write_all<'writer, 'buffer>(
+        &'writer mut self,
+        buffer: &'buffer [u8],
+    ) -> impl future::Future<Output = WriteAllOutput> + 'writer
+    where
+        'buffer: 'writer,
+    {
+        async move {
+            let mut buffer = buffer;
+            while !buffer.is_empty() {
+                match self.write(buffer).await {
+                    Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                    Ok(size) => buffer = &buffer[size..],
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Writes all the buffered bytes into the inner sink, and flushes the inner sink.
+    pub fn flush(&mut self) -> impl future::Future<Output = FlushOutput> + '_ {
+        future::poll_fn(move |cx| pin::Pin::new(&mut *self).poll_flush(cx))
+    }
+
+    /// Writes all the buffered bytes into the inner sink, flushes the inner sink, and shuts it down, e.g., the
+    /// peer of a connection reads the EOF once it has read all the bytes. This is what should be awaited before
+    /// dropping a `BufWriter`, in place of the asynchronous `Drop` which Rust lacks.
+    ///
+    /// # Note:
+    /// If the future fails or gets dropped, the bytes not written yet stay in the buffer, so that `close` can be
+    /// awaited again.
+    pub fn close(&mut self) -> impl future::Future<Output = CloseOutput> + '_ {
+        future::poll_fn(move |cx| pin::Pin::new(&mut *self).poll_shutdown(cx))
+    }
+}
+
+/// The output type of `BufWriter::write`.
+pub type WriteOutput = io::Result<usize>;
+
+/// The output type of `BufWriter::write_all`.
+pub type WriteAllOutput = io::Result<()>;
+
+/// The output type of `BufWriter::flush`.
+pub type FlushOutput = io::Result<()>;
+
+/// The output type of `BufWriter::close`.
+pub type CloseOutput = io::Result<()>;
+
+impl<W> AsyncWrite for BufWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &[u8],
+    ) -> task::Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.buffer.len() + buffer.len() > this.capacity {
+            match this.poll_flush_buf(cx) {
+                task::Poll::Ready(Ok(())) => {}
+                task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+        }
+        // Note:
+        // Bypasses the buffer when the given buffer is at least as large as it, since buffering would only add a
+        // copy.
+        if buffer.len() >= this.capacity {
+            return pin::Pin::new(&mut this.inner).poll_write(cx, buffer);
+        }
+        this.buffer.extend_from_slice(buffer);
+        task::Poll::Ready(Ok(buffer.len()))
+    }
+
+    fn poll_flush(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_flush_buf(cx) {
+            task::Poll::Ready(Ok(())) => pin::Pin::new(&mut this.inner).poll_flush(cx),
+            poll => poll,
+        }
+    }
+
+    fn poll_shutdown(
+        mut self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        match self.as_mut().poll_flush(cx) {
+            task::Poll::Ready(Ok(())) => pin::Pin::new(&mut self.get_mut().inner).poll_shutdown(cx),
+            poll => poll,
+        }
+    }
+}
+
+impl<W> AsyncRead for BufWriter<W>
+where
+    W: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &mut [u8],
+    ) -> task::Poll<io::Result<usize>> {
+        pin::Pin::new(&mut self.get_mut().inner).poll_read(cx, buffer)
+    }
+}
+
+impl<W> Drop for BufWriter<W> {
+    fn drop(&mut self) {
+        #[cfg(feature = "trace")]
+        if self.written < self.buffer.len() {
+            crate::trace::warn(format_args!(
+                "BufWriter dropped with {} unflushed bytes; await `close` before dropping it",
+                self.buffer.len() - self.written
+            ));
+        }
+    }
+}
+
+impl<W> fmt::Debug for BufWriter<W>
+where
+    W: fmt::Debug,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "BufWriter {{ inner: {:?}, buffered: {}/{} }}",
+            self.inner,
+            self.buffer.len() - self.written,
+            self.capacity
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::duplex;
+
+    /// Reads from the given `stream` until the EOF.
+    async fn read_to_end(stream: &mut duplex::DuplexStream) -> Vec<u8> {
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 256];
+        loop {
+            match stream.read(&mut buffer).await.unwrap() {
+                0 => return received,
+                size => received.extend_from_slice(&buffer[..size]),
+            }
+        }
+    }
+
+    /// Represents a sink which records the bytes written into it and the number of writes issued.
+    #[derive(Default)]
+    struct Recorder {
+        written: Vec<u8>,
+        writes: usize,
+    }
+
+    impl AsyncWrite for Recorder {
+        fn poll_write(
+            self: pin::Pin<&mut Self>,
+            _cx: &mut task::Context<'_>,
+            buffer: &[u8],
+        ) -> task::Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            this.written.extend_from_slice(buffer);
+            this.writes += 1;
+            task::Poll::Ready(Ok(buffer.len()))
+        }
+    }
+
+    #[test]
+    fn small_writes_stay_buffered_until_flushed() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut writer = BufWriter::with_capacity(16, Recorder::default());
+            writer.write_all(b"abc").await.unwrap();
+            writer.write_all(b"def").await.unwrap();
+            assert_eq!(writer.buffer(), b"abcdef");
+            assert_eq!(writer.get_ref().writes, 0);
+            writer.flush().await.unwrap();
+            assert!(writer.buffer().is_empty());
+            assert_eq!(writer.get_ref().written, b"abcdef");
+            assert_eq!(writer.get_ref().writes, 1);
+        });
+    }
+
+    #[test]
+    fn close_flushes_everything_and_shuts_the_writing_down() {
+        let _serial = crate::tests::serial();
+        let payload: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let expected = payload.clone();
+        crate::block_on(async move {
+            // Note:
+            // The duplex buffer is smaller than the payload, so that the writer has to wait for the reader while
+            // closing.
+            let (one, mut two) = duplex::duplex(128);
+            let reader = crate::spawn_with_output(async move { read_to_end(&mut two).await });
+            let mut writer = BufWriter::with_capacity(1024, one);
+            for chunk in payload.chunks(7) {
+                writer.write_all(chunk).await.unwrap();
+            }
+            assert!(!writer.buffer().is_empty());
+            writer.close().await.unwrap();
+            assert!(writer.buffer().is_empty());
+            assert_eq!(reader.await.unwrap(), expected);
+        });
+    }
+
+    #[test]
+    fn large_writes_bypass_the_buffer() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut writer = BufWriter::with_capacity(8, Recorder::default());
+            writer.write_all(b"ab").await.unwrap();
+            writer.write_all(b"0123456789").await.unwrap();
+            assert!(writer.buffer().is_empty());
+            assert_eq!(writer.get_ref().written, b"ab0123456789");
+            assert_eq!(writer.get_ref().writes, 2);
+        });
+    }
+}
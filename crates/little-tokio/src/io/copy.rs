@@ -0,0 +1,376 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of the copies between the `AsyncRead`s and the `AsyncWrite`s, e.g., the
+//! forwarding of a proxy between two connections in both directions.
+
+use crate::io::{AsyncRead, AsyncWrite};
+use std::{fmt, future, io, pin, task};
+
+/// The capacity of the buffer of each direction of a copy.
+const BUFFER_CAPACITY: usize = 8 * 1024;
+
+/// Copies all the bytes from the given `reader` into the given `writer` until the `reader` reads the EOF, flushes
+/// the `writer`, and returns the number of bytes copied.
+///
+/// # Note:
+/// The `writer` is not shut down, i.e., the peer of the `writer` does not read the EOF, so that more bytes can be
+/// written after the copy.
+pub fn copy<'copy, R, W>(
+    reader: &'copy mut R,
+    writer: &'copy mut W,
+) -> impl future::Future<Output = CopyOutput> + 'copy
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut transfer = Transfer::new();
+    future::poll_fn(move |cx| transfer.poll_copy(cx, &mut *reader, &mut *writer))
+}
+
+/// The output type of `copy`.
+pub type CopyOutput = io::Result<u64>;
+
+/// Copies the bytes between the given `one` and `two` in both directions, and returns the numbers of bytes copied
+/// from `one` into `two` and from `two` into `one`, e.g., forwards a proxied connection.
+///
+/// ```
+/// use little_tokio::io::AsyncWrite;
+///
+/// little_tokio::block_on(async {
+///     let (mut client, one) = little_tokio::io::duplex(64);
+///     let (two, mut server) = little_tokio::io::duplex(64);
+///     let proxy = little_tokio::spawn_with_output(async move {
+///         let (mut one, mut two) = (one, two);
+///         little_tokio::io::copy_bidirectional(&mut one, &mut two).await
+///     });
+///     client.write(b"ping").await.unwrap();
+///     std::future::poll_fn(|cx| std::pin::Pin::new(&mut client).poll_shutdown(cx)).await.unwrap();
+///     let mut buffer = [0u8; 64];
+///     assert_eq!(server.read(&mut buffer).await.unwrap(), 4);
+///     assert_eq!(server.read(&mut buffer).await.unwrap(), 0);
+///     server.write(b"pong!").await.unwrap();
+///     drop(server);
+///     assert_eq!(client.read(&mut buffer).await.unwrap(), 5);
+///     assert_eq!(proxy.await.unwrap().unwrap(), (4, 5));
+/// });
+/// ```
+///
+/// # Note:
+/// Each direction runs independently. Once a direction reads the EOF and has written all the bytes, the half-close
+/// is forwarded by shutting the writing side of the direction down, while the other direction keeps flowing, e.g.,
+/// a client which has shut down its writing after a request still receives the response. The future resolves once
+/// both of the directions are done, or either of them fails.
+pub fn copy_bidirectional<'copy, A, B>(
+    one: &'copy mut A,
+    two: &'copy mut B,
+) -> impl future::Future<Output = CopyBidirectionalOutput> + 'copy
+where
+    A: AsyncRead + AsyncWrite + Unpin + ?Sized,
+    B: AsyncRead + AsyncWrite + Unpin + ?Sized,
+{
+    let mut forward = Direction::new();
+    let mut backward = Direction::new();
+    future::poll_fn(move |cx| {
+        let forwarded = forward.poll(cx, &mut *one, &mut *two)?;
+        let backwarded = backward.poll(cx, &mut *two, &mut *one)?;
+        match (forwarded, backwarded) {
+            (task::Poll::Ready(forwarded), task::Poll::Ready(backwarded)) => {
+                task::Poll::Ready(Ok((forwarded, backwarded)))
+            }
+            _ => task::Poll::Pending,
+        }
+    })
+}
+
+/// The output type of `copy_bidirectional`.
+pub type CopyBidirectionalOutput = io::Result<(u64, u64)>;
+
+/// Represents the buffer of a copy in one direction.
+struct Transfer {
+    /// Holds the bytes read but not written yet.
+    buffer: Box<[u8]>,
+    /// Holds the position of the first byte not written yet.
+    pos: usize,
+    /// Holds the number of bytes filled in the buffer.
+    filled: usize,
+    /// Holds the number of bytes written so far.
+    amount: u64,
+    /// Specifies whether the reader has read the EOF.
+    eof: bool,
+}
+
+impl Transfer {
+    /// Creates a new `Transfer` with an empty buffer.
+    fn new() -> Self {
+        Self {
+            buffer: vec![0u8; BUFFER_CAPACITY].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+            amount: 0,
+            eof: false,
+        }
+    }
+
+    /// Copies the bytes from the given `reader` into the given `writer` until the EOF, and flushes the `writer`.
+    fn poll_copy<R, W>(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> task::Poll<io::Result<u64>>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        loop {
+            if self.pos == self.filled && !self.eof {
+                match pin::Pin::new(&mut *reader).poll_read(cx, &mut self.buffer) {
+                    task::Poll::Ready(Ok(0)) => self.eof = true,
+                    task::Poll::Ready(Ok(size)) => {
+                        self.pos = 0;
+                        self.filled = size;
+                    }
+                    task::Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+                    task::Poll::Pending => return task::Poll::Pending,
+                }
+            }
+            while self.pos < self.filled {
+                match pin::Pin::new(&mut *writer)
+                    .poll_write(cx, &self.buffer[self.pos..self.filled])
+                {
+                    task::Poll::Ready(Ok(0)) => {
+                        return task::Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::WriteZero,
+                            "failed to write the copied data",
+                        )))
+                    }
+                    task::Poll::Ready(Ok(size)) => {
+                        self.pos += size;
+                        self.amount += size as u64;
+                    }
+                    task::Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                    task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+                    task::Poll::Pending => return task::Poll::Pending,
+                }
+            }
+            if self.eof {
+                return match pin::Pin::new(&mut *writer).poll_flush(cx) {
+                    task::Poll::Ready(Ok(())) => task::Poll::Ready(Ok(self.amount)),
+                    task::Poll::Ready(Err(e)) => task::Poll::Ready(Err(e)),
+                    task::Poll::Pending => task::Poll::Pending,
+                };
+            }
+        }
+    }
+}
+
+/// Represents the state of one direction of `copy_bidirectional`.
+enum State {
+    /// The bytes are being copied.
+    Running,
+    /// The reader has read the EOF and all the bytes have been written, i.e., the half-close is being forwarded by
+    /// shutting the writer down.
+    ReadClosed,
+    /// The writer has been shut down after the given number of bytes.
+    Done(u64),
+}
+
+/// Represents one direction of `copy_bidirectional`.
+struct Direction {
+    /// Holds the buffer of the copy.
+    transfer: Transfer,
+    /// Holds the state of the direction.
+    state: State,
+}
+
+impl Direction {
+    /// Creates a new `Direction` in the `Running` state.
+    fn new() -> Self {
+        Self {
+            transfer: Transfer::new(),
+            state: State::Running,
+        }
+    }
+
+    /// Drives the direction from the given `reader` into the given `writer`, and returns the number of bytes copied
+    /// once the direction is done.
+    fn poll<R, W>(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> task::Poll<io::Result<u64>>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        loop {
+            match self.state {
+                State::Running => match self.transfer.poll_copy(cx, reader, writer) {
+                    task::Poll::Ready(Ok(_)) => self.state = State::ReadClosed,
+                    task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+                    task::Poll::Pending => return task::Poll::Pending,
+                },
+                State::ReadClosed => match pin::Pin::new(&mut *writer).poll_shutdown(cx) {
+                    task::Poll::Ready(Ok(())) => self.state = State::Done(self.transfer.amount),
+                    task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+                    task::Poll::Pending => return task::Poll::Pending,
+                },
+                State::Done(amount) => return task::Poll::Ready(Ok(amount)),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for State {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Running => write!(fmt, "Running")?,
+            State::ReadClosed => write!(fmt, "ReadClosed")?,
+            State::Done(amount) => write!(fmt, "Done({})", amount)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::duplex;
+    use crate::net::tcp;
+    use std::net;
+
+    /// Shuts the writing of the given `stream` down.
+    async fn shutdown<S>(stream: &mut S)
+    where
+        S: AsyncWrite + Unpin,
+    {
+        future::poll_fn(|cx| pin::Pin::new(&mut *stream).poll_shutdown(cx))
+            .await
+            .unwrap();
+    }
+
+    /// Reads from the given `stream` until the EOF.
+    async fn read_to_end<S>(stream: &mut S) -> Vec<u8>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 256];
+        loop {
+            match future::poll_fn(|cx| pin::Pin::new(&mut *stream).poll_read(cx, &mut buffer))
+                .await
+                .unwrap()
+            {
+                0 => return received,
+                size => received.extend_from_slice(&buffer[..size]),
+            }
+        }
+    }
+
+    #[test]
+    fn copy_copies_until_the_eof() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let (mut one, mut two) = duplex::duplex(16);
+            let (mut three, mut four) = duplex::duplex(16);
+            let payload: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+            let expected = payload.clone();
+            let writer = crate::spawn_with_output(async move {
+                let mut written = 0;
+                while written < payload.len() {
+                    written += one.write(&payload[written..]).await.unwrap();
+                }
+                shutdown(&mut one).await;
+            });
+            let reader = crate::spawn_with_output(async move { read_to_end(&mut four).await });
+            assert_eq!(copy(&mut two, &mut three).await.unwrap(), 1000);
+            // Note:
+            // The copy does not shut the writer down, so that the reader reads the EOF only once it gets dropped.
+            drop(three);
+            writer.await.unwrap();
+            assert_eq!(reader.await.unwrap(), expected);
+        });
+    }
+
+    #[test]
+    fn half_close_is_forwarded_without_terminating_the_other_direction() {
+        let _serial = crate::tests::serial();
+        let ret = crate::block_on_timeout(
+            async {
+                let (mut client, one) = duplex::duplex(16);
+                let (two, mut server) = duplex::duplex(16);
+                let proxy = crate::spawn_with_output(async move {
+                    let (mut one, mut two) = (one, two);
+                    copy_bidirectional(&mut one, &mut two).await
+                });
+                let response = vec![b'r'; 1000];
+                let expected = response.clone();
+                let server = crate::spawn_with_output(async move {
+                    // Note:
+                    // The server reads the EOF only if the proxy has forwarded the half-close of the client.
+                    let request = read_to_end(&mut server).await;
+                    let mut written = 0;
+                    while written < response.len() {
+                        written += server.write(&response[written..]).await.unwrap();
+                    }
+                    shutdown(&mut server).await;
+                    request
+                });
+                client.write(b"request").await.unwrap();
+                shutdown(&mut client).await;
+                assert_eq!(read_to_end(&mut client).await, expected);
+                assert_eq!(server.await.unwrap(), b"request");
+                assert_eq!(proxy.await.unwrap().unwrap(), (7, 1000));
+            },
+            std::time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+    }
+
+    #[test]
+    fn half_close_is_forwarded_over_tcp() {
+        let _serial = crate::tests::serial();
+        let ret = crate::block_on_timeout(
+            async {
+                let mut upstream = tcp::Listener::bind("127.0.0.1:0").unwrap();
+                let upstream_addr = upstream.local_addr().unwrap();
+                let mut frontend = tcp::Listener::bind("127.0.0.1:0").unwrap();
+                let frontend_addr = frontend.local_addr().unwrap();
+                let proxy = crate::spawn_with_output(async move {
+                    let (mut inbound, _) = frontend.accept().await.unwrap();
+                    let mut outbound = tcp::Stream::connect(upstream_addr).await.unwrap();
+                    copy_bidirectional(&mut inbound, &mut outbound).await
+                });
+                let server = crate::spawn_with_output(async move {
+                    let (mut stream, _) = upstream.accept().await.unwrap();
+                    let request = read_to_end(&mut stream).await;
+                    stream.write_all(b"response").await.unwrap();
+                    stream.shutdown(net::Shutdown::Write).await.unwrap();
+                    request
+                });
+                let mut client = tcp::Stream::connect(frontend_addr).await.unwrap();
+                client.write_all(b"request").await.unwrap();
+                client.shutdown(net::Shutdown::Write).await.unwrap();
+                assert_eq!(read_to_end(&mut client).await, b"response");
+                assert_eq!(server.await.unwrap(), b"request");
+                assert_eq!(proxy.await.unwrap().unwrap(), (7, 8));
+            },
+            std::time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of an in-memory duplex stream, i.e., a connected pair of streams where
+//! the bytes written to one end appear as the bytes read from the other end, which allows testing protocols without
+//! any sockets.
+
+use crate::io::{AsyncRead, AsyncWrite};
+use std::{cmp, collections, fmt, future, io, pin, sync, task};
+
+/// Creates a connected pair of `DuplexStream`s, each direction of which buffers at most `capacity` bytes. Writes
+/// wait while the buffer of the direction is full, and reads wait while it is empty.
+///
+/// ```
+/// little_tokio::block_on(async {
+///     let (mut client, mut server) = little_tokio::io::duplex(64);
+///     client.write(b"ping").await.unwrap();
+///     let mut buffer = [0u8; 64];
+///     let size = server.read(&mut buffer).await.unwrap();
+///     assert_eq!(&buffer[..size], b"ping");
+/// });
+/// ```
+///
+/// # Panics:
+/// Panics if the given `capacity` is zero.
+pub fn duplex(capacity: usize) -> (DuplexStream, DuplexStream) {
+    assert!(
+        capacity > 0,
+        "capacity of the duplex stream should be positive"
+    );
+    let one = sync::Arc::new(sync::Mutex::new(Pipe::new(capacity)));
+    let two = sync::Arc::new(sync::Mutex::new(Pipe::new(capacity)));
+    (
+        DuplexStream {
+            read: one.clone(),
+            write: two.clone(),
+        },
+        DuplexStream {
+            read: two,
+            write: one,
+        },
+    )
+}
+
+/// Represents one direction of a duplex stream.
+struct Pipe {
+    /// Holds the bytes written but not read yet.
+    buffer: collections::VecDeque<u8>,
+    /// Holds the maximal number of bytes buffered.
+    capacity: usize,
+    /// Holds the waker of the reader waiting for bytes.
+    read_waker: Option<task::Waker>,
+    /// Holds the waker of the writer waiting for a free space.
+    write_waker: Option<task::Waker>,
+    /// Specifies whether the reading end has been dropped.
+    read_closed: bool,
+    /// Specifies whether the writing end has been dropped.
+    write_closed: bool,
+}
+
+impl Pipe {
+    /// Creates a new `Pipe` with the given `capacity`.
+    fn new(capacity: usize) -> Self {
+        Self {
+            buffer: collections::VecDeque::with_capacity(capacity),
+            capacity,
+            read_waker: None,
+            write_waker: None,
+            read_closed: false,
+            write_closed: false,
+        }
+    }
+}
+
+/// Represents one end of an in-memory duplex stream created by `duplex`, which implements `AsyncRead` and
+/// `AsyncWrite` without any file descriptors nor the reactor. Dropping one end makes the other end read the EOF
+/// and fail to write with `io::ErrorKind::BrokenPipe`. Shutting down the writing of one end makes the other end read
+/// the EOF, while the former can keep reading.
+pub struct DuplexStream {
+    /// Holds the direction this end reads from.
+    read: sync::Arc<sync::Mutex<Pipe>>,
+    /// Holds the direction this end writes into.
+    write: sync::Arc<sync::Mutex<Pipe>>,
+}
+
+impl DuplexStream {
+    /// Reads the bytes written by the other end into the given `buffer`, returning `Ok(0)` once the other end has
+    /// been dropped and all the bytes have been read.
+    pub fn read<'stream>(
+        &'stream mut self,
+        buffer: &'stream mut [u8],
+    ) -> impl future::Future<Output = io::Result<usize>> + 'stream {
+        future::poll_fn(move |cx| pin::Pin::new(&mut *self).poll_read(cx, buffer))
+    }
+
+    /// Writes the bytes from the given `buffer` for the other end to read, returning the number of bytes written.
+    pub fn write<'stream>(
+        &'stream mut self,
+        buffer: &'stream [u8],
+    ) -> impl future::Future<Output = io::Result<usize>> + 'stream {
+        future::poll_fn(move |cx| pin::Pin::new(&mut *self).poll_write(cx, buffer))
+    }
+}
+
+/// Locks the given `pipe`.
+fn lock(pipe: &sync::Mutex<Pipe>) -> sync::MutexGuard<'_, Pipe> {
+    pipe.lock()
+        .expect("`MutexGuard` of the duplex stream should be locked properly")
+}
+
+impl AsyncRead for DuplexStream {
+    fn poll_read(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &mut [u8],
+    ) -> task::Poll<io::Result<usize>> {
+        let mut pipe = lock(&self.read);
+        if buffer.is_empty() {
+            return task::Poll::Ready(Ok(0));
+        }
+        if pipe.buffer.is_empty() {
+            if pipe.write_closed {
+                return task::Poll::Ready(Ok(0));
+            }
+            pipe.read_waker = Some(cx.waker().clone());
+            return task::Poll::Pending;
+        }
+        let size = cmp::min(buffer.len(), pipe.buffer.len());
+        for (dst, src) in buffer.iter_mut().zip(pipe.buffer.drain(..size)) {
+            *dst = src;
+        }
+        if let Some(waker) = pipe.write_waker.take() {
+            waker.wake();
+        }
+        task::Poll::Ready(Ok(size))
+    }
+}
+
+impl AsyncWrite for DuplexStream {
+    fn poll_write(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &[u8],
+    ) -> task::Poll<io::Result<usize>> {
+        let mut pipe = lock(&self.write);
+        if pipe.read_closed || pipe.write_closed {
+            return task::Poll::Ready(Err(io::Error::from(io::ErrorKind::BrokenPipe)));
+        }
+        if buffer.is_empty() {
+            return task::Poll::Ready(Ok(0));
+        }
+        let space = pipe.capacity - pipe.buffer.len();
+        if space == 0 {
+            pipe.write_waker = Some(cx.waker().clone());
+            return task::Poll::Pending;
+        }
+        let size = cmp::min(buffer.len(), space);
+        pipe.buffer.extend(&buffer[..size]);
+        if let Some(waker) = pipe.read_waker.take() {
+            waker.wake();
+        }
+        task::Poll::Ready(Ok(size))
+    }
+
+    fn poll_shutdown(
+        self: pin::Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let mut pipe = lock(&self.write);
+        pipe.write_closed = true;
+        if let Some(waker) = pipe.read_waker.take() {
+            waker.wake();
+        }
+        task::Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for DuplexStream {
+    fn drop(&mut self) {
+        let mut read = lock(&self.read);
+        read.read_closed = true;
+        if let Some(waker) = read.write_waker.take() {
+            waker.wake();
+        }
+        drop(read);
+        let mut write = lock(&self.write);
+        write.write_closed = true;
+        if let Some(waker) = write.read_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for DuplexStream {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "DuplexStream {{ .. }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_written_on_one_end_are_read_on_the_other() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let (mut one, mut two) = duplex(16);
+            let mut buffer = [0u8; 16];
+            assert_eq!(one.write(b"ping").await.unwrap(), 4);
+            assert_eq!(two.read(&mut buffer).await.unwrap(), 4);
+            assert_eq!(&buffer[..4], b"ping");
+            assert_eq!(two.write(b"pong").await.unwrap(), 4);
+            assert_eq!(one.read(&mut buffer).await.unwrap(), 4);
+            assert_eq!(&buffer[..4], b"pong");
+        });
+    }
+
+    #[test]
+    fn writes_wait_while_the_buffer_is_full() {
+        let _serial = crate::tests::serial();
+        let received = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let slot = received.clone();
+        crate::block_on(async move {
+            let (mut writer, mut reader) = duplex(4);
+            crate::spawn(async move {
+                let mut remaining: &[u8] = b"hello, world";
+                while !remaining.is_empty() {
+                    let size = writer.write(remaining).await.unwrap();
+                    assert!(size <= 4);
+                    remaining = &remaining[size..];
+                }
+            });
+            let mut buffer = [0u8; 64];
+            loop {
+                let size = reader.read(&mut buffer).await.unwrap();
+                if size == 0 {
+                    break;
+                }
+                slot.lock().unwrap().extend_from_slice(&buffer[..size]);
+            }
+        });
+        assert_eq!(*received.lock().unwrap(), b"hello, world");
+    }
+
+    #[test]
+    fn dropping_an_end_closes_both_directions() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let (mut one, two) = duplex(16);
+            drop(two);
+            let mut buffer = [0u8; 16];
+            assert_eq!(one.read(&mut buffer).await.unwrap(), 0);
+            let e = one.write(b"ping").await.unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+        });
+    }
+
+    #[test]
+    fn shutdown_closes_the_writing_only() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let (mut one, mut two) = duplex(16);
+            one.write(b"ping").await.unwrap();
+            future::poll_fn(|cx| pin::Pin::new(&mut one).poll_shutdown(cx))
+                .await
+                .unwrap();
+            let mut buffer = [0u8; 16];
+            assert_eq!(two.read(&mut buffer).await.unwrap(), 4);
+            assert_eq!(two.read(&mut buffer).await.unwrap(), 0);
+            let e = one.write(b"ping").await.unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+            two.write(b"pong").await.unwrap();
+            assert_eq!(one.read(&mut buffer).await.unwrap(), 4);
+            assert_eq!(&buffer[..4], b"pong");
+        });
+    }
+}
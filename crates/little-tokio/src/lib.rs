@@ -16,33 +16,525 @@
 //! The implementation is for self-study purpose only, so there might be some
 //! issues. Please use this crate at your own risk.
 
+pub mod codec;
 mod core;
+mod error;
+pub mod io;
 pub mod net;
+pub mod runtime;
+pub mod signal;
+pub mod sink;
+pub mod stream;
+pub mod sync;
 mod sys;
+pub mod task;
+#[cfg(feature = "test-util")]
+pub mod test;
+pub mod time;
+#[cfg(feature = "trace")]
+mod trace;
 pub mod utils;
 
+pub use crate::error::{Error, Result};
+
 use crate::core::reactor::Reactor;
 use crate::core::scheduler::{Scheduler, Status};
-use std::{future, marker};
+use std::{future, marker, pin};
 
 /// Runs a `Future` to completion on the Little Tokio runtime. This is the runtime’s entry point.
+///
+/// # Panics:
+/// Panics if the event loop fails or the runtime deadlocks, see `try_block_on` for propagating the failure instead.
 pub fn block_on(main: impl future::Future<Output = ()> + marker::Send + 'static) {
+    try_block_on(main).expect("should run the Little Tokio runtime properly")
+}
+
+/// Runs a `Future` to completion on the Little Tokio runtime, or returns the error the event loop fails with.
+/// In the latter case, all the remaining tasks and registrations are torn down, so that the runtime can be
+/// started over by the caller, e.g., a long-running server recovering from a transient failure.
+///
+/// # Note:
+/// The runtime reports a deadlock only when no `Waker` is alive, i.e., the deadlock detection is a heuristic which
+/// cannot tell whether a `Waker` will ever be woken. A task waiting for a channel whose `Sender` is alive but never
+/// sends, e.g., because it has been leaked or is held by the waiting task itself, keeps its `Waker` stored in the
+/// channel, so that the runtime waits forever instead of returning an error.
+pub fn try_block_on(
+    main: impl future::Future<Output = ()> + marker::Send + 'static,
+) -> std::io::Result<()> {
     // Spawns the main task.
     spawn(main);
-    // Performs the task execution if there are tasks that can be processed. Otherwise, turns the event loop.
+    if let Err(e) = run(None) {
+        Scheduler::reset();
+        Reactor::reset();
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Runs a `Future` to completion on the Little Tokio runtime and returns its output, or returns `None` if the
+/// runtime does not complete until the given `timeout` elapses. In the latter case, all the remaining tasks and
+/// registrations are torn down, so that this function never hangs for longer than the `timeout` plus the time
+/// spent in a single poll.
+///
+/// # Note:
+/// This is a safety net for test harnesses and batch jobs, and the runtime waits for all the spawned tasks as
+/// `block_on` does, i.e., not only for the given `main`. The runtime waiting for the `timeout` is never considered
+/// as deadlocked, i.e., a `main` which never completes results in `None`.
+///
+/// # Panics:
+/// Panics if the event loop fails, after tearing down the remaining tasks and registrations.
+pub fn block_on_timeout<F>(main: F, timeout: time::Duration) -> Option<F::Output>
+where
+    F: future::Future + marker::Send + 'static,
+    F::Output: marker::Send + 'static,
+{
+    let slot = std::sync::Arc::new(std::sync::Mutex::new(None));
+    let output = slot.clone();
+    spawn(async move {
+        let ret = main.await;
+        *output
+            .lock()
+            .expect("`MutexGuard` of the output slot should be locked properly") = Some(ret);
+    });
+    let ret = run(Some(time::Instant::now() + timeout));
+    if !matches!(ret, Ok(true)) {
+        Scheduler::reset();
+        Reactor::reset();
+    }
+    if !ret.expect("should run the Little Tokio runtime properly") {
+        return None;
+    }
+    let ret = slot
+        .lock()
+        .expect("`MutexGuard` of the output slot should be locked properly")
+        .take();
+    ret
+}
+
+/// Runs a `Future` to completion on the Little Tokio runtime, or until the given `signal` completes, e.g.,
+/// `signal::ctrl_c()`, whichever comes first. Once the `signal` completes, the runtime shuts down gracefully:
+///  - The `main` future is dropped, i.e., an accept loop running in it stops accepting new connections.
+///  - The tasks spawned from then on, from any thread, are dropped without being polled.
+///  - The tasks scheduled at that moment keep being polled until no task is scheduled anymore.
+///  - The tasks left parked, i.e., waiting for IO, timers or other tasks, are dropped, so that their file
+///    descriptors get deregistered.
+///
+/// # Note:
+/// The connection handlers parked on the peers when the `signal` completes are dropped as well. To drain them,
+/// have them wait for a shared `sync::Notify` besides their IO, and notify it with `notify_waiters` before the
+/// `signal` completes.
+///
+/// # Panics:
+/// Panics if the event loop fails or the runtime deadlocks.
+pub fn block_on_with_shutdown(
+    main: impl future::Future<Output = ()> + marker::Send + 'static,
+    signal: impl future::Future<Output = ()> + marker::Send + 'static,
+) {
+    spawn(async move {
+        let mut main = pin::pin!(main);
+        let mut signal = pin::pin!(signal);
+        future::poll_fn(|cx| {
+            if main.as_mut().poll(cx).is_ready() {
+                return std::task::Poll::Ready(());
+            }
+            if signal.as_mut().poll(cx).is_ready() {
+                Scheduler::shut_down();
+                return std::task::Poll::Ready(());
+            }
+            std::task::Poll::Pending
+        })
+        .await
+    });
+    let ret = run(None);
+    if ret.is_err() || Scheduler::is_shutting_down() {
+        Scheduler::reset();
+        Reactor::reset();
+    }
+    ret.expect("should run the Little Tokio runtime properly");
+}
+
+/// Performs the task execution if there are tasks that can be processed. Otherwise, turns the event loop. Returns
+/// `false` if the given `deadline` has passed before all the tasks complete, or the first error the event loop
+/// fails with. The runtime fails as deadlocked when the pending tasks wait for nothing that could ever wake them
+/// up, e.g., `future::pending()`, instead of waiting for the next events forever.
+///
+/// # Note:
+/// The runtime counts the live `Waker`s, i.e., a task waiting for a `Waker` held anywhere, e.g., by a channel, by
+/// another thread or by a `ParkToken`, is never considered as deadlocked. The flip side is that a task waiting for
+/// a channel which is never written is not detected as long as the channel holds its `Waker`. Besides, the runtime
+/// waiting for the given `deadline` is never considered as deadlocked.
+fn run(deadline: Option<time::Instant>) -> std::io::Result<bool> {
+    Scheduler::enter();
+    let ret = run_loop(deadline);
+    Scheduler::exit();
+    ret
+}
+
+/// Performs the iterations of the runtime for `run`.
+fn run_loop(deadline: Option<time::Instant>) -> std::io::Result<bool> {
     loop {
+        Scheduler::schedule_injected();
         for id in Scheduler::scheduled_ids() {
             Scheduler::poll(id);
         }
+        let timeout = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(time::Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => Some(remaining),
+                _ => return Ok(Scheduler::status() == Status::Done),
+            },
+            None => None,
+        };
+        // Note:
+        // The event loop is turned without waiting while there are still tasks scheduled, so that the tasks ready
+        // for IO are not starved by the tasks scheduling each other.
         match Scheduler::status() {
-            Status::RunningTasks => continue,
-            Status::WaitingForEvents => Reactor::turn(),
-            Status::Done => break,
+            Status::RunningTasks => Reactor::try_turn(Some(time::Duration::ZERO))?,
+            Status::WaitingForEvents
+                if deadline.is_none()
+                    && !Reactor::has_registrations()
+                    && !runtime::has_remote_wakers()
+                    && crate::core::waker::live() == 0 =>
+            {
+                return Err(std::io::Error::other(
+                    "runtime deadlocked: tasks parked but no waker, I/O or timer left to wake them",
+                ));
+            }
+            Status::WaitingForEvents => Reactor::try_turn(timeout)?,
+            Status::Done => return Ok(true),
         }
     }
 }
 
+/// Returns a `Handle` to the Little Tokio runtime, which can be sent to the other threads to spawn futures onto
+/// the runtime from there.
+///
+/// ```
+/// let (sender, mut receiver) = little_tokio::sync::channel();
+/// little_tokio::block_on(async move {
+///     let handle = little_tokio::handle();
+///     std::thread::spawn(move || {
+///         let answer = 6 * 7;
+///         handle.spawn(async move {
+///             sender.send(answer).unwrap();
+///         });
+///     });
+///     assert_eq!(receiver.recv().await, Some(42));
+/// });
+/// ```
+pub fn handle() -> runtime::Handle {
+    runtime::Handle::new()
+}
+
 /// Spawns a future onto the Little Tokio runtime.
+///
+/// # Note:
+/// The future is polled for the first time before this function returns, so that tasks are first polled in the
+/// order they have been spawned. Afterwards, tasks are polled in the order they have been woken up, in batches of
+/// at most 64 tasks between which the IO events are processed. A task woken during a batch, e.g., by waking itself
+/// to yield, is polled in a later batch, so that no task can be polled twice before the others woken earlier. The
+/// scheduling is cooperative, i.e., a task running a long computation without ever returning `Poll::Pending`
+/// blocks all the other tasks.
 pub fn spawn(task: impl future::Future<Output = ()> + marker::Send + 'static) {
     Scheduler::schedule(Box::pin(task));
 }
+
+/// Spawns a future which is not `Send`, e.g., one holding an `Rc`, onto the Little Tokio runtime. The future is
+/// scheduled onto the same single-threaded scheduler as the ones spawned by `spawn`, and polled for the first time
+/// before this function returns as well.
+///
+/// # Note:
+/// This is sound since the runtime polls all the tasks on the single thread calling `block_on`, i.e., it never
+/// moves tasks across threads. The runtime itself, however, is shared by the whole process, so that the future is
+/// pinned to the thread calling this function, which must be the thread running, or going to run, the runtime.
+///
+/// # Panics:
+/// Panics if the future gets polled on another thread, e.g., by a `block_on` called from another thread. The
+/// future is leaked rather than dropped if the runtime gets torn down on another thread.
+pub fn spawn_local(task: impl future::Future<Output = ()> + 'static) {
+    Scheduler::schedule(Box::pin(crate::core::task::Local::new(task)));
+}
+
+/// Spawns a future onto the Little Tokio runtime and returns a `JoinHandle` which completes with the output of
+/// the future.
+///
+/// # Note:
+/// The task is dropped without its output when it gets aborted via `JoinHandle::abort`, or when the runtime gets
+/// torn down, e.g., by `block_on_timeout`, or shuts down, in which case the `JoinHandle` completes with
+/// `Error::Cancelled` or `Error::Shutdown`.
+pub fn spawn_with_output<T>(
+    task: impl future::Future<Output = T> + marker::Send + 'static,
+) -> runtime::JoinHandle<T>
+where
+    T: marker::Send + 'static,
+{
+    spawn_joinable(task, false)
+}
+
+/// Spawns a future onto the Little Tokio runtime as `spawn_with_output` does, except that a panic of the future is
+/// caught rather than unwinding the runtime, in which case the `JoinHandle` completes with `Error::Panicked`.
+pub(crate) fn spawn_catching_panics<T>(
+    task: impl future::Future<Output = T> + marker::Send + 'static,
+) -> runtime::JoinHandle<T>
+where
+    T: marker::Send + 'static,
+{
+    spawn_joinable(task, true)
+}
+
+/// Spawns a future onto the Little Tokio runtime and returns a `JoinHandle` which completes with the output of
+/// the future, catching the panic of the future if `catching` is `true`.
+fn spawn_joinable<T>(
+    task: impl future::Future<Output = T> + marker::Send + 'static,
+    catching: bool,
+) -> runtime::JoinHandle<T>
+where
+    T: marker::Send + 'static,
+{
+    let (handle, completion) = runtime::JoinHandle::new();
+    spawn(async move {
+        let mut task = Box::pin(task);
+        let output = future::poll_fn(|cx| {
+            if completion.poll_aborted(cx) {
+                return std::task::Poll::Ready(None);
+            }
+            if !catching {
+                return task.as_mut().poll(cx).map(|output| Some(Ok(output)));
+            }
+            // Note:
+            // The panicked future is never polled again but dropped right below, i.e., no broken invariant of it
+            // can be observed afterwards.
+            match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| task.as_mut().poll(cx)))
+            {
+                Ok(poll) => poll.map(|output| Some(Ok(output))),
+                Err(payload) => std::task::Poll::Ready(Some(Err(Error::panicked(payload)))),
+            }
+        })
+        .await;
+        // Note:
+        // The aborted task is dropped before the `JoinHandle` completes with the error.
+        drop(task);
+        match output {
+            Some(Ok(output)) => completion.complete(output),
+            Some(Err(e)) => completion.fail(e),
+            None => {}
+        }
+    });
+    handle
+}
+
+/// Audits the file descriptors registered to the Little Tokio runtime and returns the number of stale ones, i.e.,
+/// the file descriptors closed without being deregistered, reporting each of them as a warning. The runtime also
+/// performs this audit periodically while turning the event loop.
+#[cfg(feature = "trace")]
+pub fn audit_registrations() -> usize {
+    Reactor::audit_registrations()
+}
+
+/// Resets the global state of the Little Tokio runtime, i.e., drops all the pending tasks and registrations and
+/// re-creates the underlying selector, so that sequential tests running in the same process do not observe each
+/// other's leftovers.
+///
+/// # Note:
+/// This function is only for tests and must not be called while a `block_on` is running.
+#[cfg(any(test, feature = "test-util"))]
+pub fn reset() {
+    Scheduler::reset();
+    Reactor::reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync, thread};
+
+    /// Serializes the tests running the Little Tokio runtime, which is a process-wide singleton, and resets the
+    /// runtime together with its configuration so that each test starts from the initial state. A test failing
+    /// while holding the lock does not poison the following tests.
+    pub(crate) fn serial() -> sync::MutexGuard<'static, ()> {
+        static LOCK: sync::Mutex<()> = sync::Mutex::new(());
+        let guard = LOCK.lock().unwrap_or_else(sync::PoisonError::into_inner);
+        runtime::Builder::new().apply();
+        reset();
+        guard
+    }
+
+    /// Yields to the other tasks once, i.e., wakes the current task up and returns `Poll::Pending` once.
+    pub(crate) async fn yield_now() {
+        let mut yielded = false;
+        future::poll_fn(|cx| {
+            if yielded {
+                return std::task::Poll::Ready(());
+            }
+            yielded = true;
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        })
+        .await
+    }
+
+    #[test]
+    fn block_on_runs_spawned_tasks_to_completion() {
+        let _serial = serial();
+        let done = sync::Arc::new(sync::atomic::AtomicUsize::new(0));
+        let counter = done.clone();
+        block_on(async move {
+            for _ in 0..3 {
+                let counter = counter.clone();
+                spawn(async move {
+                    counter.fetch_add(1, sync::atomic::Ordering::SeqCst);
+                });
+            }
+        });
+        assert_eq!(done.load(sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn try_block_on_reports_a_deadlock() {
+        let _serial = serial();
+        let started = time::Instant::now();
+        let ret = try_block_on(future::pending());
+        assert!(ret.is_err());
+        assert!(started.elapsed() < time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn try_block_on_does_not_detect_a_task_waiting_for_a_channel_never_written() {
+        let _serial = serial();
+        let runtime = thread::spawn(|| {
+            try_block_on(async {
+                let (sender, mut receiver) = crate::sync::channel::<()>();
+                receiver.recv().await;
+                drop(sender);
+            })
+        });
+        thread::sleep(time::Duration::from_millis(100));
+        // Note:
+        // The `Sender` is held by the waiting task itself, i.e., the channel is never written, but the `Waker` stored
+        // in the channel is alive, so that the runtime keeps waiting rather than failing as deadlocked. The runtime
+        // is shut down from here in order to finish the test.
+        assert!(!runtime.is_finished());
+        Scheduler::shut_down();
+        Reactor::wake();
+        assert!(runtime.join().unwrap().is_ok());
+        reset();
+    }
+
+    #[test]
+    fn try_block_on_waits_for_a_waker_woken_from_another_thread() {
+        let _serial = serial();
+        let ret = try_block_on(async {
+            let mut spawned = false;
+            future::poll_fn(|cx| {
+                if spawned {
+                    return std::task::Poll::Ready(());
+                }
+                spawned = true;
+                let waker = cx.waker().clone();
+                thread::spawn(move || {
+                    thread::sleep(time::Duration::from_millis(50));
+                    waker.wake();
+                });
+                std::task::Poll::Pending
+            })
+            .await
+        });
+        assert!(ret.is_ok());
+    }
+
+    #[test]
+    fn try_block_on_waits_for_a_sender_on_another_thread() {
+        let _serial = serial();
+        let (sender, mut receiver) = crate::sync::channel();
+        let received = sync::Arc::new(sync::Mutex::new(None));
+        let slot = received.clone();
+        let ret = try_block_on(async move {
+            thread::spawn(move || {
+                thread::sleep(time::Duration::from_millis(50));
+                sender.send(42).unwrap();
+            });
+            *slot.lock().unwrap() = receiver.recv().await;
+        });
+        assert!(ret.is_ok());
+        assert_eq!(*received.lock().unwrap(), Some(42));
+    }
+
+    #[test]
+    fn block_on_timeout_returns_the_output() {
+        let _serial = serial();
+        let ret = block_on_timeout(async { 42 }, time::Duration::from_secs(1));
+        assert_eq!(ret, Some(42));
+    }
+
+    #[test]
+    fn block_on_timeout_gives_up_a_pending_future_at_the_timeout() {
+        let _serial = serial();
+        let started = time::Instant::now();
+        let ret = block_on_timeout(future::pending::<()>(), time::Duration::from_millis(200));
+        assert_eq!(ret, None);
+        assert!(started.elapsed() >= time::Duration::from_millis(200));
+        // Note:
+        // The runtime is torn down, i.e., it can be started over.
+        assert_eq!(
+            block_on_timeout(async { 1 }, time::Duration::from_secs(1)),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn block_on_timeout_tears_down_the_parked_tasks_and_registrations() {
+        let _serial = serial();
+        struct Flag(sync::Arc<sync::atomic::AtomicBool>);
+        impl Drop for Flag {
+            fn drop(&mut self) {
+                self.0.store(true, sync::atomic::Ordering::SeqCst);
+            }
+        }
+        let dropped = sync::Arc::new(sync::atomic::AtomicBool::new(false));
+        let flag = Flag(dropped.clone());
+        let started = time::Instant::now();
+        let ret = block_on_timeout(
+            async move {
+                spawn(async move {
+                    let _flag = flag;
+                    let mut listener = net::tcp::Listener::bind("127.0.0.1:0").unwrap();
+                    let _ = listener.accept().await;
+                });
+                future::pending::<()>().await
+            },
+            time::Duration::from_millis(100),
+        );
+        assert_eq!(ret, None);
+        assert!(started.elapsed() < time::Duration::from_secs(1));
+        assert!(dropped.load(sync::atomic::Ordering::SeqCst));
+        assert_eq!(Scheduler::pending_len(), 0);
+        assert!(!Reactor::has_registrations());
+    }
+
+    #[test]
+    fn spawn_local_runs_futures_holding_rc_across_awaits() {
+        let _serial = serial();
+        let sum = sync::Arc::new(sync::atomic::AtomicUsize::new(0));
+        let total = sum.clone();
+        let log = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let before = log.clone();
+        // Note:
+        // The future spawned before `block_on` is pinned to this thread, which goes on to run the runtime.
+        spawn_local(async move {
+            before.borrow_mut().push("before");
+            yield_now().await;
+            before.borrow_mut().push("resumed");
+        });
+        block_on(async move {
+            spawn_local(async move {
+                let shared = std::rc::Rc::new(std::cell::Cell::new(0));
+                for n in 1..=4 {
+                    spawn_with_output(async move { n }).await.unwrap();
+                    shared.set(shared.get() + n);
+                }
+                total.store(shared.get(), sync::atomic::Ordering::SeqCst);
+            });
+        });
+        assert_eq!(sum.load(sync::atomic::Ordering::SeqCst), 10);
+        assert_eq!(*log.borrow(), ["before", "resumed"]);
+    }
+}
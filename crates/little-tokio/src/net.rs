@@ -15,3 +15,4 @@
 //! This module contains networking primitives for IO demultiplexing.
 
 pub mod tcp;
+pub mod udp;
@@ -14,34 +14,117 @@
 
 //! This module contains the implementation of TCP related network demultiplexing utilities.
 
+mod split;
+
+pub use self::split::{OwnedReadHalf, OwnedWriteHalf};
+
 use crate::core::interest::Interest;
 use crate::core::reactor::Reactor;
-use pin_project::{pin_project, pinned_drop};
+use crate::core::registration::Registration;
+use crate::core::scheduler::Scheduler;
+use crate::io::{AsyncRead, AsyncWrite};
+use crate::runtime;
+use crate::sync::spsc;
+use crate::sys::unix::net as sys;
+use pin_project::pin_project;
 use std::io::Read as _;
 use std::io::Write as _;
-use std::{future, io, net, ops, pin, task};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, RawFd};
+use std::{cmp, ffi, fmt, future, io, net, ops, pin, task, time};
+
+/// The size of the chunk `Stream::read_to_end` reads at once.
+const READ_TO_END_CHUNK: usize = 4096;
 
 /// Represents the Little Tokio wrapper arround a `TcpListener`. This wrapper is essentially equivalent to
 /// `TcpListener`. It implements `Deref` and `DerefMut` to delegate the underlying `TcpListener` methods.
 /// Additionally, this struct is responsible for `register` and/or `deregister` (IO demultiplexing) the
 /// network IO events to the Little Tokio runtime, which is the core part of this crate.
+///
+/// # Note:
+/// The listener is set non-blocking mode once by `bind` and must stay in it, i.e., calling
+/// `set_nonblocking(false)` through `Deref` blocks the whole runtime on the subsequent accepts.
 pub struct Listener {
     delegatee: net::TcpListener,
 }
 
 impl Listener {
-    /// Binds inner `TcpListener` to the given `addr` and sets it non-blocking mode.
+    /// Binds inner `TcpListener` to the given `addr` and sets it non-blocking mode. Binding to the port `0`
+    /// requests the OS to assign an ephemeral port, which can be retrieved via `local_addr`.
     pub fn bind(addr: impl net::ToSocketAddrs) -> io::Result<Self> {
         let delegatee = net::TcpListener::bind(addr)?;
         delegatee.set_nonblocking(true)?;
         Ok(Self { delegatee })
     }
 
+    /// Returns the local socket address this listener is bound to, including the port assigned by the OS
+    /// when the listener has been bound to the port `0`.
+    pub fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.delegatee.local_addr()
+    }
+
     /// Accepts the incoming connection and returns an `Accept` struct, which offers an abstraction over
     /// IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio runtime.
     pub fn accept(&mut self) -> impl future::Future<Output = AcceptOutput> + '_ {
         Accept::new(self)
     }
+
+    /// Returns an `Incoming` struct, which is a `Stream` of the incoming connections. The listener stays
+    /// registered to the runtime between the connections, i.e., the connections pending in the backlog are
+    /// accepted one after another without waiting for the next readiness event.
+    ///
+    /// ```ignore
+    /// let mut incoming = listener.incoming();
+    /// while let Some(connection) = incoming.next().await {
+    ///     little_tokio::spawn(handle(connection?));
+    /// }
+    /// ```
+    pub fn incoming(&mut self) -> Incoming<'_> {
+        Incoming::new(self)
+    }
+
+    /// Accepts up to `max` incoming connections at once and returns an `AcceptMany` struct, which offers an
+    /// abstraction over IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio runtime.
+    /// This drains a burst of pending connections in the backlog within a single readiness event.
+    pub fn accept_many(
+        &mut self,
+        max: usize,
+    ) -> impl future::Future<Output = AcceptManyOutput> + '_ {
+        AcceptMany::new(self, max)
+    }
+
+    /// Moves this listener into a task accepting the incoming connections, and returns a `Connections` struct,
+    /// which is a `Stream` of them. The accepted connections are sent into a bounded channel buffering at most
+    /// `buffer` of them, i.e., the task stops accepting while the channel is full, leaving the further connections
+    /// in the backlog, and resumes once the consumer has pulled a connection out. This decouples accepting from
+    /// handling, so that the consumer pulls the connections at its own pace.
+    ///
+    /// ```ignore
+    /// let mut connections = listener.into_stream(64);
+    /// while let Some(connection) = connections.next().await {
+    ///     let (stream, addr) = connection?;
+    ///     little_tokio::spawn(handle(stream, addr));
+    /// }
+    /// ```
+    ///
+    /// # Note:
+    /// The accept errors are sent into the channel as well, and the task keeps accepting after them. The task is
+    /// aborted, and thus the listener gets closed, once the `Connections` gets dropped.
+    ///
+    /// # Panics:
+    /// Panics if the given `buffer` is zero.
+    pub fn into_stream(self, buffer: usize) -> Connections {
+        let (mut sender, receiver) = spsc::channel(buffer);
+        let task = crate::spawn_with_output(async move {
+            let mut listener = self;
+            loop {
+                let connection = listener.accept().await;
+                if sender.send(connection).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Connections { receiver, task }
+    }
 }
 
 impl ops::Deref for Listener {
@@ -58,23 +141,47 @@ impl ops::DerefMut for Listener {
     }
 }
 
+impl AsRawFd for Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.delegatee.as_raw_fd()
+    }
+}
+
+impl AsFd for Listener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.delegatee.as_fd()
+    }
+}
+
+impl IntoRawFd for Listener {
+    /// Hands over the ownership of the file descriptor without closing it, e.g., to pass the listening socket to
+    /// another process or runtime for zero-downtime restarts. The `Listener` is registered to the runtime only
+    /// while an `Accept` or `AcceptMany` future is alive, and those futures borrow the `Listener`, so that the
+    /// returned file descriptor is never left registered.
+    fn into_raw_fd(self) -> RawFd {
+        self.delegatee.into_raw_fd()
+    }
+}
+
 /// Represents the acceptance of a TCP connection, abstracting the IO demultiplexing of the Little Tokio runtime.
 /// It provides the following two functionalities:
 ///  - Registration of the file descriptor to the runtime to monitor readiness for reading from the associated stream.
 ///  - Implementation of the `Future` trait for the event loop of the runtime to await read-ready events.
+///
+/// The file descriptor is registered lazily, i.e., only when the first attempt to accept returns `WouldBlock`, so
+/// that a connection already pending in the backlog is accepted without touching the runtime.
 pub struct Accept<'listener> {
     listener: &'listener mut Listener,
+    registration: Option<Registration>,
 }
 
 impl<'listener> Accept<'listener> {
-    /// Creates a new `Accept` instance from the specified `listener` and registers it to the runtime.
+    /// Creates a new `Accept` instance from the specified `listener`.
     fn new(listener: &'listener mut Listener) -> Self {
-        listener
-            .delegatee
-            .set_nonblocking(true)
-            .expect("should make the TCP listener non blocking properly");
-        Reactor::register(&listener.delegatee, Interest::READABLE);
-        Self { listener }
+        Self {
+            listener,
+            registration: None,
+        }
     }
 }
 
@@ -84,36 +191,335 @@ impl<'listener> future::Future for Accept<'listener> {
     type Output = AcceptOutput;
 
     fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
-        match self.listener.delegatee.accept() {
-            Ok((stream, addr)) => task::Poll::Ready(Ok((Stream::new(stream)?, addr))),
-            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                Reactor::block(&self.listener.delegatee, cx.waker().clone());
-                task::Poll::Pending
+        let this = self.get_mut();
+        poll_accept(this.listener, &mut this.registration, cx)
+    }
+}
+
+/// Attempts to accept an incoming connection of the given `listener`, blocking the current task on the given
+/// `registration` if there are no connections pending in the backlog. The `listener` is registered into the
+/// `registration` only at that point, unless it has been registered already.
+fn poll_accept(
+    listener: &Listener,
+    registration: &mut Option<Registration>,
+    cx: &mut task::Context<'_>,
+) -> task::Poll<AcceptOutput> {
+    // Note:
+    // The connections left in the backlog keep the listening socket readable, so that accepting them is simply
+    // retried once the task gets woken up, without waiting for another event.
+    if !Scheduler::admit_connection(cx.waker()) {
+        return task::Poll::Pending;
+    }
+    if !Scheduler::consume_accept_budget() {
+        cx.waker().wake_by_ref();
+        return task::Poll::Pending;
+    }
+    match sys::accept(&listener.delegatee) {
+        Ok((stream, addr)) => task::Poll::Ready(Ok((Stream::from_accepted(stream), addr))),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+            registration
+                .get_or_insert_with(|| Reactor::register(&listener.delegatee, Interest::READABLE))
+                .block(cx.waker().clone());
+            task::Poll::Pending
+        }
+        Err(e) => task::Poll::Ready(Err(e)),
+    }
+}
+
+/// Represents the incoming connections of a TCP listener as a `Stream`, abstracting the IO demultiplexing of the
+/// Little Tokio runtime. Unlike `Accept`, the registration of the file descriptor lives as long as this struct,
+/// i.e., it is shared by all the connections yielded. It is made lazily on the first `WouldBlock` as well.
+pub struct Incoming<'listener> {
+    listener: &'listener mut Listener,
+    registration: Option<Registration>,
+}
+
+impl<'listener> Incoming<'listener> {
+    /// Creates a new `Incoming` instance from the specified `listener`.
+    fn new(listener: &'listener mut Listener) -> Self {
+        Self {
+            listener,
+            registration: None,
+        }
+    }
+}
+
+impl<'listener> crate::stream::Stream for Incoming<'listener> {
+    type Item = io::Result<Stream>;
+
+    fn poll_next(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match poll_accept(this.listener, &mut this.registration, cx) {
+            task::Poll::Ready(Ok((stream, _))) => {
+                if let Some(registration) = this.registration.as_mut() {
+                    registration.progress();
+                }
+                task::Poll::Ready(Some(Ok(stream)))
             }
-            Err(e) => task::Poll::Ready(Err(e)),
+            task::Poll::Ready(Err(e)) => task::Poll::Ready(Some(Err(e))),
+            task::Poll::Pending => task::Poll::Pending,
         }
     }
 }
 
-impl<'listener> Drop for Accept<'listener> {
+/// Represents the connections accepted by the task which `Listener::into_stream` has spawned, as a `Stream`. The
+/// stream never ends by itself, and aborts the task when it gets dropped.
+pub struct Connections {
+    receiver: spsc::Receiver<AcceptOutput>,
+    task: runtime::JoinHandle<()>,
+}
+
+impl Connections {
+    /// Receives the next accepted connection, waiting for the task to accept one if none is buffered.
+    pub fn recv(&mut self) -> impl future::Future<Output = Option<AcceptOutput>> + '_ {
+        self.receiver.recv()
+    }
+}
+
+impl crate::stream::Stream for Connections {
+    type Item = AcceptOutput;
+
+    fn poll_next(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        crate::stream::Stream::poll_next(pin::Pin::new(&mut self.get_mut().receiver), cx)
+    }
+}
+
+impl Drop for Connections {
     fn drop(&mut self) {
-        Reactor::deregister(&self.listener.delegatee);
+        self.task.abort();
+    }
+}
+
+impl fmt::Debug for Connections {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Connections {{ .. }}")
+    }
+}
+
+/// Represents the acceptance of a batch of TCP connections, abstracting the IO demultiplexing of the Little Tokio
+/// runtime. It provides the following two functionalities:
+///  - Registration of the file descriptor to the runtime to monitor readiness for reading from the associated stream.
+///  - Implementation of the `Future` trait for the event loop of the runtime to await read-ready events.
+///
+/// The future resolves as soon as at least one connection has been accepted, i.e., it accepts connections until
+/// `accept` returns `WouldBlock` or `max` connections have been accepted. The file descriptor is registered lazily
+/// as is the case with `Accept`.
+pub struct AcceptMany<'listener> {
+    listener: &'listener mut Listener,
+    registration: Option<Registration>,
+    max: usize,
+}
+
+impl<'listener> AcceptMany<'listener> {
+    /// Creates a new `AcceptMany` instance from the specified `listener`.
+    fn new(listener: &'listener mut Listener, max: usize) -> Self {
+        Self {
+            listener,
+            registration: None,
+            max,
+        }
+    }
+}
+
+pub type AcceptManyOutput = io::Result<Vec<(Stream, net::SocketAddr)>>;
+
+impl<'listener> future::Future for AcceptMany<'listener> {
+    type Output = AcceptManyOutput;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut accepted = Vec::new();
+        while accepted.len() < this.max {
+            if !Scheduler::consume_accept_budget() {
+                if accepted.is_empty() {
+                    cx.waker().wake_by_ref();
+                    return task::Poll::Pending;
+                }
+                break;
+            }
+            match sys::accept(&this.listener.delegatee) {
+                Ok((stream, addr)) => accepted.push((Stream::from_accepted(stream), addr)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                // Note:
+                // The connections accepted so far are returned rather than dropped. A persistent error will be
+                // surfaced by the next acceptance again.
+                Err(_) if !accepted.is_empty() => break,
+                Err(e) => return task::Poll::Ready(Err(e)),
+            }
+        }
+        if accepted.is_empty() && this.max > 0 {
+            this.registration
+                .get_or_insert_with(|| {
+                    Reactor::register(&this.listener.delegatee, Interest::READABLE)
+                })
+                .block(cx.waker().clone());
+            return task::Poll::Pending;
+        }
+        task::Poll::Ready(Ok(accepted))
+    }
+}
+
+/// Represents the establishment of a TCP connection, abstracting the IO demultiplexing of the Little Tokio runtime.
+/// It provides the following two functionalities:
+///  - Registration of the file descriptor to the runtime to monitor readiness for writing to the associated stream.
+///  - Implementation of the `Future` trait for the event loop of the runtime to await write-ready events, which
+///    notify that the connection has been either established or failed.
+struct Connect {
+    // Note:
+    // The `registration` must be declared before the `connecting` so that it gets dropped, i.e., deregistered,
+    // while the file descriptor is still open.
+    registration: Option<Registration>,
+    connecting: Option<net::TcpStream>,
+    addrs: std::vec::IntoIter<net::SocketAddr>,
+    error: Option<io::Error>,
+}
+
+impl Connect {
+    /// Creates a new `Connect` instance which tries to connect to the addresses the given `addr` resolves to.
+    fn new(addr: impl net::ToSocketAddrs) -> Self {
+        let (addrs, error) = match addr.to_socket_addrs() {
+            Ok(addrs) => (addrs.collect::<Vec<_>>().into_iter(), None),
+            Err(e) => (Vec::new().into_iter(), Some(e)),
+        };
+        Self {
+            registration: None,
+            connecting: None,
+            addrs,
+            error,
+        }
+    }
+
+    /// Gives up the connection in progress, keeping the given `error` to report if no other addresses are left.
+    fn fail(&mut self, error: io::Error) {
+        self.registration = None;
+        self.connecting = None;
+        self.error = Some(error);
+    }
+}
+
+pub type ConnectOutput = io::Result<Stream>;
+
+impl future::Future for Connect {
+    type Output = ConnectOutput;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            if let Some(connecting) = this.connecting.as_ref() {
+                // Note:
+                // A socket becomes writable also when the connection has failed, e.g., it has been refused, so
+                // that `SO_ERROR` must be checked first. A connection which is neither established nor failed yet
+                // reports `NotConnected` for its peer address, i.e., the wake up has been spurious.
+                match connecting.take_error() {
+                    Ok(Some(e)) | Err(e) => {
+                        this.fail(e);
+                        continue;
+                    }
+                    Ok(None) => {}
+                }
+                match connecting.peer_addr() {
+                    Ok(_) => {
+                        this.registration = None;
+                        let stream = this
+                            .connecting
+                            .take()
+                            .expect("the connection in progress should be kept");
+                        return task::Poll::Ready(Ok(Stream::from_accepted(stream)));
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotConnected => {
+                        this.registration
+                            .get_or_insert_with(|| {
+                                Reactor::register(connecting, Interest::WRITABLE)
+                            })
+                            .block(cx.waker().clone());
+                        return task::Poll::Pending;
+                    }
+                    Err(e) => {
+                        this.fail(e);
+                        continue;
+                    }
+                }
+            }
+            let Some(addr) = this.addrs.next() else {
+                let error = this.error.take().unwrap_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "could not resolve to any addresses",
+                    )
+                });
+                return task::Poll::Ready(Err(error));
+            };
+            match sys::connect(&addr) {
+                Ok(connecting) => this.connecting = Some(connecting),
+                Err(e) => this.error = Some(e),
+            }
+        }
     }
 }
 
 /// Represents the Little Tokio wrapper arround a `TcpStream`. This wrapper is essentially equivalent to
-/// `TcpStream`. It implements `Deref` and `DerefMut` to delegate the underlying `TcpStream` methods.
-/// Additionally, this struct is responsible for `register` and/or `deregister` (IO demultiplexing) the
-/// network IO events to the Little Tokio runtime, which is the core part of this crate.
+/// `TcpStream`. It implements `Deref` to delegate the underlying `TcpStream` methods, and offers the dedicated
+/// methods for the common socket options, e.g., `set_nodelay` and `set_ttl`. Additionally, this struct is
+/// responsible for `register` and/or `deregister` (IO demultiplexing) the network IO events to the Little Tokio
+/// runtime, which is the core part of this crate.
+///
+/// # Note:
+/// The connection must stay in non-blocking mode, i.e., calling `set_nonblocking(false)` through `Deref` blocks
+/// the whole runtime on the subsequent IO operations. Hence, `DerefMut` is not implemented, so that the blocking
+/// `Read` and `Write` implementations of the `TcpStream` cannot be reached from this struct either.
 pub struct Stream {
+    // Note:
+    // The `registration` must be declared before the `delegatee` so that it gets dropped, i.e., deregistered,
+    // while the file descriptor is still open.
+    registration: Option<Registration>,
     delegatee: net::TcpStream,
 }
 
 impl Stream {
-    /// Creates a new `Stream` instance from the specified `stream` and sets it non-blocking mode.
-    fn new(stream: net::TcpStream) -> io::Result<Self> {
-        stream.set_nonblocking(true)?;
-        Ok(Self { delegatee: stream })
+    /// Creates a new `Stream` instance from the specified `stream` returned by `sys::accept` or `sys::connect`,
+    /// which is already in non-blocking mode.
+    fn from_accepted(stream: net::TcpStream) -> Self {
+        Self {
+            registration: None,
+            delegatee: stream,
+        }
+    }
+
+    /// Opens a connection to the given `addr` and returns a `Connect` struct, which offers an abstraction over
+    /// IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio runtime. If the `addr` resolves
+    /// to several addresses, each of them is tried in order until a connection succeeds.
+    ///
+    /// # Note:
+    /// The resolution of the `addr` is performed synchronously, i.e., resolving a host name blocks the runtime.
+    pub fn connect(addr: impl net::ToSocketAddrs) -> impl future::Future<Output = ConnectOutput> {
+        Connect::new(addr)
+    }
+
+    /// Opens a connection to the given `addr` as `connect` does, but gives up with a `TimedOut` error if the
+    /// connection has not been established before the given `duration` has elapsed.
+    ///
+    /// # Note:
+    /// The `duration` bounds the whole attempt, i.e., it is shared by all the addresses the `addr` resolves to. On
+    /// timeout, the half-open socket is deregistered from the runtime and closed.
+    pub fn connect_timeout(
+        addr: impl net::ToSocketAddrs,
+        duration: time::Duration,
+    ) -> impl future::Future<Output = ConnectOutput> {
+        let connect = crate::time::timeout(duration, Connect::new(addr));
+        async move {
+            connect.await.unwrap_or_else(|_| {
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "connection timed out",
+                ))
+            })
+        }
     }
 
     /// Reads from the incoming connection and returns an `Read` struct, which offers an abstraction over
@@ -128,6 +534,74 @@ impl Stream {
         Read::new(self, buffer)
     }
 
+    /// Reads from the incoming connection until the given `buffer` is completely filled, awaiting the readiness of
+    /// the connection again on each short read. Returns `io::ErrorKind::UnexpectedEof` if the connection gets
+    /// closed before the `buffer` is filled.
+    ///
+    /// # Note:
+    /// The future may get dropped after reading a part of the `buffer`, in which case the bytes read so far are
+    /// lost for the caller.
+    pub fn read_exact<'stream, 'buffer>(
+        &'stream mut self,
+        buffer: &'buffer mut [u8],
+    ) -> impl future::Future<Output = ReadExactOutput> + 'stream
+    where
+        'buffer: 'stream,
+    {
+        async move {
+            let mut offset = 0;
+            while offset < buffer.len() {
+                match self.read(&mut buffer[offset..]).await {
+                    Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                    Ok(size) => offset += size,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Reads from the incoming connection until the EOF, appending the read bytes to the given `buffer`, and
+    /// returns the number of bytes appended. Each read awaits the readiness of the connection again.
+    ///
+    /// # Note:
+    /// The bytes are read into an intermediate chunk and appended once read, so that the `buffer` holds all the
+    /// bytes read so far, and nothing else, even if the future gets dropped before the EOF.
+    pub fn read_to_end<'stream, 'buffer>(
+        &'stream mut self,
+        buffer: &'buffer mut Vec<u8>,
+    ) -> impl future::Future<Output = ReadOutput> + 'stream
+    where
+        'buffer: 'stream,
+    {
+        async move {
+            let mut chunk = [0u8; READ_TO_END_CHUNK];
+            let mut total = 0;
+            loop {
+                match self.read(&mut chunk).await {
+                    Ok(0) => return Ok(total),
+                    Ok(size) => {
+                        buffer.extend_from_slice(&chunk[..size]);
+                        total += size;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+
+    /// Reads from the incoming connection into the given `buffers` in order, i.e., performs a scatter read, and
+    /// returns an `ReadVectored` struct, which offers an abstraction over IO demultiplexing using the Rust's
+    /// `Future` runtime, i.e., the Little Tokio runtime.
+    pub fn read_vectored<'stream, 'data>(
+        &'stream mut self,
+        buffers: &'stream mut [io::IoSliceMut<'data>],
+    ) -> ReadVectored<'stream, 'data> {
+        ReadVectored::new(self, buffers)
+    }
+
     /// Writes to the outgoing connection and returns an `Write` struct, which offers an abstraction over
     /// IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio runtime.
     pub fn write<'stream, 'buffer>(
@@ -139,6 +613,313 @@ impl Stream {
     {
         Write::new(self, buffer)
     }
+
+    /// Writes the whole of the given `buffer` to the outgoing connection, awaiting the writability of the
+    /// connection again on each short write until everything has been written.
+    ///
+    /// # Note:
+    /// The future may get dropped after writing a part of the `buffer`, in which case it is unknown how many
+    /// bytes have been written.
+    pub fn write_all<'stream, 'buffer>(
+        &'stream mut self,
+        buffer: &'buffer [u8],
+    ) -> impl future::Future<Output = WriteAllOutput> + 'stream
+    where
+        'buffer: 'stream,
+    {
+        async move {
+            let mut buffer = buffer;
+            while !buffer.is_empty() {
+                match self.write(buffer).await {
+                    Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                    Ok(size) => buffer = &buffer[size..],
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Waits until all the data written so far has been transmitted to and acknowledged by the peer, i.e., the
+    /// kernel send buffer has been drained, and returns a `Flush` struct, which offers an abstraction over IO
+    /// demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio runtime.
+    ///
+    /// # Note:
+    /// Since `Stream` buffers nothing in the user space, a successful write only means that the data has been
+    /// copied into the kernel. This method additionally awaits the acknowledgement of the data, which does not
+    /// mean that the peer application has read it. The unsent bytes are checked each time the connection gets
+    /// writable, since the send buffer shrinks when the data is acknowledged, and periodically on a backoff timer,
+    /// since the connection which never stops being writable reports no write-ready event.
+    pub fn flush(&self) -> impl future::Future<Output = FlushOutput> + '_ {
+        Flush::new(self)
+    }
+
+    /// Shuts down the reading, the writing, or both halves of the connection as specified by `how`, e.g., the peer
+    /// reads the EOF once it has read all the data written so far when the writing half gets shut down. The
+    /// `Interest`s of the halves shut down are deregistered from the runtime, so that the stale readiness events
+    /// of them do not wake up the task anymore.
+    ///
+    /// # Note:
+    /// Since shutting down a connection never blocks, the returned future resolves on its first poll.
+    pub async fn shutdown(&mut self, how: net::Shutdown) -> ShutdownOutput {
+        self.delegatee.shutdown(how)?;
+        match how {
+            net::Shutdown::Read => self.unblock(Interest::READABLE),
+            net::Shutdown::Write => self.unblock(Interest::WRITABLE),
+            net::Shutdown::Both => self.registration = None,
+        }
+        Ok(())
+    }
+
+    /// Returns the socket address of the remote peer of the connection.
+    pub fn peer_addr(&self) -> io::Result<net::SocketAddr> {
+        self.delegatee.peer_addr()
+    }
+
+    /// Returns the socket address of the local half of the connection.
+    pub fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.delegatee.local_addr()
+    }
+
+    /// Splits the connection into the owned read and write halves, which can be moved into two different tasks to
+    /// read and write the connection concurrently.
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        let Self {
+            registration,
+            delegatee,
+        } = self;
+        drop(registration);
+        split::split(delegatee)
+    }
+
+    /// Tries to read from the incoming connection without awaiting, i.e., returns `io::ErrorKind::WouldBlock`
+    /// if there is no data available yet. This is the building block for driving external state machines on
+    /// top of the Little Tokio runtime together with `readable`.
+    pub fn try_read(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        (&self.delegatee).read(buffer)
+    }
+
+    /// Tries to write to the outgoing connection without awaiting, i.e., returns `io::ErrorKind::WouldBlock`
+    /// if the send buffer is full. This is the building block for driving external state machines on top of
+    /// the Little Tokio runtime together with `writable`.
+    pub fn try_write(&self, buffer: &[u8]) -> io::Result<usize> {
+        (&self.delegatee).write(buffer)
+    }
+
+    /// Waits for the connection to become readable and returns an `Ready` struct, which offers an abstraction
+    /// over IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio runtime.
+    ///
+    /// # Note:
+    /// The `Ready` resolves only once the runtime has signaled the connection readable, but the readiness might
+    /// have been consumed elsewhere in the meantime, so that the subsequent `try_read` may still return `WouldBlock`.
+    pub fn readable(&self) -> impl future::Future<Output = ReadyOutput> + '_ {
+        Ready::new(self, Interest::READABLE)
+    }
+
+    /// Waits for the connection to become writable and returns an `Ready` struct, which offers an abstraction
+    /// over IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio runtime.
+    ///
+    /// # Note:
+    /// The `Ready` resolves only once the runtime has signaled the connection writable, but the readiness might
+    /// have been consumed elsewhere in the meantime, so that the subsequent `try_write` may still return
+    /// `WouldBlock`.
+    pub fn writable(&self) -> impl future::Future<Output = ReadyOutput> + '_ {
+        Ready::new(self, Interest::WRITABLE)
+    }
+
+    /// Gets the value of the socket option specified by the `level` and the `name`, e.g., `IPPROTO_TCP` and
+    /// `TCP_MAXSEG`. This is the escape hatch for the options which have no dedicated methods.
+    ///
+    /// # Safety
+    /// The caller must ensure that `T` has the exact size and layout the kernel writes for the option, and that any
+    /// bit pattern written by the kernel is a valid value of `T`. Use `get_sockopt_int` for the common `c_int`
+    /// valued options instead.
+    pub unsafe fn get_sockopt<T>(&self, level: ffi::c_int, name: ffi::c_int) -> io::Result<T>
+    where
+        T: Copy,
+    {
+        sys::getsockopt(self.delegatee.as_raw_fd(), level, name)
+    }
+
+    /// Sets the value of the socket option specified by the `level` and the `name`, e.g., `IPPROTO_IP` and
+    /// `IP_TOS`. This is the escape hatch for the options which have no dedicated methods.
+    ///
+    /// # Safety
+    /// The caller must ensure that `T` has the exact size and layout the kernel reads for the option. Use
+    /// `set_sockopt_int` for the common `c_int` valued options instead.
+    pub unsafe fn set_sockopt<T>(
+        &self,
+        level: ffi::c_int,
+        name: ffi::c_int,
+        value: T,
+    ) -> io::Result<()>
+    where
+        T: Copy,
+    {
+        sys::setsockopt(self.delegatee.as_raw_fd(), level, name, value)
+    }
+
+    /// Gets the value of the `c_int` valued socket option specified by the `level` and the `name`.
+    pub fn get_sockopt_int(&self, level: ffi::c_int, name: ffi::c_int) -> io::Result<ffi::c_int> {
+        // Safety:
+        // `c_int` is valid for any bit pattern, and the size of the value is checked against the one the kernel
+        // reports, so that non `c_int` valued options result in an error.
+        unsafe { self.get_sockopt(level, name) }
+    }
+
+    /// Sets the value of the `c_int` valued socket option specified by the `level` and the `name`.
+    pub fn set_sockopt_int(
+        &self,
+        level: ffi::c_int,
+        name: ffi::c_int,
+        value: ffi::c_int,
+    ) -> io::Result<()> {
+        // Safety:
+        // The kernel validates the size of the value, so that non `c_int` valued options result in an error.
+        unsafe { self.set_sockopt(level, name, value) }
+    }
+
+    /// Sets the `TCP_NODELAY` option, i.e., disables the Nagle's algorithm if `nodelay` is `true`, so that small
+    /// writes are sent immediately instead of being coalesced. The option is kept for the lifetime of the connection.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.delegatee.set_nodelay(nodelay)
+    }
+
+    /// Gets the `TCP_NODELAY` option. See `set_nodelay` for the semantics.
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.delegatee.nodelay()
+    }
+
+    /// Sets the `IP_TTL` option, i.e., the time-to-live of the IP packets sent from this connection.
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.delegatee.set_ttl(ttl)
+    }
+
+    /// Gets the `IP_TTL` option. See `set_ttl` for the semantics.
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.delegatee.ttl()
+    }
+
+    /// Sets the `SO_LINGER` option, i.e., how the connection gets closed while there is unsent data. `None` closes
+    /// the connection gracefully in the background, `Some(Duration::ZERO)` resets the connection by sending `RST`
+    /// and discards the unsent data, and the other durations bound the time to flush the unsent data in seconds.
+    ///
+    /// # Note:
+    /// Although the socket is in non-blocking mode, a non-zero linger makes the `close(2)` of the dropped `Stream`
+    /// block the runtime until the unsent data has been flushed or the linger has elapsed.
+    pub fn set_linger(&self, linger: Option<time::Duration>) -> io::Result<()> {
+        let value = libc::linger {
+            l_onoff: ffi::c_int::from(linger.is_some()),
+            l_linger: linger.map_or(0, |linger| {
+                linger.as_secs().min(ffi::c_int::MAX as u64) as ffi::c_int
+            }),
+        };
+        // Safety:
+        // `linger` is the exact type the kernel reads for `SO_LINGER`.
+        unsafe { self.set_sockopt(libc::SOL_SOCKET, sys::SO_LINGER, value) }
+    }
+
+    /// Gets the `SO_LINGER` option. See `set_linger` for the semantics.
+    pub fn linger(&self) -> io::Result<Option<time::Duration>> {
+        // Safety:
+        // `linger` is the exact type the kernel writes for `SO_LINGER` and is valid for any bit pattern.
+        let value: libc::linger = unsafe { self.get_sockopt(libc::SOL_SOCKET, sys::SO_LINGER)? };
+        Ok((value.l_onoff != 0).then(|| time::Duration::from_secs(value.l_linger as u64)))
+    }
+
+    /// Blocks the current task until the connection gets ready for the given `interest`, registering the
+    /// connection to the runtime as needed. The registration lives as long as this `Stream`.
+    fn block(&mut self, interest: Interest, waker: task::Waker) {
+        match self.registration.as_mut() {
+            Some(registration) => {
+                registration.set_interest(registration.interest() | interest);
+                registration.block_for(interest, waker);
+            }
+            None => {
+                let mut registration = Reactor::register(&self.delegatee, interest);
+                registration.block(waker);
+                self.registration = Some(registration);
+            }
+        }
+    }
+
+    /// Notifies the registration that an IO operation has made a progress.
+    fn progress(&mut self) {
+        if let Some(registration) = self.registration.as_mut() {
+            registration.progress();
+        }
+    }
+
+    /// Stops monitoring the given `interest` once the IO operation has succeeded, keeping the other interest
+    /// registered. Since sockets are writable most of the time, keeping a stale writable interest would make
+    /// the runtime wake up the task for spurious write-ready events.
+    fn unblock(&mut self, interest: Interest) {
+        let Some(registration) = self.registration.as_mut() else {
+            return;
+        };
+        let remaining = registration.interest().remove(interest);
+        if remaining.is_empty() {
+            self.registration = None;
+        } else {
+            registration.set_interest(remaining);
+        }
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &mut [u8],
+    ) -> task::Poll<io::Result<usize>> {
+        match self.try_read(buffer) {
+            Ok(size) => {
+                self.get_mut().progress();
+                task::Poll::Ready(Ok(size))
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                self.get_mut().block(Interest::READABLE, cx.waker().clone());
+                task::Poll::Pending
+            }
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &[u8],
+    ) -> task::Poll<io::Result<usize>> {
+        if buffer.is_empty() {
+            return task::Poll::Ready(Ok(0));
+        }
+        match self.try_write(buffer) {
+            Ok(size) => {
+                let this = self.get_mut();
+                this.unblock(Interest::WRITABLE);
+                this.progress();
+                task::Poll::Ready(Ok(size))
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                self.get_mut().block(Interest::WRITABLE, cx.waker().clone());
+                task::Poll::Pending
+            }
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+
+    fn poll_shutdown(
+        self: pin::Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.delegatee.shutdown(net::Shutdown::Write)?;
+        this.unblock(Interest::WRITABLE);
+        task::Poll::Ready(Ok(()))
+    }
 }
 
 impl ops::Deref for Stream {
@@ -149,9 +930,28 @@ impl ops::Deref for Stream {
     }
 }
 
-impl ops::DerefMut for Stream {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.delegatee
+impl AsRawFd for Stream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.delegatee.as_raw_fd()
+    }
+}
+
+impl AsFd for Stream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.delegatee.as_fd()
+    }
+}
+
+impl IntoRawFd for Stream {
+    /// Deregisters the connection from the runtime and hands over the ownership of the file descriptor, so that
+    /// the runtime never keeps monitoring a file descriptor it no longer owns.
+    fn into_raw_fd(self) -> RawFd {
+        let Self {
+            registration,
+            delegatee,
+        } = self;
+        drop(registration);
+        delegatee.into_raw_fd()
     }
 }
 
@@ -159,37 +959,40 @@ impl ops::DerefMut for Stream {
 /// It provides the following two functionalities:
 ///  - Registration of the file descriptor to the runtime to monitor readiness for reading from the associated stream.
 ///  - Implementation of the `Future` trait for the event loop of the runtime to await read-ready events.
-#[pin_project(PinnedDrop)]
+///
+/// The registration is the one of the associated stream, which is made lazily on the first `WouldBlock` and lives
+/// as long as the stream, so that a loop creating a `Read` per iteration does not register the file descriptor
+/// again and again.
+#[pin_project]
 struct Read<'stream, 'buffer> {
     stream: &'stream mut Stream,
     buffer: &'buffer mut [u8],
 }
 
 impl<'stream, 'buffer> Read<'stream, 'buffer> {
-    /// Creates a new `Read` instance from the specified `stream` and registers it to the runtime.
+    /// Creates a new `Read` instance from the specified `stream`.
     fn new(stream: &'stream mut Stream, buffer: &'buffer mut [u8]) -> Self {
-        stream
-            .delegatee
-            .set_nonblocking(true)
-            .expect("should set non-blocking properly");
-        Reactor::register(&stream.delegatee, Interest::READABLE);
         Self { stream, buffer }
     }
 }
 
 pub type ReadOutput = io::Result<usize>;
 
+pub type ReadExactOutput = io::Result<()>;
+
 impl<'stream, 'buffer> future::Future for Read<'stream, 'buffer> {
     type Output = ReadOutput;
 
     fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
         let this = self.project();
-        let stream = &mut this.stream.delegatee;
         let buffer = this.buffer;
-        match stream.read(buffer) {
-            Ok(size) => task::Poll::Ready(Ok(size)),
+        match this.stream.delegatee.read(buffer) {
+            Ok(size) => {
+                this.stream.progress();
+                task::Poll::Ready(Ok(size))
+            }
             Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                Reactor::block(stream, cx.waker().clone());
+                this.stream.block(Interest::READABLE, cx.waker().clone());
                 task::Poll::Pending
             }
             Err(e) => task::Poll::Ready(Err(e)),
@@ -197,52 +1000,91 @@ impl<'stream, 'buffer> future::Future for Read<'stream, 'buffer> {
     }
 }
 
-#[pinned_drop]
-impl<'stream, 'buffer> PinnedDrop for Read<'stream, 'buffer> {
-    fn drop(self: pin::Pin<&mut Self>) {
-        Reactor::deregister(&self.stream.delegatee);
+/// Represents the scatter read event of a TCP connection, abstracting the IO demultiplexing of the Little Tokio
+/// runtime. It provides the following two functionalities:
+///  - Registration of the file descriptor to the runtime to monitor readiness for reading from the associated stream.
+///  - Implementation of the `Future` trait for the event loop of the runtime to await read-ready events.
+///
+/// The registration is the one of the associated stream, as is the case with `Read`.
+#[pin_project]
+pub struct ReadVectored<'stream, 'data> {
+    stream: &'stream mut Stream,
+    buffers: &'stream mut [io::IoSliceMut<'data>],
+}
+
+impl<'stream, 'data> ReadVectored<'stream, 'data> {
+    /// Creates a new `ReadVectored` instance from the specified `stream`.
+    fn new(stream: &'stream mut Stream, buffers: &'stream mut [io::IoSliceMut<'data>]) -> Self {
+        Self { stream, buffers }
+    }
+}
+
+impl<'stream, 'data> future::Future for ReadVectored<'stream, 'data> {
+    type Output = ReadOutput;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        let buffers = this.buffers;
+        match this.stream.delegatee.read_vectored(buffers) {
+            Ok(size) => {
+                this.stream.progress();
+                task::Poll::Ready(Ok(size))
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                this.stream.block(Interest::READABLE, cx.waker().clone());
+                task::Poll::Pending
+            }
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
     }
 }
 
 /// Represents the write event of a TCP connection, abstracting the IO demultiplexing of the Little Tokio runtime.
 /// It provides the following two functionalities:
 ///  - Registration of the file descriptor to the runtime to monitor readiness for writing to the associated stream.
-///  - Implementation of the `Future` trait for the event loop of the runtime to await read-ready events.
-#[pin_project(PinnedDrop)]
+///  - Implementation of the `Future` trait for the event loop of the runtime to await write-ready events.
+///
+/// The file descriptor is registered lazily, i.e., only when the first attempt to write returns `WouldBlock`, so
+/// that writes which succeed immediately never touch the runtime. The registration is the one of the associated
+/// stream, whose writable interest is dropped again once the write succeeds.
 struct Write<'stream, 'buffer> {
     stream: &'stream mut Stream,
     buffer: &'buffer [u8],
 }
 
 impl<'stream, 'buffer> Write<'stream, 'buffer> {
-    /// Creates a new `Write` instance from the specified `stream` and registers it to the runtime.
+    /// Creates a new `Write` instance from the specified `stream`.
     fn new(stream: &'stream mut Stream, buffer: &'buffer [u8]) -> Self {
-        stream
-            .delegatee
-            .set_nonblocking(true)
-            .expect("should set non-blocking properly");
-        Reactor::register(&stream.delegatee, Interest::WRITABLE);
         Self { stream, buffer }
     }
 }
 
 pub type WriteOutput = io::Result<usize>;
 
+pub type WriteAllOutput = io::Result<()>;
+
 impl<'stream, 'buffer> future::Future for Write<'stream, 'buffer> {
     type Output = WriteOutput;
 
     fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
-        //    fn poll(mut self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
-        let this = self.project();
-        let stream = &mut this.stream.delegatee;
-        let buffer = this.buffer;
-        //        let this = &mut *self;
-        match stream.write(buffer) {
-            //        match this.stream.delegatee.write(this.buffer) {
-            Ok(size) => task::Poll::Ready(Ok(size)),
+        // Note:
+        // `Write` holds no self-referential fields, i.e., it is `Unpin`, so that the fields can be borrowed
+        // separately without any pin projection.
+        let this = self.get_mut();
+        // Note:
+        // Writing an empty buffer is a no-op, so that it resolves immediately without touching the socket nor
+        // the runtime. Unlike reads, `Ok(0)` of a write does not indicate that the connection has been closed.
+        if this.buffer.is_empty() {
+            return task::Poll::Ready(Ok(0));
+        }
+        match this.stream.delegatee.write(this.buffer) {
+            Ok(size) => {
+                this.stream.unblock(Interest::WRITABLE);
+                this.stream.progress();
+                task::Poll::Ready(Ok(size))
+            }
             Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                Reactor::block(stream, cx.waker().clone());
-                //                Reactor::block(&this.stream.delegatee, cx.waker().clone());
+                this.stream.block(Interest::WRITABLE, cx.waker().clone());
                 task::Poll::Pending
             }
             Err(e) => task::Poll::Ready(Err(e)),
@@ -250,9 +1092,950 @@ impl<'stream, 'buffer> future::Future for Write<'stream, 'buffer> {
     }
 }
 
-#[pinned_drop]
-impl<'stream, 'buffer> PinnedDrop for Write<'stream, 'buffer> {
-    fn drop(self: pin::Pin<&mut Self>) {
-        Reactor::deregister(&self.stream.delegatee);
+/// The initial interval at which `Flush` re-checks the unsent bytes regardless of the write-ready events.
+const FLUSH_BACKOFF_MIN: time::Duration = time::Duration::from_millis(1);
+
+/// The maximal interval at which `Flush` re-checks the unsent bytes regardless of the write-ready events.
+const FLUSH_BACKOFF_MAX: time::Duration = time::Duration::from_millis(64);
+
+/// Represents the drain of the send buffer of a TCP connection, abstracting the IO demultiplexing of the Little
+/// Tokio runtime. It provides the following two functionalities:
+///  - Registration of the file descriptor to the runtime to monitor readiness for writing to the associated stream.
+///  - Implementation of the `Future` trait for the event loop of the runtime to await write-ready events.
+///
+/// # Note:
+/// An edge-triggered `epoll` reports no write-ready event when the send buffer drains while the socket has never
+/// stopped being writable, i.e., `Flush` also re-checks the unsent bytes on a timer, of which the interval doubles
+/// from `FLUSH_BACKOFF_MIN` up to `FLUSH_BACKOFF_MAX` while the send buffer does not shrink.
+struct Flush<'stream> {
+    stream: &'stream Stream,
+    registration: Option<Registration>,
+    unsent: usize,
+    backoff: Option<crate::time::Sleep>,
+    interval: time::Duration,
+}
+
+impl<'stream> Flush<'stream> {
+    /// Creates a new `Flush` instance from the specified `stream`.
+    fn new(stream: &'stream Stream) -> Self {
+        Self {
+            stream,
+            registration: None,
+            unsent: usize::MAX,
+            backoff: None,
+            interval: FLUSH_BACKOFF_MIN,
+        }
+    }
+}
+
+pub type FlushOutput = io::Result<()>;
+
+pub type ShutdownOutput = io::Result<()>;
+
+impl<'stream> future::Future for Flush<'stream> {
+    type Output = FlushOutput;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.get_mut();
+        match sys::unsent(this.stream.delegatee.as_raw_fd()) {
+            Ok(0) => task::Poll::Ready(Ok(())),
+            Ok(unsent) => {
+                let registration = this.registration.get_or_insert_with(|| {
+                    Reactor::register(&this.stream.delegatee, Interest::WRITABLE)
+                });
+                // Note:
+                // Waiting for a slow peer to acknowledge the data is not a busy loop as long as the send buffer
+                // keeps shrinking.
+                if unsent < this.unsent {
+                    registration.progress();
+                    this.interval = FLUSH_BACKOFF_MIN;
+                }
+                this.unsent = unsent;
+                registration.block(cx.waker().clone());
+                let backoff = this
+                    .backoff
+                    .get_or_insert_with(|| crate::time::sleep(this.interval));
+                if future::Future::poll(pin::Pin::new(&mut *backoff), cx).is_ready() {
+                    *backoff = crate::time::sleep(this.interval);
+                    this.interval = cmp::min(this.interval * 2, FLUSH_BACKOFF_MAX);
+                    let _ = future::Future::poll(pin::Pin::new(backoff), cx);
+                }
+                task::Poll::Pending
+            }
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+}
+
+/// Represents the readiness of a TCP connection, abstracting the IO demultiplexing of the Little Tokio runtime.
+/// It provides the following two functionalities:
+///  - Registration of the file descriptor to the runtime to monitor readiness specified by the `interest`.
+///  - Implementation of the `Future` trait for the event loop of the runtime to await the ready events.
+struct Ready<'stream> {
+    stream: &'stream Stream,
+    interest: Interest,
+    registration: Option<Registration>,
+}
+
+impl<'stream> Ready<'stream> {
+    /// Creates a new `Ready` instance from the specified `stream` and `interest`.
+    fn new(stream: &'stream Stream, interest: Interest) -> Self {
+        Self {
+            stream,
+            interest,
+            registration: None,
+        }
+    }
+}
+
+pub type ReadyOutput = io::Result<()>;
+
+impl<'stream> future::Future for Ready<'stream> {
+    type Output = ReadyOutput;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.get_mut();
+        // Note:
+        // The `Ready` resolves only once the reactor has signaled the `interest`, i.e., it keeps waiting when the
+        // task is woken up for anything else, e.g., another future polled by the same task.
+        match this.registration.as_mut() {
+            Some(registration) if registration.is_ready(this.interest) => task::Poll::Ready(Ok(())),
+            Some(registration) => {
+                registration.block(cx.waker().clone());
+                task::Poll::Pending
+            }
+            None => {
+                let mut registration = Reactor::register(&this.stream.delegatee, this.interest);
+                registration.block(cx.waker().clone());
+                this.registration = Some(registration);
+                task::Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{marker, sync};
+
+    /// Runs the given `test` on the Little Tokio runtime with a connection to a peer accepted by a plain
+    /// `std::net::TcpListener`, which the `test` drives synchronously.
+    fn with_peer<F, Fut>(test: F)
+    where
+        F: FnOnce(Stream, net::TcpStream) -> Fut + marker::Send + 'static,
+        Fut: future::Future<Output = ()> + marker::Send + 'static,
+    {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        crate::block_on(async move {
+            let stream = Stream::connect(addr).await.unwrap();
+            let (peer, _) = listener.accept().unwrap();
+            test(stream, peer).await;
+        });
+    }
+
+    #[test]
+    fn readable_ignores_spurious_wakes() {
+        let _serial = crate::tests::serial();
+        let polls = sync::Arc::new(sync::Mutex::new(0));
+        let counted = polls.clone();
+        with_peer(move |stream, mut peer| async move {
+            let mut readable = pin::pin!(stream.readable());
+            future::poll_fn(|cx| {
+                let mut polls = counted.lock().unwrap();
+                *polls += 1;
+                if let task::Poll::Ready(ret) = future::Future::poll(readable.as_mut(), cx) {
+                    return task::Poll::Ready(ret);
+                }
+                if *polls < 3 {
+                    cx.waker().wake_by_ref();
+                } else if *polls == 3 {
+                    peer.write_all(b"x").unwrap();
+                }
+                task::Poll::Pending
+            })
+            .await
+            .unwrap();
+            let mut buffer = [0u8; 1];
+            assert_eq!(stream.try_read(&mut buffer).unwrap(), 1);
+        });
+        assert!(*polls.lock().unwrap() > 3);
+    }
+
+    #[test]
+    fn flush_completes_once_the_peer_drains_the_send_buffer() {
+        let _serial = crate::tests::serial();
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let reader = std::thread::spawn(move || {
+            let (mut peer, _) = listener.accept().unwrap();
+            std::thread::sleep(time::Duration::from_millis(100));
+            let mut received = Vec::new();
+            peer.read_to_end(&mut received).unwrap();
+            received.len()
+        });
+        let payload = vec![0x2au8; 4 * 1024 * 1024];
+        let ret = crate::block_on_timeout(
+            async move {
+                let mut stream = Stream::connect(addr).await.unwrap();
+                stream.write_all(&payload).await.unwrap();
+                stream.flush().await.unwrap();
+            },
+            time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+        assert_eq!(reader.join().unwrap(), 4 * 1024 * 1024);
+    }
+
+    #[test]
+    fn into_stream_pauses_accepting_while_the_channel_is_full() {
+        let _serial = crate::tests::serial();
+        let listener = Listener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Note:
+        // The clone shares the backlog, and the non-blocking mode, of the listener, so that it tells how many
+        // connections the task has left unaccepted.
+        let backlog = listener.try_clone().unwrap();
+        let ret = crate::block_on_timeout(
+            async move {
+                let mut connections = listener.into_stream(2);
+                let clients: Vec<_> = (0..5)
+                    .map(|_| net::TcpStream::connect(addr).unwrap())
+                    .collect();
+                crate::time::sleep(time::Duration::from_millis(100)).await;
+                // Note:
+                // The task has accepted two connections into the channel and a third one waiting for a free slot.
+                let mut left = 0;
+                while backlog.accept().is_ok() {
+                    left += 1;
+                }
+                assert_eq!(left, 2);
+                let more: Vec<_> = (0..3)
+                    .map(|_| net::TcpStream::connect(addr).unwrap())
+                    .collect();
+                for _ in 0..6 {
+                    let (stream, _) = connections.recv().await.unwrap().unwrap();
+                    assert!(stream.peer_addr().is_ok());
+                }
+                drop((clients, more));
+            },
+            time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+    }
+
+    /// Writes into the given `stream` until its send buffer is full, and returns the number of bytes written.
+    fn fill_send_buffer(stream: &Stream) -> usize {
+        let chunk = [0x2au8; 64 * 1024];
+        let mut filled = 0;
+        loop {
+            match stream.try_write(&chunk) {
+                Ok(size) => filled += size,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return filled,
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn write_completes_on_the_write_ready_event_after_would_block() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, mut peer| async move {
+            let filled = fill_send_buffer(&stream);
+            let mut write = pin::pin!(stream.write(b"tail"));
+            let mut polls = 0;
+            let written = future::poll_fn(|cx| {
+                polls += 1;
+                let poll = future::Future::poll(write.as_mut(), cx);
+                if polls == 1 {
+                    assert!(poll.is_pending());
+                    let mut drained = vec![0u8; filled];
+                    peer.read_exact(&mut drained).unwrap();
+                }
+                poll
+            })
+            .await
+            .unwrap();
+            assert!(polls > 1);
+            assert!(written > 0 && written <= 4);
+            let mut tail = vec![0u8; written];
+            peer.read_exact(&mut tail).unwrap();
+            assert_eq!(tail, b"tail"[..written]);
+        });
+    }
+
+    #[test]
+    fn full_write_drops_the_writable_interest() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, peer| async move {
+            let filled = fill_send_buffer(&stream);
+            let mut drainer = None;
+            {
+                let mut write = pin::pin!(stream.write(b"tail"));
+                future::poll_fn(|cx| {
+                    let poll = future::Future::poll(write.as_mut(), cx);
+                    if drainer.is_none() {
+                        assert!(poll.is_pending());
+                        let mut peer = peer.try_clone().unwrap();
+                        drainer = Some(std::thread::spawn(move || {
+                            let mut drained = vec![0u8; filled];
+                            peer.read_exact(&mut drained).unwrap();
+                        }));
+                    }
+                    poll
+                })
+                .await
+                .unwrap();
+            }
+            drainer.unwrap().join().unwrap();
+            assert!(stream.registration.is_none());
+            assert!(!Reactor::has_registrations());
+            // Note:
+            // The drained socket stays writable, which must not wake up the task blocked for reading anymore.
+            let mut polls = 0;
+            let mut readable = pin::pin!(stream.readable());
+            let ret = crate::time::timeout(
+                time::Duration::from_millis(50),
+                future::poll_fn(|cx| {
+                    polls += 1;
+                    future::Future::poll(readable.as_mut(), cx)
+                }),
+            )
+            .await;
+            assert!(ret.is_err());
+            assert!(polls <= 2);
+        });
+    }
+
+    #[test]
+    fn accept_many_drains_the_backlog_in_one_batch() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut listener = Listener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            let clients: Vec<_> = (0..5)
+                .map(|_| net::TcpStream::connect(addr).unwrap())
+                .collect();
+            let batch = listener.accept_many(3).await.unwrap();
+            assert_eq!(batch.len(), 3);
+            let batch = listener.accept_many(10).await.unwrap();
+            assert_eq!(batch.len(), 2);
+            let mut peers: Vec<_> = batch.iter().map(|(_, addr)| *addr).collect();
+            peers.sort();
+            let mut expected: Vec<_> = clients[3..]
+                .iter()
+                .map(|c| c.local_addr().unwrap())
+                .collect();
+            expected.sort();
+            assert_eq!(peers, expected);
+        });
+    }
+
+    #[test]
+    fn empty_writes_touch_neither_the_socket_nor_the_reactor() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, peer| async move {
+            // Note:
+            // The send buffer is full, so that any write reaching the socket would block and register the stream.
+            fill_send_buffer(&stream);
+            assert_eq!(stream.write(&[]).await.unwrap(), 0);
+            stream.write_all(&[]).await.unwrap();
+            assert!(stream.registration.is_none());
+            assert!(!Reactor::has_registrations());
+            drop(peer);
+        });
+    }
+
+    #[test]
+    fn read_vectored_scatters_into_the_buffers_in_order() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, mut peer| async move {
+            let mut head = [0u8; 5];
+            let mut tail = [0u8; 16];
+            let size = {
+                let mut buffers = [
+                    io::IoSliceMut::new(&mut head),
+                    io::IoSliceMut::new(&mut tail),
+                ];
+                let mut read = pin::pin!(stream.read_vectored(&mut buffers));
+                let mut polled = false;
+                future::poll_fn(|cx| {
+                    let poll = future::Future::poll(read.as_mut(), cx);
+                    if !polled {
+                        // Note:
+                        // Nothing has been sent yet, so that the read parks on the read-readiness first.
+                        assert!(poll.is_pending());
+                        peer.write_all(b"hello world").unwrap();
+                        polled = true;
+                    }
+                    poll
+                })
+                .await
+                .unwrap()
+            };
+            assert_eq!(size, 11);
+            assert_eq!(&head, b"hello");
+            assert_eq!(&tail[..6], b" world");
+        });
+    }
+
+    #[test]
+    fn listener_bound_to_port_zero_reports_the_assigned_port() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut listener = Listener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            assert_ne!(addr.port(), 0);
+            let mut client = Stream::connect(addr).await.unwrap();
+            let (mut accepted, peer) = listener.accept().await.unwrap();
+            assert_eq!(peer, client.local_addr().unwrap());
+            assert_eq!(accepted.local_addr().unwrap(), addr);
+            client.write_all(b"ping").await.unwrap();
+            let mut buffer = [0u8; 4];
+            accepted.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(&buffer, b"ping");
+        });
+    }
+
+    #[test]
+    fn sockopt_round_trips_an_integer_option() {
+        let _serial = crate::tests::serial();
+        with_peer(|stream, peer| async move {
+            stream
+                .set_sockopt_int(libc::IPPROTO_TCP, libc::TCP_NODELAY, 1)
+                .unwrap();
+            assert_ne!(
+                stream
+                    .get_sockopt_int(libc::IPPROTO_TCP, libc::TCP_NODELAY)
+                    .unwrap(),
+                0
+            );
+            assert!(stream.nodelay().unwrap());
+            stream
+                .set_sockopt_int(libc::IPPROTO_TCP, libc::TCP_NODELAY, 0)
+                .unwrap();
+            assert!(!stream.nodelay().unwrap());
+            // Safety:
+            // `SO_TYPE` is a `c_int` valued option.
+            let kind: ffi::c_int =
+                unsafe { stream.get_sockopt(libc::SOL_SOCKET, libc::SO_TYPE) }.unwrap();
+            assert_eq!(kind, libc::SOCK_STREAM);
+            drop(peer);
+        });
+    }
+
+    #[test]
+    fn into_raw_fd_deregisters_and_keeps_the_descriptor_open() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, mut peer| async move {
+            let mut buffer = [0u8; 4];
+            let pending =
+                future::poll_fn(
+                    |cx| match pin::Pin::new(&mut stream).poll_read(cx, &mut buffer) {
+                        task::Poll::Pending => task::Poll::Ready(true),
+                        task::Poll::Ready(_) => task::Poll::Ready(false),
+                    },
+                )
+                .await;
+            assert!(pending);
+            assert!(stream.registration.is_some());
+            assert!(Reactor::has_registrations());
+            let fd = stream.into_raw_fd();
+            assert!(!Reactor::has_registrations());
+            // Safety:
+            // `into_raw_fd` has handed over the ownership of `fd`, which is closed exactly once by `restored`.
+            let mut restored =
+                unsafe { <net::TcpStream as std::os::fd::FromRawFd>::from_raw_fd(fd) };
+            peer.write_all(b"ping").unwrap();
+            restored.set_nonblocking(false).unwrap();
+            restored.read_exact(&mut buffer).unwrap();
+            assert_eq!(&buffer, b"ping");
+        });
+    }
+
+    #[test]
+    fn listener_into_raw_fd_hands_over_an_unregistered_listener() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut listener = Listener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            {
+                let mut accept = pin::pin!(listener.accept());
+                let pending = future::poll_fn(|cx| {
+                    task::Poll::Ready(future::Future::poll(accept.as_mut(), cx).is_pending())
+                })
+                .await;
+                assert!(pending);
+                assert!(Reactor::has_registrations());
+            }
+            let fd = listener.into_raw_fd();
+            assert!(!Reactor::has_registrations());
+            // Safety:
+            // `into_raw_fd` has handed over the ownership of `fd`, which is closed exactly once by `restored`.
+            let restored = unsafe { <net::TcpListener as std::os::fd::FromRawFd>::from_raw_fd(fd) };
+            restored.set_nonblocking(false).unwrap();
+            let client = net::TcpStream::connect(addr).unwrap();
+            let (_, peer) = restored.accept().unwrap();
+            assert_eq!(peer, client.local_addr().unwrap());
+        });
+    }
+
+    #[test]
+    fn linger_round_trips() {
+        let _serial = crate::tests::serial();
+        with_peer(|stream, peer| async move {
+            assert_eq!(stream.linger().unwrap(), None);
+            stream
+                .set_linger(Some(time::Duration::from_secs(5)))
+                .unwrap();
+            assert_eq!(stream.linger().unwrap(), Some(time::Duration::from_secs(5)));
+            stream.set_linger(Some(time::Duration::ZERO)).unwrap();
+            assert_eq!(stream.linger().unwrap(), Some(time::Duration::ZERO));
+            stream.set_linger(None).unwrap();
+            assert_eq!(stream.linger().unwrap(), None);
+            drop(peer);
+        });
+    }
+
+    #[test]
+    fn shutdown_of_the_write_half_makes_the_peer_read_eof() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, mut peer| async move {
+            stream.write_all(b"request").await.unwrap();
+            stream.shutdown(net::Shutdown::Write).await.unwrap();
+            let mut request = Vec::new();
+            peer.read_to_end(&mut request).unwrap();
+            assert_eq!(request, b"request");
+            peer.write_all(b"response").unwrap();
+            drop(peer);
+            let mut response = [0u8; 8];
+            stream.read_exact(&mut response).await.unwrap();
+            assert_eq!(&response, b"response");
+        });
+    }
+
+    #[test]
+    fn shutdown_of_the_write_half_drops_the_writable_interest() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, peer| async move {
+            fill_send_buffer(&stream);
+            let pending = future::poll_fn(|cx| {
+                task::Poll::Ready(pin::Pin::new(&mut stream).poll_write(cx, b"x").is_pending())
+            })
+            .await;
+            assert!(pending);
+            assert!(stream.registration.is_some());
+            stream.shutdown(net::Shutdown::Write).await.unwrap();
+            assert!(stream.registration.is_none());
+            assert!(!Reactor::has_registrations());
+            drop(peer);
+        });
+    }
+
+    #[test]
+    fn blocked_read_resolves_to_eof_once_the_peer_closes() {
+        let _serial = crate::tests::serial();
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let ret = crate::block_on_timeout(
+            async move {
+                let mut stream = Stream::connect(addr).await.unwrap();
+                let (peer, _) = listener.accept().unwrap();
+                let mut buffer = [0u8; 16];
+                let mut read = pin::pin!(stream.read(&mut buffer));
+                let pending = future::poll_fn(|cx| {
+                    task::Poll::Ready(future::Future::poll(read.as_mut(), cx).is_pending())
+                })
+                .await;
+                assert!(pending);
+                std::thread::spawn(move || {
+                    std::thread::sleep(time::Duration::from_millis(50));
+                    drop(peer);
+                });
+                read.await.unwrap()
+            },
+            time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(0));
+    }
+
+    #[test]
+    fn nodelay_and_ttl_survive_reads_and_keep_the_stream_non_blocking() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, mut peer| async move {
+            stream.set_nodelay(true).unwrap();
+            stream.set_ttl(42).unwrap();
+            peer.write_all(b"ping").unwrap();
+            let mut buffer = [0u8; 4];
+            stream.read_exact(&mut buffer).await.unwrap();
+            assert!(stream.nodelay().unwrap());
+            assert_eq!(stream.ttl().unwrap(), 42);
+            // Note:
+            // Reading from a non-blocking socket with nothing to read results in `WouldBlock` rather than blocking.
+            let e = stream.try_read(&mut buffer).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+            drop(peer);
+        });
+    }
+
+    #[test]
+    fn connect_timeout_gives_up_on_an_unresponsive_peer() {
+        let _serial = crate::tests::serial();
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Note:
+        // Shrinking the backlog and filling it up makes the kernel drop the subsequent handshakes, i.e., the peer
+        // is routable but never responds.
+        // Safety:
+        // `listen(2)` is called on the file descriptor owned by `listener`.
+        assert_eq!(unsafe { libc::listen(listener.as_raw_fd(), 0) }, 0);
+        let mut backlog = Vec::new();
+        while let Ok(client) =
+            net::TcpStream::connect_timeout(&addr, time::Duration::from_millis(100))
+        {
+            backlog.push(client);
+            assert!(backlog.len() < 16, "backlog should be filled up");
+        }
+        let ret = crate::block_on_timeout(
+            async move {
+                let started = time::Instant::now();
+                let e = Stream::connect_timeout(addr, time::Duration::from_millis(200))
+                    .await
+                    .err()
+                    .unwrap();
+                assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+                assert!(started.elapsed() >= time::Duration::from_millis(200));
+                assert!(!Reactor::has_registrations());
+            },
+            time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+        drop(listener);
+    }
+
+    #[test]
+    fn incoming_accepts_the_connections_one_after_another() {
+        let _serial = crate::tests::serial();
+        let ret = crate::block_on_timeout(
+            async {
+                use crate::stream::StreamExt as _;
+                let mut listener = Listener::bind("127.0.0.1:0").unwrap();
+                let addr = listener.local_addr().unwrap();
+                std::thread::spawn(move || {
+                    for _ in 0..3 {
+                        std::thread::sleep(time::Duration::from_millis(20));
+                        let mut client = net::TcpStream::connect(addr).unwrap();
+                        client.write_all(b"hi").unwrap();
+                    }
+                });
+                let mut incoming = listener.incoming();
+                for _ in 0..3 {
+                    let mut stream = incoming.next().await.unwrap().unwrap();
+                    let mut buffer = [0u8; 2];
+                    stream.read_exact(&mut buffer).await.unwrap();
+                    assert_eq!(&buffer, b"hi");
+                    drop(stream);
+                    // Note:
+                    // The listener stays registered between the connections.
+                    assert!(incoming.registration.is_some());
+                }
+            },
+            time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+    }
+
+    /// Accepts the given number of `connections` pending in the backlog, spawning a handler yielding once per
+    /// connection, and returns the maximal number of the pending tasks observed on accepting.
+    fn max_pending_on_accept(builder: runtime::Builder, connections: usize) -> usize {
+        let observed = sync::Arc::new(sync::Mutex::new(0));
+        let max = observed.clone();
+        builder
+            .try_block_on(async move {
+                let mut listener = Listener::bind("127.0.0.1:0").unwrap();
+                let addr = listener.local_addr().unwrap();
+                let _clients: Vec<_> = (0..connections)
+                    .map(|_| net::TcpStream::connect(addr).unwrap())
+                    .collect();
+                for _ in 0..connections {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let mut max = max.lock().unwrap();
+                    *max = cmp::max(*max, runtime::pending_tasks());
+                    crate::spawn(async move {
+                        crate::tests::yield_now().await;
+                        drop(stream);
+                    });
+                }
+            })
+            .unwrap();
+        let max = *observed.lock().unwrap();
+        max
+    }
+
+    #[test]
+    fn without_accept_budget_the_backlog_is_drained_in_one_go() {
+        let _serial = crate::tests::serial();
+        assert_eq!(max_pending_on_accept(runtime::Builder::new(), 6), 5);
+    }
+
+    #[test]
+    fn accept_budget_bounds_the_handlers_in_flight() {
+        let _serial = crate::tests::serial();
+        assert!(max_pending_on_accept(runtime::Builder::new().accept_budget(2), 6) <= 2);
+    }
+
+    #[test]
+    fn connect_to_a_closed_port_surfaces_the_refusal() {
+        let _serial = crate::tests::serial();
+        let addr = {
+            let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+        crate::block_on(async move {
+            match Stream::connect(addr).await {
+                Err(e) => assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused),
+                Ok(stream) => panic!("connected to a closed port: {:?}", stream.peer_addr()),
+            }
+            assert!(!Reactor::has_registrations());
+        });
+    }
+
+    #[test]
+    fn read_to_end_appends_the_chunks_written_over_time_until_the_eof() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, mut peer| async move {
+            let payload: Vec<u8> = (0..3 * READ_TO_END_CHUNK + 7).map(|i| i as u8).collect();
+            let sent = payload.clone();
+            let writer = std::thread::spawn(move || {
+                for chunk in sent.chunks(READ_TO_END_CHUNK / 2 + 1) {
+                    peer.write_all(chunk).unwrap();
+                    std::thread::sleep(time::Duration::from_millis(2));
+                }
+            });
+            let mut buffer = b"head".to_vec();
+            let appended = stream.read_to_end(&mut buffer).await.unwrap();
+            writer.join().unwrap();
+            assert_eq!(appended, payload.len());
+            assert_eq!(&buffer[..4], b"head");
+            assert_eq!(&buffer[4..], &payload[..]);
+        });
+    }
+
+    #[test]
+    fn write_all_writes_the_whole_buffer_under_backpressure() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, mut peer| async move {
+            // Note:
+            // The send buffer is shrunk and the peer starts reading late, so that the writes must hit `WouldBlock`.
+            stream
+                .set_sockopt_int(libc::SOL_SOCKET, libc::SO_SNDBUF, 16 * 1024)
+                .unwrap();
+            let payload: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+            let reader = std::thread::spawn(move || {
+                std::thread::sleep(time::Duration::from_millis(20));
+                let mut received = Vec::new();
+                peer.read_to_end(&mut received).unwrap();
+                received
+            });
+            stream.write_all(&payload).await.unwrap();
+            stream.shutdown(net::Shutdown::Write).await.unwrap();
+            assert!(reader.join().unwrap() == payload);
+        });
+    }
+
+    #[test]
+    fn read_exact_fills_the_buffer_across_partial_reads() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, mut peer| async move {
+            let writer = std::thread::spawn(move || {
+                for part in [&b"he"[..], b"llo", b" wor", b"ld"] {
+                    peer.write_all(part).unwrap();
+                    std::thread::sleep(time::Duration::from_millis(5));
+                }
+                peer
+            });
+            let mut buffer = [0u8; 11];
+            stream.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(&buffer, b"hello world");
+            drop(writer.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn read_exact_fails_with_the_unexpected_eof_on_a_short_read() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, mut peer| async move {
+            peer.write_all(b"short").unwrap();
+            drop(peer);
+            let mut buffer = [0u8; 8];
+            let e = stream.read_exact(&mut buffer).await.unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+            assert_eq!(&buffer[..5], b"short");
+        });
+    }
+
+    #[test]
+    fn accepted_stream_reports_the_addresses_of_the_connection() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut listener = Listener::bind("127.0.0.1:0").unwrap();
+            let client = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            let (stream, addr) = listener.accept().await.unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), addr);
+            assert_eq!(stream.peer_addr().unwrap(), client.local_addr().unwrap());
+            assert_eq!(stream.local_addr().unwrap(), client.peer_addr().unwrap());
+        });
+    }
+
+    #[test]
+    fn connections_reopened_on_reused_descriptors_are_woken_for_their_own_events() {
+        let _serial = crate::tests::serial();
+        let ret = crate::block_on_timeout(
+            async {
+                let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+                let addr = listener.local_addr().unwrap();
+                let mut fds = std::collections::HashSet::new();
+                for round in 0..64u8 {
+                    let mut stream = Stream::connect(addr).await.unwrap();
+                    let (mut peer, _) = listener.accept().unwrap();
+                    fds.insert(stream.as_raw_fd());
+                    let mut buffer = [0u8; 1];
+                    let size = {
+                        let mut reading = pin::pin!(stream.read(&mut buffer));
+                        let pending = future::poll_fn(|cx| {
+                            task::Poll::Ready(
+                                future::Future::poll(reading.as_mut(), cx).is_pending(),
+                            )
+                        })
+                        .await;
+                        assert!(pending);
+                        peer.write_all(&[round]).unwrap();
+                        reading.await.unwrap()
+                    };
+                    assert_eq!(size, 1);
+                    assert_eq!(buffer, [round]);
+                }
+                assert!(!Reactor::has_registrations());
+                fds.len()
+            },
+            time::Duration::from_secs(5),
+        );
+        // Note:
+        // The descriptors closed are handed out again, i.e., the rounds share a few descriptor numbers.
+        assert!(ret.unwrap() < 64);
+    }
+
+    /// Returns `true` if the given `fd` is in non-blocking mode.
+    fn is_nonblocking(fd: &impl AsRawFd) -> bool {
+        // Safety:
+        // `F_GETFL` only reads the file status flags of the open file descriptor.
+        let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) };
+        assert!(flags >= 0, "{}", io::Error::last_os_error());
+        flags & libc::O_NONBLOCK != 0
+    }
+
+    #[test]
+    fn listener_stays_non_blocking_and_dropped_accepts_deregister_it() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut listener = Listener::bind("127.0.0.1:0").unwrap();
+            assert!(is_nonblocking(&*listener));
+            {
+                let mut accept = pin::pin!(listener.accept());
+                let pending = future::poll_fn(|cx| {
+                    task::Poll::Ready(future::Future::poll(accept.as_mut(), cx).is_pending())
+                })
+                .await;
+                assert!(pending);
+                assert!(Reactor::has_registrations());
+            }
+            assert!(!Reactor::has_registrations());
+            let _client = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+            assert!(is_nonblocking(&*listener));
+            assert!(is_nonblocking(&*stream));
+        });
+    }
+
+    #[test]
+    fn read_and_write_futures_share_the_registration_of_the_stream() {
+        let _serial = crate::tests::serial();
+        with_peer(|mut stream, peer| async move {
+            let fd = stream.as_raw_fd();
+            let generation = Reactor::generation(fd);
+            let mut writer = peer.try_clone().unwrap();
+            for round in 0..8u8 {
+                let mut buffer = [0u8; 1];
+                let size = {
+                    let mut reading = pin::pin!(stream.read(&mut buffer));
+                    let pending = future::poll_fn(|cx| {
+                        task::Poll::Ready(future::Future::poll(reading.as_mut(), cx).is_pending())
+                    })
+                    .await;
+                    assert!(pending);
+                    writer.write_all(&[round]).unwrap();
+                    reading.await.unwrap()
+                };
+                assert_eq!((size, buffer), (1, [round]));
+                stream.write(&buffer).await.unwrap();
+                // Note:
+                // The registration of the stream is neither dropped nor re-created by the futures, i.e., the
+                // descriptor is never entirely deregistered in between.
+                assert_eq!(
+                    stream.registration.as_ref().map(Registration::interest),
+                    Some(Interest::READABLE)
+                );
+                assert_eq!(Reactor::generation(fd), generation);
+            }
+            drop(stream);
+            assert_ne!(Reactor::generation(fd), generation);
+            drop(peer);
+        });
+    }
+
+    #[test]
+    fn operations_ready_right_away_never_touch_the_reactor() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut listener = Listener::bind("127.0.0.1:0").unwrap();
+            let mut client = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            client.write_all(b"ping").unwrap();
+            // Note:
+            // The loopback connection is pending in the backlog and its data has arrived by now, i.e., neither the
+            // accept nor the read would block.
+            let (mut stream, _) = listener.accept().await.unwrap();
+            assert!(!Reactor::has_registrations());
+            let mut buffer = [0u8; 4];
+            stream.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(&buffer, b"ping");
+            stream.write_all(b"pong").await.unwrap();
+            assert!(stream.registration.is_none());
+            assert!(!Reactor::has_registrations());
+        });
+    }
+
+    #[test]
+    fn connect_falls_back_to_the_next_address_once_refused() {
+        let _serial = crate::tests::serial();
+        let refused = {
+            let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let ret = crate::block_on_timeout(
+            async move {
+                let started = time::Instant::now();
+                let stream = Stream::connect([refused, addr].as_slice()).await.unwrap();
+                assert_eq!(stream.peer_addr().unwrap(), addr);
+                // Note:
+                // The refusal is surfaced by `SO_ERROR` on the first wake up, rather than by a timeout.
+                started.elapsed()
+            },
+            time::Duration::from_secs(5),
+        );
+        assert!(ret.unwrap() < time::Duration::from_secs(1));
+        drop(listener);
     }
 }
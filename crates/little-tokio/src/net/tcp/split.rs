@@ -0,0 +1,287 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of the owned halves of a TCP connection, which allow the connection to
+//! be read and written from two different tasks concurrently.
+
+use crate::core::interest::Interest;
+use crate::core::reactor::Reactor;
+use crate::core::registration::Registration;
+use crate::io::{AsyncRead, AsyncWrite};
+use std::io::Read as _;
+use std::io::Write as _;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::{future, io, net, pin, sync, task};
+
+/// Splits the given `stream` into the owned halves sharing the connection.
+pub(super) fn split(stream: net::TcpStream) -> (OwnedReadHalf, OwnedWriteHalf) {
+    let stream = sync::Arc::new(stream);
+    let read = OwnedReadHalf {
+        registration: None,
+        stream: sync::Arc::clone(&stream),
+    };
+    let write = OwnedWriteHalf {
+        registration: None,
+        stream,
+    };
+    (read, write)
+}
+
+/// Represents the read half of a TCP connection created by `Stream::into_split`. Each half registers its own
+/// `Interest` to the Little Tokio runtime, and the connection is entirely deregistered and closed once both of the
+/// halves have been dropped.
+pub struct OwnedReadHalf {
+    // Note:
+    // The `registration` is declared before the `stream`, so that it gets dropped before the connection may be
+    // closed.
+    registration: Option<Registration>,
+    stream: sync::Arc<net::TcpStream>,
+}
+
+impl OwnedReadHalf {
+    /// Reads from the incoming connection into the given `buffer`, awaiting the connection to become readable.
+    pub fn read<'half>(
+        &'half mut self,
+        buffer: &'half mut [u8],
+    ) -> impl future::Future<Output = io::Result<usize>> + 'half {
+        future::poll_fn(move |cx| pin::Pin::new(&mut *self).poll_read(cx, buffer))
+    }
+
+    /// Tries to read from the incoming connection without awaiting, i.e., returns `io::ErrorKind::WouldBlock`
+    /// if there is no data available yet.
+    pub fn try_read(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        (&*self.stream).read(buffer)
+    }
+
+    /// Returns the socket address of the remote peer of the connection.
+    pub fn peer_addr(&self) -> io::Result<net::SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// Returns the socket address of the local half of the connection.
+    pub fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.stream.local_addr()
+    }
+}
+
+impl AsyncRead for OwnedReadHalf {
+    fn poll_read(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &mut [u8],
+    ) -> task::Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match this.try_read(buffer) {
+            Ok(size) => {
+                if let Some(registration) = this.registration.as_mut() {
+                    registration.progress();
+                }
+                task::Poll::Ready(Ok(size))
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                this.registration
+                    .get_or_insert_with(|| Reactor::register(&*this.stream, Interest::READABLE))
+                    .block(cx.waker().clone());
+                task::Poll::Pending
+            }
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+}
+
+impl AsRawFd for OwnedReadHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl AsFd for OwnedReadHalf {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+/// Represents the write half of a TCP connection created by `Stream::into_split`. Each half registers its own
+/// `Interest` to the Little Tokio runtime, and the connection is entirely deregistered and closed once both of the
+/// halves have been dropped.
+pub struct OwnedWriteHalf {
+    // Note:
+    // The `registration` is declared before the `stream`, so that it gets dropped before the connection may be
+    // closed.
+    registration: Option<Registration>,
+    stream: sync::Arc<net::TcpStream>,
+}
+
+impl OwnedWriteHalf {
+    /// Writes the given `buffer` to the outgoing connection, awaiting the connection to become writable.
+    pub fn write<'half>(
+        &'half mut self,
+        buffer: &'half [u8],
+    ) -> impl future::Future<Output = io::Result<usize>> + 'half {
+        future::poll_fn(move |cx| pin::Pin::new(&mut *self).poll_write(cx, buffer))
+    }
+
+    /// Tries to write to the outgoing connection without awaiting, i.e., returns `io::ErrorKind::WouldBlock`
+    /// if the send buffer is full.
+    pub fn try_write(&self, buffer: &[u8]) -> io::Result<usize> {
+        (&*self.stream).write(buffer)
+    }
+
+    /// Shuts down the writing half of the connection, i.e., the peer reads the EOF once it has read all the data
+    /// written so far.
+    pub fn shutdown(&self) -> io::Result<()> {
+        self.stream.shutdown(net::Shutdown::Write)
+    }
+
+    /// Returns the socket address of the remote peer of the connection.
+    pub fn peer_addr(&self) -> io::Result<net::SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// Returns the socket address of the local half of the connection.
+    pub fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.stream.local_addr()
+    }
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    fn poll_write(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buffer: &[u8],
+    ) -> task::Poll<io::Result<usize>> {
+        if buffer.is_empty() {
+            return task::Poll::Ready(Ok(0));
+        }
+        let this = self.get_mut();
+        match this.try_write(buffer) {
+            Ok(size) => {
+                // Note:
+                // Since sockets are writable most of the time, the writable interest is dropped once the write has
+                // succeeded, so that the runtime does not wake up the task for spurious write-ready events.
+                this.registration = None;
+                task::Poll::Ready(Ok(size))
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                this.registration
+                    .get_or_insert_with(|| Reactor::register(&*this.stream, Interest::WRITABLE))
+                    .block(cx.waker().clone());
+                task::Poll::Pending
+            }
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+
+    fn poll_shutdown(
+        self: pin::Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.shutdown()?;
+        this.registration = None;
+        task::Poll::Ready(Ok(()))
+    }
+}
+
+impl AsRawFd for OwnedWriteHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl AsFd for OwnedWriteHalf {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::tcp::Stream;
+    use std::{thread, time};
+
+    /// Connects a `Stream` to a blocking peer, and splits the `Stream` into the owned halves.
+    async fn connect() -> (OwnedReadHalf, OwnedWriteHalf, net::TcpStream) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = Stream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (peer, _) = listener.accept().unwrap();
+        let (read, write) = stream.into_split();
+        (read, write, peer)
+    }
+
+    #[test]
+    fn halves_block_on_their_own_interests_concurrently() {
+        let _serial = crate::tests::serial();
+        let ret = crate::block_on_timeout(
+            async {
+                let (mut read, mut write, mut peer) = connect().await;
+                let mut filled = 0;
+                while let Ok(size) = write.try_write(&[0u8; 65536]) {
+                    filled += size;
+                }
+                let reader = crate::spawn_with_output(async move {
+                    let mut buffer = [0u8; 4];
+                    let size = read.read(&mut buffer).await.unwrap();
+                    buffer[..size].to_vec()
+                });
+                let writer =
+                    crate::spawn_with_output(async move { write.write(b"x").await.unwrap() });
+                thread::spawn(move || {
+                    thread::sleep(time::Duration::from_millis(50));
+                    let mut drained = vec![0u8; filled + 1];
+                    peer.read_exact(&mut drained).unwrap();
+                    peer.write_all(b"pong").unwrap();
+                    // Note:
+                    // The connection is kept open until the reader has read the response.
+                    thread::sleep(time::Duration::from_millis(100));
+                });
+                (reader.await.unwrap(), writer.await.unwrap())
+            },
+            time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some((b"pong".to_vec(), 1)));
+    }
+
+    #[test]
+    fn connection_is_closed_once_both_halves_are_dropped() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let (read, mut write, mut peer) = connect().await;
+            drop(read);
+            write.write(b"ping").await.unwrap();
+            drop(write);
+            assert!(!Reactor::has_registrations());
+            let mut received = Vec::new();
+            peer.read_to_end(&mut received).unwrap();
+            assert_eq!(received, b"ping");
+        });
+    }
+
+    #[test]
+    fn halves_report_the_addresses_of_the_connection() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let (read, write, peer) = connect().await;
+            let local = peer.peer_addr().unwrap();
+            let remote = peer.local_addr().unwrap();
+            assert_eq!(read.local_addr().unwrap(), local);
+            assert_eq!(read.peer_addr().unwrap(), remote);
+            assert_eq!(write.local_addr().unwrap(), local);
+            assert_eq!(write.peer_addr().unwrap(), remote);
+        });
+    }
+}
@@ -0,0 +1,440 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of UDP networking for the Little Tokio runtime.
+
+use crate::core::interest::Interest;
+use crate::core::reactor::Reactor;
+use crate::core::registration::Registration;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, RawFd};
+use std::{future, io, net, ops, pin, task};
+
+/// Represents the Little Tokio wrapper arround a `UdpSocket`. This wrapper is essentially equivalent to
+/// `UdpSocket`. It implements `Deref` and `DerefMut` to delegate the underlying `UdpSocket` methods.
+/// Additionally, this struct is responsible for `register` and/or `deregister` (IO demultiplexing) the
+/// network IO events to the Little Tokio runtime, which is the core part of this crate.
+pub struct Socket {
+    delegatee: net::UdpSocket,
+}
+
+impl Socket {
+    /// Binds inner `UdpSocket` to the given `addr` and sets it non-blocking mode. Binding to the port `0`
+    /// requests the OS to assign an ephemeral port, which can be retrieved via `local_addr`.
+    pub fn bind(addr: impl net::ToSocketAddrs) -> io::Result<Self> {
+        let delegatee = net::UdpSocket::bind(addr)?;
+        delegatee.set_nonblocking(true)?;
+        Ok(Self { delegatee })
+    }
+
+    /// Returns the local socket address this socket is bound to, including the port assigned by the OS
+    /// when the socket has been bound to the port `0`.
+    pub fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.delegatee.local_addr()
+    }
+
+    /// Receives a single datagram into the given `buffer` and returns an `RecvFrom` struct, which offers an
+    /// abstraction over IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio runtime.
+    /// The future resolves to the number of bytes received and the address of the sender.
+    ///
+    /// # Note:
+    /// The excess bytes of a datagram larger than the `buffer` are discarded.
+    pub fn recv_from<'socket, 'buffer>(
+        &'socket mut self,
+        buffer: &'buffer mut [u8],
+    ) -> impl future::Future<Output = RecvFromOutput> + 'socket
+    where
+        'buffer: 'socket,
+    {
+        RecvFrom::new(self, buffer)
+    }
+
+    /// Sends the given `buffer` as a single datagram to the given `addr` and returns an `SendTo` struct, which
+    /// offers an abstraction over IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio
+    /// runtime. The future resolves to the number of bytes sent.
+    pub fn send_to<'socket, 'buffer>(
+        &'socket mut self,
+        buffer: &'buffer [u8],
+        addr: net::SocketAddr,
+    ) -> impl future::Future<Output = SendToOutput> + 'socket
+    where
+        'buffer: 'socket,
+    {
+        SendTo::new(self, buffer, addr)
+    }
+
+    /// Connects this socket to the given `addr`, i.e., `send` sends datagrams to the `addr`, and `recv` receives
+    /// datagrams only from the `addr` since the kernel filters out the datagrams from the other addresses. If the
+    /// `addr` resolves to several addresses, the first one the socket succeeds to connect to is used.
+    ///
+    /// # Note:
+    /// Since connecting a UDP socket only sets up the default peer in the kernel, this method never blocks.
+    pub fn connect(&self, addr: impl net::ToSocketAddrs) -> io::Result<()> {
+        self.delegatee.connect(addr)
+    }
+
+    /// Returns the socket address of the peer this socket has been connected to.
+    pub fn peer_addr(&self) -> io::Result<net::SocketAddr> {
+        self.delegatee.peer_addr()
+    }
+
+    /// Receives a single datagram from the connected peer into the given `buffer` and returns an `Recv` struct,
+    /// which offers an abstraction over IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio
+    /// runtime. The future resolves to the number of bytes received, or fails if the socket has not been
+    /// connected.
+    ///
+    /// # Note:
+    /// The excess bytes of a datagram larger than the `buffer` are discarded.
+    pub fn recv<'socket, 'buffer>(
+        &'socket mut self,
+        buffer: &'buffer mut [u8],
+    ) -> impl future::Future<Output = RecvOutput> + 'socket
+    where
+        'buffer: 'socket,
+    {
+        Recv::new(self, buffer)
+    }
+
+    /// Sends the given `buffer` as a single datagram to the connected peer and returns an `Send` struct, which
+    /// offers an abstraction over IO demultiplexing using the Rust's `Future` runtime, i.e., the Little Tokio
+    /// runtime. The future resolves to the number of bytes sent, or fails if the socket has not been connected.
+    pub fn send<'socket, 'buffer>(
+        &'socket mut self,
+        buffer: &'buffer [u8],
+    ) -> impl future::Future<Output = SendOutput> + 'socket
+    where
+        'buffer: 'socket,
+    {
+        Send::new(self, buffer)
+    }
+}
+
+impl ops::Deref for Socket {
+    type Target = net::UdpSocket;
+
+    fn deref(&self) -> &Self::Target {
+        &self.delegatee
+    }
+}
+
+impl ops::DerefMut for Socket {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.delegatee
+    }
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.delegatee.as_raw_fd()
+    }
+}
+
+impl AsFd for Socket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.delegatee.as_fd()
+    }
+}
+
+impl IntoRawFd for Socket {
+    fn into_raw_fd(self) -> RawFd {
+        self.delegatee.into_raw_fd()
+    }
+}
+
+/// Represents the reception of a UDP datagram, abstracting the IO demultiplexing of the Little Tokio runtime.
+/// It provides the following two functionalities:
+///  - Registration of the file descriptor to the runtime to monitor readiness for reading from the socket.
+///  - Implementation of the `Future` trait for the event loop of the runtime to await read-ready events.
+///
+/// The file descriptor is registered only when the socket would block, and deregistered when this future gets
+/// dropped via its `Registration`.
+struct RecvFrom<'socket, 'buffer> {
+    socket: &'socket mut Socket,
+    buffer: &'buffer mut [u8],
+    registration: Option<Registration>,
+}
+
+impl<'socket, 'buffer> RecvFrom<'socket, 'buffer> {
+    /// Creates a new `RecvFrom` instance from the specified `socket`.
+    fn new(socket: &'socket mut Socket, buffer: &'buffer mut [u8]) -> Self {
+        Self {
+            socket,
+            buffer,
+            registration: None,
+        }
+    }
+}
+
+pub type RecvFromOutput = io::Result<(usize, net::SocketAddr)>;
+
+impl<'socket, 'buffer> future::Future for RecvFrom<'socket, 'buffer> {
+    type Output = RecvFromOutput;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        // Note:
+        // `RecvFrom` holds no self-referential fields, i.e., it is `Unpin`, so that the fields can be borrowed
+        // separately without any pin projection.
+        let this = self.get_mut();
+        match this.socket.delegatee.recv_from(this.buffer) {
+            Ok(received) => {
+                if let Some(registration) = this.registration.as_mut() {
+                    registration.progress();
+                }
+                task::Poll::Ready(Ok(received))
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                this.registration
+                    .get_or_insert_with(|| {
+                        Reactor::register(&this.socket.delegatee, Interest::READABLE)
+                    })
+                    .block(cx.waker().clone());
+                task::Poll::Pending
+            }
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+}
+
+/// Represents the transmission of a UDP datagram, abstracting the IO demultiplexing of the Little Tokio runtime.
+/// It provides the following two functionalities:
+///  - Registration of the file descriptor to the runtime to monitor readiness for writing to the socket.
+///  - Implementation of the `Future` trait for the event loop of the runtime to await write-ready events.
+///
+/// The file descriptor is registered only when the socket would block, and deregistered when this future gets
+/// dropped via its `Registration`.
+struct SendTo<'socket, 'buffer> {
+    socket: &'socket mut Socket,
+    buffer: &'buffer [u8],
+    addr: net::SocketAddr,
+    registration: Option<Registration>,
+}
+
+impl<'socket, 'buffer> SendTo<'socket, 'buffer> {
+    /// Creates a new `SendTo` instance from the specified `socket`.
+    fn new(socket: &'socket mut Socket, buffer: &'buffer [u8], addr: net::SocketAddr) -> Self {
+        Self {
+            socket,
+            buffer,
+            addr,
+            registration: None,
+        }
+    }
+}
+
+pub type SendToOutput = io::Result<usize>;
+
+impl<'socket, 'buffer> future::Future for SendTo<'socket, 'buffer> {
+    type Output = SendToOutput;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        // Note:
+        // `SendTo` holds no self-referential fields, i.e., it is `Unpin`, so that the fields can be borrowed
+        // separately without any pin projection.
+        let this = self.get_mut();
+        match this.socket.delegatee.send_to(this.buffer, this.addr) {
+            Ok(size) => task::Poll::Ready(Ok(size)),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                this.registration
+                    .get_or_insert_with(|| {
+                        Reactor::register(&this.socket.delegatee, Interest::WRITABLE)
+                    })
+                    .block(cx.waker().clone());
+                task::Poll::Pending
+            }
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+}
+
+/// Represents the reception of a UDP datagram from the connected peer, abstracting the IO demultiplexing of the
+/// Little Tokio runtime. It provides the following two functionalities:
+///  - Registration of the file descriptor to the runtime to monitor readiness for reading from the socket.
+///  - Implementation of the `Future` trait for the event loop of the runtime to await read-ready events.
+///
+/// The file descriptor is registered only when the socket would block, and deregistered when this future gets
+/// dropped via its `Registration`.
+struct Recv<'socket, 'buffer> {
+    socket: &'socket mut Socket,
+    buffer: &'buffer mut [u8],
+    registration: Option<Registration>,
+}
+
+impl<'socket, 'buffer> Recv<'socket, 'buffer> {
+    /// Creates a new `Recv` instance from the specified `socket`.
+    fn new(socket: &'socket mut Socket, buffer: &'buffer mut [u8]) -> Self {
+        Self {
+            socket,
+            buffer,
+            registration: None,
+        }
+    }
+}
+
+pub type RecvOutput = io::Result<usize>;
+
+impl<'socket, 'buffer> future::Future for Recv<'socket, 'buffer> {
+    type Output = RecvOutput;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        // Note:
+        // `Recv` holds no self-referential fields, i.e., it is `Unpin`, so that the fields can be borrowed
+        // separately without any pin projection.
+        let this = self.get_mut();
+        match this.socket.delegatee.recv(this.buffer) {
+            Ok(received) => {
+                if let Some(registration) = this.registration.as_mut() {
+                    registration.progress();
+                }
+                task::Poll::Ready(Ok(received))
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                this.registration
+                    .get_or_insert_with(|| {
+                        Reactor::register(&this.socket.delegatee, Interest::READABLE)
+                    })
+                    .block(cx.waker().clone());
+                task::Poll::Pending
+            }
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+}
+
+/// Represents the transmission of a UDP datagram to the connected peer, abstracting the IO demultiplexing of the
+/// Little Tokio runtime. It provides the following two functionalities:
+///  - Registration of the file descriptor to the runtime to monitor readiness for writing to the socket.
+///  - Implementation of the `Future` trait for the event loop of the runtime to await write-ready events.
+///
+/// The file descriptor is registered only when the socket would block, and deregistered when this future gets
+/// dropped via its `Registration`.
+struct Send<'socket, 'buffer> {
+    socket: &'socket mut Socket,
+    buffer: &'buffer [u8],
+    registration: Option<Registration>,
+}
+
+impl<'socket, 'buffer> Send<'socket, 'buffer> {
+    /// Creates a new `Send` instance from the specified `socket`.
+    fn new(socket: &'socket mut Socket, buffer: &'buffer [u8]) -> Self {
+        Self {
+            socket,
+            buffer,
+            registration: None,
+        }
+    }
+}
+
+pub type SendOutput = io::Result<usize>;
+
+impl<'socket, 'buffer> future::Future for Send<'socket, 'buffer> {
+    type Output = SendOutput;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        // Note:
+        // `Send` holds no self-referential fields, i.e., it is `Unpin`, so that the fields can be borrowed
+        // separately without any pin projection.
+        let this = self.get_mut();
+        match this.socket.delegatee.send(this.buffer) {
+            Ok(size) => task::Poll::Ready(Ok(size)),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                this.registration
+                    .get_or_insert_with(|| {
+                        Reactor::register(&this.socket.delegatee, Interest::WRITABLE)
+                    })
+                    .block(cx.waker().clone());
+                task::Poll::Pending
+            }
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unconnected_sockets_exchange_datagrams() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut server = Socket::bind("127.0.0.1:0").unwrap();
+            let mut client = Socket::bind("127.0.0.1:0").unwrap();
+            let server_addr = server.local_addr().unwrap();
+            let client_addr = client.local_addr().unwrap();
+            let mut buffer = [0u8; 16];
+            crate::spawn(async move {
+                let (size, from) = server.recv_from(&mut buffer).await.unwrap();
+                assert_eq!(from, client_addr);
+                server.send_to(&buffer[..size], from).await.unwrap();
+            });
+            assert_eq!(client.send_to(b"ping", server_addr).await.unwrap(), 4);
+            let mut echoed = [0u8; 16];
+            let (size, from) = client.recv_from(&mut echoed).await.unwrap();
+            assert_eq!((&echoed[..size], from), (&b"ping"[..], server_addr));
+        });
+    }
+
+    #[test]
+    fn connected_sockets_exchange_datagrams() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut left = Socket::bind("127.0.0.1:0").unwrap();
+            let mut right = Socket::bind("127.0.0.1:0").unwrap();
+            left.connect(right.local_addr().unwrap()).unwrap();
+            right.connect(left.local_addr().unwrap()).unwrap();
+            assert_eq!(left.peer_addr().unwrap(), right.local_addr().unwrap());
+            crate::spawn(async move {
+                let mut buffer = [0u8; 16];
+                for _ in 0..3 {
+                    let size = right.recv(&mut buffer).await.unwrap();
+                    buffer[..size].reverse();
+                    right.send(&buffer[..size]).await.unwrap();
+                }
+            });
+            let mut buffer = [0u8; 16];
+            for datagram in [&b"abc"[..], b"hello", b"x"] {
+                assert_eq!(left.send(datagram).await.unwrap(), datagram.len());
+                let size = left.recv(&mut buffer).await.unwrap();
+                let mut expected = datagram.to_vec();
+                expected.reverse();
+                assert_eq!(&buffer[..size], &expected[..]);
+            }
+        });
+    }
+
+    #[test]
+    fn connected_socket_filters_out_the_other_peers() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut socket = Socket::bind("127.0.0.1:0").unwrap();
+            let peer = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+            let stranger = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket.connect(peer.local_addr().unwrap()).unwrap();
+            let addr = socket.local_addr().unwrap();
+            stranger.send_to(b"stranger", addr).unwrap();
+            peer.send_to(b"peer", addr).unwrap();
+            let mut buffer = [0u8; 16];
+            let size = socket.recv(&mut buffer).await.unwrap();
+            assert_eq!(&buffer[..size], b"peer");
+        });
+    }
+
+    #[test]
+    fn send_fails_on_an_unconnected_socket() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut socket = Socket::bind("127.0.0.1:0").unwrap();
+            assert!(socket.send(b"nowhere").await.is_err());
+        });
+    }
+}
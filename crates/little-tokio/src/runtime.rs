@@ -0,0 +1,540 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of a `Builder` which configures the Little Tokio runtime, a `Handle`
+//! which refers to the runtime from the other threads, a `JoinHandle` which awaits the output of a spawned task,
+//! the `park`/`unpark` API for custom event sources, and `pending_tasks` which reports the load of the runtime.
+
+use crate::core::reactor::Reactor;
+use crate::core::scheduler::Scheduler;
+use std::sync::atomic::{self, AtomicUsize};
+use std::{fmt, future, io, marker, pin, sync, task};
+
+/// Holds the number of `Handle`s alive, while which another thread may spawn a task, and wake up the runtime, at
+/// any time.
+static HANDLES: AtomicUsize = AtomicUsize::new(0);
+
+/// Holds the number of `ParkToken`s which have been neither unparked nor dropped so far.
+static PARKED: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns `true` if a task can be woken up from outside of the runtime, i.e., a `Handle` is alive or a
+/// `ParkToken` is outstanding, so that the runtime waiting for the next events is not deadlocked even if it has
+/// neither file descriptors nor timers registered.
+pub(crate) fn has_remote_wakers() -> bool {
+    HANDLES.load(atomic::Ordering::Acquire) > 0 || PARKED.load(atomic::Ordering::Acquire) > 0
+}
+
+/// Configures the Little Tokio runtime before running a `Future` on it.
+#[derive(Default, Clone, Copy)]
+pub struct Builder {
+    /// Holds the maximal number of events processed per turn of the event loop.
+    event_budget: Option<usize>,
+    /// Holds the maximal number of connections accepted per iteration of the runtime.
+    accept_budget: Option<usize>,
+    /// Holds the maximal number of pending tasks up to which connections are accepted.
+    max_concurrent_tasks: Option<usize>,
+}
+
+impl Builder {
+    /// Creates a new `Builder` with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximal number of IO events the reactor processes per turn of the event loop. The remaining
+    /// events are carried over to the next turn, so that the task execution can interleave with the event
+    /// processing when thousands of file descriptors are ready at once. The number is unbounded by default.
+    pub fn event_budget(mut self, budget: usize) -> Self {
+        self.event_budget = Some(budget.max(1));
+        self
+    }
+
+    /// Sets the maximal number of connections the `Listener`s accept per iteration of the runtime, i.e., per batch
+    /// of the scheduled tasks polled. Once the budget is exhausted, the accepting tasks yield until the next
+    /// iteration, so that the handler tasks get polled in between instead of being flooded with new connections.
+    /// The number is unbounded by default.
+    pub fn accept_budget(mut self, budget: usize) -> Self {
+        self.accept_budget = Some(budget.max(1));
+        self
+    }
+
+    /// Sets the maximal number of pending tasks up to which the `Listener`s accept connections. Once the number of
+    /// tasks spawned but not completed yet reaches the limit, the accepting tasks stop accepting, leaving the
+    /// connections in the backlog of the listening sockets, until the tasks drain below the limit. This protects a
+    /// server from the resource exhaustion by a flood of connections. The number is unbounded by default.
+    ///
+    /// # Note:
+    /// The accepting task itself is not counted while it is being polled, but the other long-living tasks, e.g.,
+    /// the other accept loops, are.
+    pub fn max_concurrent_tasks(mut self, limit: usize) -> Self {
+        self.max_concurrent_tasks = Some(limit.max(1));
+        self
+    }
+
+    /// Runs a `Future` to completion on the Little Tokio runtime configured by this `Builder`.
+    pub fn block_on(self, main: impl future::Future<Output = ()> + marker::Send + 'static) {
+        self.apply();
+        crate::block_on(main);
+    }
+
+    /// Runs a `Future` to completion on the Little Tokio runtime configured by this `Builder`, or returns the error
+    /// the event loop fails with.
+    pub fn try_block_on(
+        self,
+        main: impl future::Future<Output = ()> + marker::Send + 'static,
+    ) -> io::Result<()> {
+        self.apply();
+        crate::try_block_on(main)
+    }
+
+    /// Applies the configuration to the Little Tokio runtime.
+    pub(crate) fn apply(self) {
+        Reactor::set_event_budget(self.event_budget);
+        Scheduler::set_accept_budget(self.accept_budget);
+        Scheduler::set_task_limit(self.max_concurrent_tasks);
+    }
+}
+
+/// Represents a handle to the Little Tokio runtime, which is `Send` so that the other threads, e.g., the threads
+/// running blocking operations, can spawn follow-up futures onto the runtime.
+///
+/// # Note:
+/// The runtime is never considered as deadlocked while a `Handle` is alive, since the future spawned through it
+/// may wake up the other tasks. Drop the `Handle`s once they are no longer needed.
+pub struct Handle {
+    _private: (),
+}
+
+impl Handle {
+    /// Creates a new `Handle` to the Little Tokio runtime.
+    pub(crate) fn new() -> Self {
+        HANDLES.fetch_add(1, atomic::Ordering::AcqRel);
+        Self { _private: () }
+    }
+
+    /// Spawns a future onto the Little Tokio runtime from any thread. The future is not polled on the calling
+    /// thread, instead the runtime gets woken up to poll it on its own thread.
+    ///
+    /// # Note:
+    /// The future spawned after the runtime has completed, i.e., after `block_on` has returned, is kept by the
+    /// runtime and gets polled once the next `block_on` starts.
+    pub fn spawn(&self, task: impl future::Future<Output = ()> + marker::Send + 'static) {
+        Scheduler::inject(Box::pin(task));
+        Reactor::wake();
+    }
+}
+
+impl Clone for Handle {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        // Note:
+        // The runtime waiting for the next events is woken up once the last `Handle` is dropped, so that it checks
+        // for a deadlock again.
+        if HANDLES.fetch_sub(1, atomic::Ordering::AcqRel) == 1 {
+            Reactor::wake();
+        }
+    }
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Handle")
+    }
+}
+
+/// Holds the output of a spawned task until its `JoinHandle` takes it.
+struct Joined<T> {
+    /// Holds the output of the task once it has completed, or the error once it has been dropped without
+    /// completing.
+    output: Option<JoinOutput<T>>,
+    /// Holds the waker of the task awaiting the `JoinHandle`.
+    waker: Option<task::Waker>,
+    /// Specifies whether the task has been aborted via `JoinHandle::abort`.
+    aborted: bool,
+    /// Holds the waker of the spawned task itself, which gets woken up to observe the abort.
+    task_waker: Option<task::Waker>,
+}
+
+/// A `Future` which completes with the output of a task spawned by `spawn_with_output`, or with the error telling
+/// why the task has been dropped without completing:
+///  - `Error::Cancelled` when the task has been aborted via `abort`, or torn down, e.g., by `block_on_timeout`.
+///  - `Error::Shutdown` when the task has been dropped by the runtime shutting down, e.g., the task spawned after
+///    the signal of `block_on_with_shutdown` has completed.
+///  - `Error::Panicked` when the task spawned by `JoinSet::spawn` has panicked.
+///
+/// # Note:
+/// Dropping a `JoinHandle` does not cancel the task, i.e., the task keeps running and its output is dropped.
+pub struct JoinHandle<T> {
+    joined: sync::Arc<sync::Mutex<Joined<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Creates a new `JoinHandle` and the `Completion` which is handed over to the spawned task.
+    pub(crate) fn new() -> (Self, Completion<T>) {
+        let joined = sync::Arc::new(sync::Mutex::new(Joined {
+            output: None,
+            waker: None,
+            aborted: false,
+            task_waker: None,
+        }));
+        let completion = Completion {
+            joined: sync::Arc::clone(&joined),
+        };
+        (Self { joined }, completion)
+    }
+
+    /// Returns `true` if the task has completed, or has been dropped, and its output is ready to be taken.
+    pub fn is_finished(&self) -> bool {
+        self.lock().output.is_some()
+    }
+
+    /// Aborts the task, i.e., the task gets dropped without being polled anymore, and the `JoinHandle` completes
+    /// with `Error::Cancelled` unless the task has completed already. The task is dropped on the thread running the
+    /// runtime once it observes the abort, which is right after this call when called from a task.
+    pub fn abort(&self) {
+        let waker = {
+            let mut joined = self.lock();
+            joined.aborted = true;
+            joined.task_waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Returns the `MutexGuard` of the shared state.
+    fn lock(&self) -> sync::MutexGuard<'_, Joined<T>> {
+        self.joined
+            .lock()
+            .expect("`MutexGuard` of the `JoinHandle` should be locked properly")
+    }
+}
+
+/// The output type of `JoinHandle`.
+pub type JoinOutput<T> = crate::Result<T>;
+
+impl<T> future::Future for JoinHandle<T> {
+    type Output = JoinOutput<T>;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut joined = self.lock();
+        match joined.output.take() {
+            Some(output) => task::Poll::Ready(output),
+            None => {
+                joined.waker = Some(cx.waker().clone());
+                task::Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "JoinHandle(finished: {})", self.is_finished())
+    }
+}
+
+/// Represents the completing side of a `JoinHandle`, which is held by the spawned task. Dropping the `Completion`
+/// without completing it, i.e., dropping the task, completes the `JoinHandle` with the error.
+pub(crate) struct Completion<T> {
+    joined: sync::Arc<sync::Mutex<Joined<T>>>,
+}
+
+impl<T> Completion<T> {
+    /// Returns `true` if the task has been aborted, otherwise sets up the given `cx` to be woken up on the abort.
+    pub(crate) fn poll_aborted(&self, cx: &task::Context<'_>) -> bool {
+        let mut joined = self.lock();
+        if !joined.aborted {
+            joined.task_waker = Some(cx.waker().clone());
+        }
+        joined.aborted
+    }
+
+    /// Stores the `output` of the task. The task awaiting the `JoinHandle` is woken up once the `Completion`, which
+    /// this method consumes, gets dropped.
+    pub(crate) fn complete(self, output: T) {
+        self.lock().output = Some(Ok(output));
+    }
+
+    /// Stores the `error` which the task has failed with, e.g., `Error::Panicked`. The task awaiting the
+    /// `JoinHandle` is woken up once the `Completion` gets dropped, as `complete` does.
+    pub(crate) fn fail(self, error: crate::Error) {
+        self.lock().output = Some(Err(error));
+    }
+
+    /// Returns the `MutexGuard` of the shared state.
+    fn lock(&self) -> sync::MutexGuard<'_, Joined<T>> {
+        self.joined
+            .lock()
+            .expect("`MutexGuard` of the `JoinHandle` should be locked properly")
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        let error = if Scheduler::is_shutting_down() {
+            crate::Error::Shutdown
+        } else {
+            crate::Error::Cancelled
+        };
+        let waker = {
+            let mut joined = self.lock();
+            joined.output.get_or_insert(Err(error));
+            joined.task_waker = None;
+            joined.waker.take()
+        };
+        // Note:
+        // The waker is woken after the lock is released since waking may poll the awaiting task eagerly.
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Represents a task parked on a custom event source via `park`, which is `Send` so that the event source can
+/// `unpark` the task from any thread.
+pub struct ParkToken {
+    waker: task::Waker,
+}
+
+impl Drop for ParkToken {
+    fn drop(&mut self) {
+        PARKED.fetch_sub(1, atomic::Ordering::AcqRel);
+    }
+}
+
+impl fmt::Debug for ParkToken {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "ParkToken")
+    }
+}
+
+/// Parks the task associated with the given `waker` on a custom event source, i.e., an event source which is
+/// neither a file descriptor nor a timer, e.g., a completion signaled by another thread. The returned `ParkToken`
+/// has to be passed to `unpark` once the event occurs, and the future polled by the task is expected to return
+/// `Poll::Pending` after parking.
+///
+/// # Note:
+/// The runtime keeps waiting for the parked task, i.e., `block_on` never returns as long as the `ParkToken` is
+/// neither unparked nor the task completes otherwise.
+pub fn park(waker: task::Waker) -> ParkToken {
+    PARKED.fetch_add(1, atomic::Ordering::AcqRel);
+    ParkToken { waker }
+}
+
+/// Unparks the task parked with the given `token`, i.e., reschedules the task and wakes up the runtime in case
+/// it is waiting for the next events. This function may be called from any thread.
+pub fn unpark(token: ParkToken) {
+    token.waker.wake_by_ref();
+    Reactor::wake();
+}
+
+/// Returns the number of the tasks spawned onto the Little Tokio runtime but not completed yet, except the task
+/// calling this function, e.g., to monitor the load of a server.
+pub fn pending_tasks() -> usize {
+    Scheduler::pending_len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::yield_now;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn join_handle_completes_with_the_output() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let handle = crate::spawn_with_output(async {
+                yield_now().await;
+                42
+            });
+            assert!(!handle.is_finished());
+            assert!(matches!(handle.await, Ok(42)));
+        });
+    }
+
+    #[test]
+    fn aborted_join_handle_completes_with_cancelled() {
+        let _serial = crate::tests::serial();
+        let dropped = sync::Arc::new(AtomicBool::new(false));
+        let flag = dropped.clone();
+        crate::block_on(async move {
+            /// Records when the aborted task gets dropped.
+            struct Guard(sync::Arc<AtomicBool>);
+            impl Drop for Guard {
+                fn drop(&mut self) {
+                    self.0.store(true, atomic::Ordering::SeqCst);
+                }
+            }
+            let handle = crate::spawn_with_output(async move {
+                let _guard = Guard(flag);
+                future::pending::<()>().await
+            });
+            handle.abort();
+            assert!(matches!(handle.await, Err(crate::Error::Cancelled)));
+        });
+        assert!(dropped.load(atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn abort_after_completion_keeps_the_output() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let handle = crate::spawn_with_output(async { 7 });
+            handle.abort();
+            assert!(matches!(handle.await, Ok(7)));
+        });
+    }
+
+    #[test]
+    fn join_handle_of_a_task_spawned_while_shutting_down_completes_with_shutdown() {
+        let _serial = crate::tests::serial();
+        let joined = sync::Arc::new(sync::Mutex::new(None));
+        let slot = joined.clone();
+        crate::block_on_with_shutdown(
+            async move {
+                crate::spawn(async move {
+                    yield_now().await;
+                    let ret = crate::spawn_with_output(async { 1 }).await;
+                    *slot.lock().unwrap() = Some(ret);
+                });
+                future::pending::<()>().await
+            },
+            async {},
+        );
+        assert!(matches!(
+            joined.lock().unwrap().take(),
+            Some(Err(crate::Error::Shutdown))
+        ));
+    }
+
+    #[test]
+    fn unpark_wakes_the_parked_task_from_another_thread() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut parked = false;
+            future::poll_fn(|cx| {
+                if parked {
+                    return task::Poll::Ready(());
+                }
+                parked = true;
+                let token = park(cx.waker().clone());
+                std::thread::spawn(move || unpark(token));
+                task::Poll::Pending
+            })
+            .await
+        });
+    }
+
+    #[test]
+    fn builder_clamps_the_budgets_to_one() {
+        let builder = Builder::new().event_budget(0).accept_budget(0);
+        assert_eq!(builder.event_budget, Some(1));
+        assert_eq!(builder.accept_budget, Some(1));
+        assert_eq!(Builder::new().event_budget, None);
+    }
+
+    #[test]
+    fn handle_spawns_onto_the_runtime_from_another_thread() {
+        let _serial = crate::tests::serial();
+        let received = sync::Arc::new(sync::Mutex::new(None));
+        let slot = received.clone();
+        let runtime = std::thread::current().id();
+        crate::block_on(async move {
+            let (sender, mut receiver) = crate::sync::channel();
+            let handle = crate::handle();
+            std::thread::spawn(move || {
+                let blocking = std::thread::current().id();
+                handle.spawn(async move {
+                    sender
+                        .send((blocking, std::thread::current().id()))
+                        .unwrap();
+                });
+            });
+            *slot.lock().unwrap() = receiver.recv().await;
+        });
+        let (blocking, polled) = received.lock().unwrap().take().unwrap();
+        assert_ne!(blocking, runtime);
+        assert_eq!(polled, runtime);
+    }
+
+    #[test]
+    fn outstanding_park_tokens_keep_the_runtime_waiting() {
+        let _serial = crate::tests::serial();
+        let started = std::time::Instant::now();
+        let ret = crate::try_block_on(async {
+            let mut parked = false;
+            future::poll_fn(|cx| {
+                if parked {
+                    return task::Poll::Ready(());
+                }
+                parked = true;
+                let token = park(cx.waker().clone());
+                std::thread::spawn(move || {
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                    unpark(token);
+                });
+                task::Poll::Pending
+            })
+            .await
+        });
+        assert!(ret.is_ok());
+        assert!(started.elapsed() >= std::time::Duration::from_millis(100));
+        assert_eq!(PARKED.load(atomic::Ordering::Acquire), 0);
+    }
+
+    #[test]
+    fn dropped_park_tokens_let_the_runtime_report_a_deadlock() {
+        let _serial = crate::tests::serial();
+        let ret = crate::try_block_on(future::poll_fn(|cx| {
+            drop(park(cx.waker().clone()));
+            task::Poll::<()>::Pending
+        }));
+        assert!(ret.is_err());
+    }
+
+    #[test]
+    fn dropped_handles_let_the_runtime_report_a_deadlock() {
+        let _serial = crate::tests::serial();
+        let handle = crate::handle();
+        let clone = handle.clone();
+        drop(handle);
+        assert!(has_remote_wakers());
+        drop(clone);
+        assert!(!has_remote_wakers());
+        assert!(crate::try_block_on(future::pending()).is_err());
+    }
+
+    #[test]
+    fn live_handle_keeps_the_runtime_waiting_until_dropped() {
+        let _serial = crate::tests::serial();
+        let started = std::time::Instant::now();
+        let ret = crate::try_block_on(future::poll_fn(|_| {
+            let handle = crate::handle();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                drop(handle);
+            });
+            task::Poll::<()>::Pending
+        }));
+        assert!(ret.is_err());
+        assert!(started.elapsed() >= std::time::Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of the `Future`s which complete when the process receives OS signals,
+//! e.g., to shut the runtime down gracefully via `block_on_with_shutdown(main, signal::ctrl_c())`.
+//!
+//! # Note:
+//! Once a signal gets listened, its default disposition, e.g., terminating the process, is replaced for the rest of
+//! the process' lifetime, i.e., the signal arriving while no task is listening is discarded.
+
+use crate::core::reactor::Reactor;
+use crate::core::token::Token;
+use std::{fmt, future, pin, task};
+
+/// Returns a `Future` which completes when the process receives `SIGINT`, i.e., Ctrl-C is pressed.
+///
+/// # Panics:
+/// Panics if the signal cannot be registered into the runtime.
+pub fn ctrl_c() -> Signal {
+    Signal::new(libc::SIGINT)
+}
+
+/// Returns a `Future` which completes when the process receives `SIGTERM`, e.g., sent by a service manager.
+///
+/// # Panics:
+/// Panics if the signal cannot be registered into the runtime.
+pub fn terminate() -> Signal {
+    Signal::new(libc::SIGTERM)
+}
+
+/// Represents a `Future` that completes when the process receives a signal.
+///
+/// # Note:
+/// The signal gets listened when the `Signal` is created, not when it gets polled for the first time, so that the
+/// signal arriving in between is not missed.
+pub struct Signal {
+    /// Holds the number of the signal.
+    signum: libc::c_int,
+    /// Holds the `Token` of the signal and the identifier of the listener, or `None` once the signal has arrived.
+    listener: Option<(Token, u64)>,
+}
+
+impl Signal {
+    /// Creates a new `Signal` listening for the signal identified by the given `signum`.
+    fn new(signum: libc::c_int) -> Self {
+        Self {
+            signum,
+            listener: Some(Reactor::register_signal(signum)),
+        }
+    }
+}
+
+impl future::Future for Signal {
+    type Output = ();
+
+    fn poll(mut self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let Some((token, id)) = self.listener else {
+            return task::Poll::Ready(());
+        };
+        if Reactor::block_signal(token, id, cx.waker().clone()) {
+            self.listener = None;
+            return task::Poll::Ready(());
+        }
+        task::Poll::Pending
+    }
+}
+
+impl Drop for Signal {
+    fn drop(&mut self) {
+        if let Some((token, id)) = self.listener {
+            Reactor::deregister_signal(token, id);
+        }
+    }
+}
+
+impl fmt::Debug for Signal {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Signal({}, {:?})", self.signum, self.listener)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time};
+
+    /// Sends `SIGTERM` to the process.
+    fn raise() {
+        // Safety:
+        // The handler installed by `terminate` replaces the default disposition, i.e., the process keeps running.
+        assert_eq!(unsafe { libc::raise(libc::SIGTERM) }, 0);
+    }
+
+    #[test]
+    fn terminate_completes_when_the_signal_arrives() {
+        let _serial = crate::tests::serial();
+        let ret = crate::block_on_timeout(
+            async {
+                let signal = terminate();
+                thread::spawn(|| {
+                    thread::sleep(time::Duration::from_millis(50));
+                    raise();
+                });
+                signal.await;
+            },
+            time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+    }
+
+    #[test]
+    fn signal_arriving_before_the_first_poll_is_not_missed() {
+        let _serial = crate::tests::serial();
+        let ret = crate::block_on_timeout(
+            async {
+                let signal = terminate();
+                raise();
+                signal.await;
+                // Note:
+                // The signal arriving while no task is listening is discarded, rather than terminating the process.
+                raise();
+            },
+            time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+    }
+
+    #[test]
+    fn block_on_with_shutdown_returns_once_the_signal_arrives() {
+        let _serial = crate::tests::serial();
+        let started = time::Instant::now();
+        let signal = terminate();
+        thread::spawn(|| {
+            thread::sleep(time::Duration::from_millis(50));
+            raise();
+        });
+        crate::block_on_with_shutdown(future::pending(), signal);
+        assert!(started.elapsed() < time::Duration::from_secs(5));
+    }
+}
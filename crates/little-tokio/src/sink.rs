@@ -0,0 +1,230 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the `Sink` trait which represents a consumer of items written asynchronously, i.e., the
+//! counterpart of a producer of items read asynchronously.
+
+use std::{convert, future, pin, task};
+
+/// Consumes items asynchronously. Sending an item consists of waiting until the sink is ready via `poll_ready`,
+/// starting the send via `start_send`, and optionally waiting until the item gets processed via `poll_flush`.
+pub trait Sink<Item> {
+    /// Represents the error the sink fails with.
+    type Error;
+
+    /// Attempts to prepare the sink to receive an item. An implementation returns `Poll::Pending` and arranges the
+    /// current task to be woken up when the sink cannot receive an item yet.
+    fn poll_ready(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>>;
+
+    /// Starts sending the given `item` into the sink, which must be preceded by a successful `poll_ready`.
+    fn start_send(self: pin::Pin<&mut Self>, item: Item) -> Result<(), Self::Error>;
+
+    /// Attempts to flush the items sent so far, i.e., waits until all of them have been processed by the sink.
+    fn poll_flush(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>>;
+
+    /// Attempts to flush the items sent so far and close the sink.
+    fn poll_close(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>>;
+}
+
+impl<S, Item> Sink<Item> for &mut S
+where
+    S: Sink<Item> + Unpin + ?Sized,
+{
+    type Error = S::Error;
+
+    fn poll_ready(
+        mut self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        pin::Pin::new(&mut **self).poll_ready(cx)
+    }
+
+    fn start_send(mut self: pin::Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        pin::Pin::new(&mut **self).start_send(item)
+    }
+
+    fn poll_flush(
+        mut self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        pin::Pin::new(&mut **self).poll_flush(cx)
+    }
+
+    fn poll_close(
+        mut self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        pin::Pin::new(&mut **self).poll_close(cx)
+    }
+}
+
+/// Collects the items into the in-memory buffer, which is always ready.
+impl<T> Sink<T> for Vec<T> {
+    type Error = convert::Infallible;
+
+    fn poll_ready(
+        self: pin::Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: pin::Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        // Safety:
+        // `Vec` never exposes pinned references to its elements, i.e., moving the items out of it is fine even if it
+        // is pinned.
+        unsafe { self.get_unchecked_mut() }.push(item);
+        Ok(())
+    }
+
+    fn poll_flush(
+        self: pin::Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        self: pin::Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        task::Poll::Ready(Ok(()))
+    }
+}
+
+/// Provides the convenience methods of `Sink`s. This trait is implemented automatically for any `Sink`.
+///
+/// ```
+/// use little_tokio::sink::SinkExt;
+///
+/// little_tokio::block_on(async {
+///     let mut sink = Vec::new();
+///     sink.send(b"hello".to_vec()).await.unwrap();
+///     sink.send(b"world".to_vec()).await.unwrap();
+///     assert_eq!(sink.concat(), b"helloworld");
+/// });
+/// ```
+pub trait SinkExt<Item>: Sink<Item> {
+    /// Sends the given `item` into the sink and flushes it, returning a `Send` struct which resolves once the
+    /// item has been processed by the sink.
+    fn send(&mut self, item: Item) -> Send<'_, Self, Item>
+    where
+        Self: Unpin,
+    {
+        Send {
+            sink: self,
+            item: Some(item),
+        }
+    }
+}
+
+impl<S, Item> SinkExt<Item> for S where S: Sink<Item> + ?Sized {}
+
+/// Represents a `Future` that sends an item into a `Sink` and flushes it.
+pub struct Send<'sink, S, Item>
+where
+    S: Sink<Item> + Unpin + ?Sized,
+{
+    sink: &'sink mut S,
+    item: Option<Item>,
+}
+
+impl<S, Item> Unpin for Send<'_, S, Item> where S: Sink<Item> + Unpin + ?Sized {}
+
+impl<S, Item> future::Future for Send<'_, S, Item>
+where
+    S: Sink<Item> + Unpin + ?Sized,
+{
+    type Output = Result<(), S::Error>;
+
+    fn poll(mut self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = &mut *self;
+        if this.item.is_some() {
+            match pin::Pin::new(&mut *this.sink).poll_ready(cx) {
+                task::Poll::Ready(Ok(())) => {}
+                task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+            let item = this
+                .item
+                .take()
+                .expect("the item should be kept until the sink gets ready");
+            if let Err(e) = pin::Pin::new(&mut *this.sink).start_send(item) {
+                return task::Poll::Ready(Err(e));
+            }
+        }
+        pin::Pin::new(&mut *this.sink).poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt;
+
+    /// Sends the body through the given `sink`, i.e., through the `Sink` implementation of `&mut S`.
+    async fn send_body<S>(mut sink: S)
+    where
+        S: Sink<&'static [u8]> + Unpin,
+        S::Error: fmt::Debug,
+    {
+        sink.send(b"body").await.unwrap();
+    }
+
+    #[test]
+    fn send_buffers_the_items_in_order() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut sink = Vec::new();
+            for line in ["GET / HTTP/1.1\r\n", "Host: localhost\r\n", "\r\n"] {
+                sink.send(line.as_bytes()).await.unwrap();
+            }
+            send_body(&mut sink).await;
+            assert_eq!(
+                sink.concat(),
+                b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\nbody"
+            );
+        });
+    }
+
+    #[test]
+    fn send_waits_until_the_sink_gets_ready() {
+        let _serial = crate::tests::serial();
+        let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let slot = received.clone();
+        crate::block_on(async move {
+            let (mut sender, mut receiver) = crate::sync::spsc::channel(1);
+            crate::spawn(async move {
+                for item in 0..3 {
+                    sender.send(item).await.unwrap();
+                }
+            });
+            // Note:
+            // The sending task is blocked on the second item, since the channel buffers a single item.
+            while let Some(item) = receiver.recv().await {
+                slot.lock().unwrap().push(item);
+            }
+        });
+        assert_eq!(*received.lock().unwrap(), [0, 1, 2]);
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the `Stream` trait which represents a producer of items read asynchronously, i.e., the
+//! asynchronous counterpart of `Iterator`.
+
+use std::{future, pin, task};
+
+/// Produces items asynchronously. An implementation returns `Poll::Pending` and arranges the current task to be
+/// woken up when the next item is not available yet, and `Poll::Ready(None)` once the stream has been exhausted.
+pub trait Stream {
+    /// Represents the items the stream produces.
+    type Item;
+
+    /// Attempts to pull out the next item of the stream.
+    fn poll_next(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>>;
+}
+
+impl<S> Stream for &mut S
+where
+    S: Stream + Unpin + ?Sized,
+{
+    type Item = S::Item;
+
+    fn poll_next(
+        mut self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        pin::Pin::new(&mut **self).poll_next(cx)
+    }
+}
+
+/// Provides the convenience methods of `Stream`s. This trait is implemented automatically for any `Stream`.
+///
+/// ```
+/// use little_tokio::stream::StreamExt;
+///
+/// little_tokio::block_on(async {
+///     let (sender, mut receiver) = little_tokio::sync::channel();
+///     sender.send(1).unwrap();
+///     sender.send(2).unwrap();
+///     drop(sender);
+///     assert_eq!(receiver.next().await, Some(1));
+///     assert_eq!(receiver.next().await, Some(2));
+///     assert_eq!(receiver.next().await, None);
+/// });
+/// ```
+pub trait StreamExt: Stream {
+    /// Returns a `Next` struct which resolves to the next item of the stream, or `None` once the stream has been
+    /// exhausted.
+    fn next(&mut self) -> Next<'_, Self>
+    where
+        Self: Unpin,
+    {
+        Next { stream: self }
+    }
+}
+
+impl<S> StreamExt for S where S: Stream + ?Sized {}
+
+/// Represents a `Future` that pulls out the next item of a `Stream`.
+pub struct Next<'stream, S>
+where
+    S: Stream + Unpin + ?Sized,
+{
+    stream: &'stream mut S,
+}
+
+impl<S> Unpin for Next<'_, S> where S: Stream + Unpin + ?Sized {}
+
+impl<S> future::Future for Next<'_, S>
+where
+    S: Stream + Unpin + ?Sized,
+{
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        pin::Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Represents a stream counting down to zero, which is pending once before producing each item.
+    struct Countdown {
+        remaining: u32,
+        yielded: bool,
+    }
+
+    impl Stream for Countdown {
+        type Item = u32;
+
+        fn poll_next(
+            mut self: pin::Pin<&mut Self>,
+            cx: &mut task::Context<'_>,
+        ) -> task::Poll<Option<Self::Item>> {
+            if self.remaining == 0 {
+                return task::Poll::Ready(None);
+            }
+            if !self.yielded {
+                self.yielded = true;
+                cx.waker().wake_by_ref();
+                return task::Poll::Pending;
+            }
+            self.yielded = false;
+            self.remaining -= 1;
+            task::Poll::Ready(Some(self.remaining))
+        }
+    }
+
+    #[test]
+    fn next_pulls_out_the_items_until_the_stream_is_exhausted() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut countdown = Countdown {
+                remaining: 3,
+                yielded: false,
+            };
+            let mut items = Vec::new();
+            while let Some(item) = countdown.next().await {
+                items.push(item);
+            }
+            assert_eq!(items, [2, 1, 0]);
+            assert_eq!(countdown.next().await, None);
+        });
+    }
+
+    #[test]
+    fn next_pulls_out_the_items_through_a_mutable_reference() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut countdown = Countdown {
+                remaining: 2,
+                yielded: false,
+            };
+            let mut borrowed = &mut countdown;
+            assert_eq!(StreamExt::next(&mut borrowed).await, Some(1));
+            assert_eq!(countdown.next().await, Some(0));
+        });
+    }
+}
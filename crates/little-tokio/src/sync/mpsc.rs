@@ -0,0 +1,289 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of an unbounded multi-producer single-consumer channel, which is backed
+//! by a `VecDeque`.
+//!
+//! # Note:
+//! Sending never waits since the buffer grows as needed, hence `Sender::send` is a plain function which can be
+//! called from anywhere, e.g., from the `Drop` of a connection handler. The `Sender` can be cloned so that several
+//! tasks fan in to a single consuming task, and the channel gets closed once all the `Sender`s have been dropped.
+
+pub use crate::sync::spsc::SendError;
+
+use crate::stream::Stream;
+use std::{collections, fmt, future, mem, pin, sync, task};
+
+/// Creates an unbounded multi-producer single-consumer channel. The `Receiver` waits when the buffer is empty.
+///
+/// ```
+/// little_tokio::block_on(async {
+///     let (sender, mut receiver) = little_tokio::sync::channel();
+///     for id in 0..2 {
+///         let sender = sender.clone();
+///         little_tokio::spawn(async move {
+///             sender.send(id).unwrap();
+///         });
+///     }
+///     drop(sender);
+///     let mut received = Vec::new();
+///     while let Some(id) = receiver.recv().await {
+///         received.push(id);
+///     }
+///     received.sort();
+///     assert_eq!(received, [0, 1]);
+/// });
+/// ```
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = sync::Arc::new(sync::Mutex::new(Shared {
+        buffer: collections::VecDeque::new(),
+        recv_waker: None,
+        senders: 1,
+        receiver_closed: false,
+    }));
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+/// Represents the state shared by the `Sender`s and the `Receiver`.
+struct Shared<T> {
+    /// Holds the values sent but not received yet.
+    buffer: collections::VecDeque<T>,
+    /// Holds the waker of the `Receiver` waiting for a value.
+    recv_waker: Option<task::Waker>,
+    /// Holds the number of the `Sender`s alive.
+    senders: usize,
+    /// Specifies whether the `Receiver` has been dropped.
+    receiver_closed: bool,
+}
+
+/// The sending half of the channel, which can be cloned to send values from several tasks.
+pub struct Sender<T> {
+    /// Holds the state shared with the `Receiver`.
+    shared: sync::Arc<sync::Mutex<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends the given `value` into the channel and wakes up the `Receiver` waiting for it. Returns the `value`
+    /// back as the `SendError` if the `Receiver` has been dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut shared = self.shared();
+        if shared.receiver_closed {
+            return Err(SendError(value));
+        }
+        shared.buffer.push_back(value);
+        let waker = shared.recv_waker.take();
+        drop(shared);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the `Receiver` has been dropped, i.e., any further value would be given back.
+    pub fn is_closed(&self) -> bool {
+        self.shared().receiver_closed
+    }
+
+    /// Returns the `Shared` state guarded by the `Mutex`.
+    fn shared(&self) -> sync::MutexGuard<'_, Shared<T>> {
+        self.shared
+            .lock()
+            .expect("`MutexGuard` of the channel should be locked properly")
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared();
+        shared.senders -= 1;
+        if shared.senders > 0 {
+            return;
+        }
+        let waker = shared.recv_waker.take();
+        drop(shared);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Sender {{ .. }}")
+    }
+}
+
+/// The receiving half of the channel.
+pub struct Receiver<T> {
+    /// Holds the state shared with the `Sender`s.
+    shared: sync::Arc<sync::Mutex<Shared<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next value from the channel, waiting for a value if the buffer is empty. Returns `None` once
+    /// all the `Sender`s have been dropped and all the buffered values have been received.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    /// Receives the next value from the channel, registering the current task to be woken up when the buffer is
+    /// empty.
+    fn poll_recv(&mut self, cx: &mut task::Context<'_>) -> task::Poll<RecvOutput<T>> {
+        let mut shared = self.shared();
+        match shared.buffer.pop_front() {
+            Some(value) => task::Poll::Ready(Some(value)),
+            None if shared.senders == 0 => task::Poll::Ready(None),
+            None => {
+                shared.recv_waker = Some(cx.waker().clone());
+                task::Poll::Pending
+            }
+        }
+    }
+
+    /// Returns the `Shared` state guarded by the `Mutex`.
+    fn shared(&self) -> sync::MutexGuard<'_, Shared<T>> {
+        self.shared
+            .lock()
+            .expect("`MutexGuard` of the channel should be locked properly")
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        // Note:
+        // The buffered values are dropped after the `Shared` state gets unlocked, since dropping them may drop the
+        // `Sender`s sent through the channel.
+        let buffer = {
+            let mut shared = self.shared();
+            shared.receiver_closed = true;
+            mem::take(&mut shared.buffer)
+        };
+        drop(buffer);
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Receiver {{ .. }}")
+    }
+}
+
+/// Yields the values sent into the channel, and ends once all the `Sender`s have been dropped.
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// Represents the result of `Receiver::recv`.
+pub type RecvOutput<T> = Option<T>;
+
+/// Represents a `Future` that receives a value from the channel.
+pub struct Recv<'receiver, T> {
+    /// Holds the `Receiver` to receive the value.
+    receiver: &'receiver mut Receiver<T>,
+}
+
+impl<T> future::Future for Recv<'_, T> {
+    type Output = RecvOutput<T>;
+
+    fn poll(mut self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::yield_now;
+
+    #[test]
+    fn receiver_drains_the_values_of_two_producers() {
+        let _serial = crate::tests::serial();
+        let received = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let slot = received.clone();
+        crate::block_on(async move {
+            let (sender, mut receiver) = channel();
+            for producer in ['a', 'b'] {
+                let sender = sender.clone();
+                crate::spawn(async move {
+                    for value in 0..3 {
+                        sender.send((producer, value)).unwrap();
+                        yield_now().await;
+                    }
+                });
+            }
+            drop(sender);
+            while let Some(value) = receiver.recv().await {
+                slot.lock().unwrap().push(value);
+            }
+        });
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 6);
+        for producer in ['a', 'b'] {
+            let values: Vec<_> = received
+                .iter()
+                .filter(|(from, _)| *from == producer)
+                .map(|(_, value)| *value)
+                .collect();
+            assert_eq!(values, [0, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn receiver_ends_once_all_the_senders_are_dropped() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let (sender, mut receiver) = channel();
+            let cloned = sender.clone();
+            sender.send(1).unwrap();
+            drop(sender);
+            crate::spawn(async move {
+                yield_now().await;
+                cloned.send(2).unwrap();
+            });
+            assert_eq!(receiver.recv().await, Some(1));
+            assert_eq!(receiver.recv().await, Some(2));
+            assert_eq!(receiver.recv().await, None);
+        });
+    }
+
+    #[test]
+    fn send_gives_the_value_back_once_the_receiver_is_dropped() {
+        let (sender, receiver) = channel();
+        assert!(!sender.is_closed());
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert!(matches!(sender.send(7), Err(SendError(7))));
+    }
+}
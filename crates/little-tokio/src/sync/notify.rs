@@ -0,0 +1,275 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of a `Notify` which signals tasks without sending any data, e.g., to
+//! have the connection handlers spawned by a server shut down.
+//!
+//! # Note:
+//! A `Notify` is usually shared among tasks via an `Arc`. The waiting tasks are woken up in the order they have
+//! started waiting, and the notification sent by `notify_one` while no task is waiting is kept as a permit which
+//! completes the next `notified` immediately.
+
+use std::{collections, fmt, future, mem, pin, sync, task};
+
+/// Represents the state of a `Notify` guarded by its `Mutex`.
+#[derive(Default)]
+struct Inner {
+    /// Holds the identifier which will be assigned to the next waiting `Notified`.
+    next_id: u64,
+    /// Holds the wakers of the waiting `Notified`s keyed by their identifiers, i.e., in the order they have started
+    /// waiting.
+    waiters: collections::BTreeMap<u64, Option<task::Waker>>,
+    /// Holds the identifiers of the `Notified`s woken up by `notify_one` which have not observed it yet, so that
+    /// the notification is passed on when such a `Notified` gets dropped.
+    notified: collections::HashSet<u64>,
+    /// Holds the number of `notify_waiters` calls so far, so that a `Notified` created before a call completes
+    /// even if it has not been polled yet.
+    broadcasts: u64,
+    /// Specifies whether a `notify_one` has been called while no task has been waiting.
+    permit: bool,
+}
+
+impl Inner {
+    /// Notifies the oldest waiting `Notified`, or stores the permit if nothing is waiting, and returns the waker to
+    /// be woken up after the `Mutex` gets unlocked.
+    fn notify_one(&mut self) -> Option<task::Waker> {
+        match self.waiters.pop_first() {
+            Some((id, waker)) => {
+                self.notified.insert(id);
+                waker
+            }
+            None => {
+                self.permit = true;
+                None
+            }
+        }
+    }
+}
+
+/// Notifies tasks waiting for an event without any data attached.
+#[derive(Default)]
+pub struct Notify {
+    /// Holds the state guarded by the `Mutex`, since tasks are required to be `Send`.
+    inner: sync::Mutex<Inner>,
+}
+
+impl Notify {
+    /// Creates a new `Notify` without any permit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a `Future` which completes once this `Notify` gets notified.
+    pub fn notified(&self) -> Notified<'_> {
+        let broadcasts = self.inner().broadcasts;
+        Notified {
+            notify: self,
+            state: State::Init(broadcasts),
+        }
+    }
+
+    /// Wakes up the task which has been waiting the longest. If no task is waiting, the notification is stored as
+    /// a permit, i.e., the next `notified` completes immediately. The permits do not accumulate.
+    pub fn notify_one(&self) {
+        let waker = self.inner().notify_one();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Wakes up all the tasks waiting at the moment, including the `Notified`s which have been created but not
+    /// polled yet. No permit is stored.
+    pub fn notify_waiters(&self) {
+        let waiters = {
+            let mut inner = self.inner();
+            inner.broadcasts = inner.broadcasts.wrapping_add(1);
+            mem::take(&mut inner.waiters)
+        };
+        for waker in waiters.into_values().flatten() {
+            waker.wake();
+        }
+    }
+
+    /// Returns the `Inner` state guarded by the `Mutex`.
+    fn inner(&self) -> sync::MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .expect("`MutexGuard` of the `Notify` should be locked properly")
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Notify {{ .. }}")
+    }
+}
+
+/// Represents the state of a `Notified`.
+#[derive(Clone, Copy)]
+enum State {
+    /// Specifies the `Notified` which has not been polled yet, together with the number of `notify_waiters` calls
+    /// observed when it has been created.
+    Init(u64),
+    /// Specifies the `Notified` waiting with the given identifier.
+    Waiting(u64),
+    /// Specifies the `Notified` which has completed.
+    Done,
+}
+
+/// Represents a `Future` that completes once the `Notify` gets notified.
+pub struct Notified<'notify> {
+    /// Holds the `Notify` to wait for.
+    notify: &'notify Notify,
+    /// Holds the current state.
+    state: State,
+}
+
+impl future::Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut inner = self.notify.inner();
+        match self.state {
+            State::Init(broadcasts) => {
+                if inner.broadcasts != broadcasts || mem::take(&mut inner.permit) {
+                    drop(inner);
+                    self.state = State::Done;
+                    return task::Poll::Ready(());
+                }
+                let id = inner.next_id;
+                inner.next_id += 1;
+                inner.waiters.insert(id, Some(cx.waker().clone()));
+                drop(inner);
+                self.state = State::Waiting(id);
+                task::Poll::Pending
+            }
+            State::Waiting(id) => match inner.waiters.get_mut(&id) {
+                Some(waker) => {
+                    *waker = Some(cx.waker().clone());
+                    task::Poll::Pending
+                }
+                None => {
+                    inner.notified.remove(&id);
+                    drop(inner);
+                    self.state = State::Done;
+                    task::Poll::Ready(())
+                }
+            },
+            State::Done => task::Poll::Ready(()),
+        }
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let State::Waiting(id) = self.state else {
+            return;
+        };
+        let mut inner = self.notify.inner();
+        if inner.waiters.remove(&id).is_some() || !inner.notified.remove(&id) {
+            return;
+        }
+        // Note:
+        // The notification sent by `notify_one` would be lost if this `Notified` was dropped without observing it,
+        // e.g., when it loses a `select!`, hence it is passed on to the next waiting task.
+        let waker = inner.notify_one();
+        drop(inner);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for Notified<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Notified {{ .. }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Polls the given `future` once, and returns `true` if it is pending.
+    async fn is_pending(mut future: pin::Pin<&mut impl future::Future>) -> bool {
+        future::poll_fn(|cx| task::Poll::Ready(future.as_mut().poll(cx).is_pending())).await
+    }
+
+    #[test]
+    fn notify_one_without_waiters_stores_a_single_permit() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let notify = Notify::new();
+            notify.notify_one();
+            notify.notify_one();
+            notify.notified().await;
+            assert!(is_pending(pin::pin!(notify.notified())).await);
+        });
+    }
+
+    #[test]
+    fn notify_one_wakes_the_waiters_in_the_order_they_have_started_waiting() {
+        let _serial = crate::tests::serial();
+        let log = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let tasks = log.clone();
+        crate::block_on(async move {
+            let notify = sync::Arc::new(Notify::new());
+            for name in ['a', 'b', 'c'] {
+                let notify = notify.clone();
+                let log = tasks.clone();
+                crate::spawn(async move {
+                    notify.notified().await;
+                    log.lock().unwrap().push(name);
+                });
+            }
+            for _ in 0..3 {
+                notify.notify_one();
+                crate::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+        });
+        assert_eq!(*log.lock().unwrap(), ['a', 'b', 'c']);
+    }
+
+    #[test]
+    fn notify_waiters_wakes_all_the_notified_created_so_far() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let notify = Notify::new();
+            let mut polled = pin::pin!(notify.notified());
+            assert!(is_pending(polled.as_mut()).await);
+            let unpolled = notify.notified();
+            notify.notify_waiters();
+            polled.await;
+            unpolled.await;
+            // Note:
+            // No permit is stored, i.e., the `Notified` created afterwards waits.
+            assert!(is_pending(pin::pin!(notify.notified())).await);
+        });
+    }
+
+    #[test]
+    fn dropped_notified_passes_the_notification_on() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let notify = Notify::new();
+            let mut first = Box::pin(notify.notified());
+            let mut second = pin::pin!(notify.notified());
+            assert!(is_pending(first.as_mut()).await);
+            assert!(is_pending(second.as_mut()).await);
+            notify.notify_one();
+            drop(first);
+            second.await;
+        });
+    }
+}
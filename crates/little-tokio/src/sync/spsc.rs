@@ -0,0 +1,555 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of a bounded single-producer single-consumer channel, which is backed
+//! by a fixed ring buffer.
+//!
+//! # Note:
+//! This channel is meant for the case where exactly one task produces values and exactly one task consumes them,
+//! e.g., a parser feeding a writer task. Since neither the `Sender` nor the `Receiver` can be cloned, the shared
+//! state only needs to keep a single waker per side, and the buffer is allocated once when the channel is created.
+//! There are two flavors of the channel:
+//!  - `channel` creates the halves which are `Send`, i.e., which can be moved into the tasks spawned by `spawn`,
+//!    of which the shared state is guarded by a `Mutex` which is never contended on the single-threaded runtime.
+//!  - `local_channel` creates the halves which are not `Send`, i.e., which can only be moved into the tasks spawned
+//!    by `spawn_local`, of which the shared state is a `RefCell` without any atomic operation.
+//!
+//! Prefer `sync::mpsc` when several tasks produce values, or when the producer must never wait for the consumer,
+//! since its buffer is unbounded. Run `cargo bench -p little-tokio --bench channels` to compare the flavors.
+
+use crate::sink::Sink;
+use crate::stream::Stream;
+use std::{cell, fmt, future, pin, rc, sync, task};
+
+/// Creates a bounded single-producer single-consumer channel which buffers at most `capacity` values. The `Sender`
+/// waits when the buffer is full, and the `Receiver` waits when the buffer is empty.
+///
+/// # Panics:
+/// Panics if the given `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "capacity of the channel should be positive");
+    let shared = sync::Arc::new(sync::Mutex::new(Shared::new(capacity)));
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+/// Creates a bounded single-producer single-consumer channel which buffers at most `capacity` values, of which the
+/// halves are not `Send`, i.e., which is shared by the tasks spawned by `spawn_local` without any synchronization.
+///
+/// # Panics:
+/// Panics if the given `capacity` is zero.
+pub fn local_channel<T>(capacity: usize) -> (LocalSender<T>, LocalReceiver<T>) {
+    assert!(capacity > 0, "capacity of the channel should be positive");
+    let shared = rc::Rc::new(cell::RefCell::new(Shared::new(capacity)));
+    (
+        LocalSender {
+            shared: shared.clone(),
+        },
+        LocalReceiver { shared },
+    )
+}
+
+/// Represents the state shared by the `Sender` and the `Receiver`.
+///
+/// # Note:
+/// The methods of the `Shared` state never wake a task themselves but return the waker to be woken, so that the
+/// callers wake it after releasing the shared state, as `sync::mpsc` does.
+struct Shared<T> {
+    /// Holds the slots of the ring buffer.
+    buffer: Box<[Option<T>]>,
+    /// Holds the index of the slot which will be received next.
+    head: usize,
+    /// Holds the number of values currently buffered.
+    len: usize,
+    /// Holds the waker of the `Sender` waiting for a free slot.
+    send_waker: Option<task::Waker>,
+    /// Holds the waker of the `Receiver` waiting for a value.
+    recv_waker: Option<task::Waker>,
+    /// Specifies whether the `Sender` has been dropped.
+    sender_closed: bool,
+    /// Specifies whether the `Receiver` has been dropped.
+    receiver_closed: bool,
+}
+
+impl<T> Shared<T> {
+    /// Creates a new `Shared` state with the given `capacity`.
+    fn new(capacity: usize) -> Self {
+        Self {
+            buffer: (0..capacity).map(|_| None).collect(),
+            head: 0,
+            len: 0,
+            send_waker: None,
+            recv_waker: None,
+            sender_closed: false,
+            receiver_closed: false,
+        }
+    }
+
+    /// Pushes the given `value` into the tail of the ring buffer and returns the waker of the `Receiver` waiting
+    /// for a value, or returns the `value` back if the buffer is full.
+    fn push(&mut self, value: T) -> Result<Option<task::Waker>, T> {
+        if self.len == self.buffer.len() {
+            return Err(value);
+        }
+        let tail = (self.head + self.len) % self.buffer.len();
+        self.buffer[tail] = Some(value);
+        self.len += 1;
+        Ok(self.recv_waker.take())
+    }
+
+    /// Pops the value from the head of the ring buffer together with the waker of the `Sender` waiting for a free
+    /// slot, or returns `None` if the buffer is empty.
+    fn pop(&mut self) -> Option<(T, Option<task::Waker>)> {
+        if self.len == 0 {
+            return None;
+        }
+        let value = self.buffer[self.head].take()?;
+        self.head = (self.head + 1) % self.buffer.len();
+        self.len -= 1;
+        Some((value, self.send_waker.take()))
+    }
+
+    /// Tries to send the value held by the given `slot`, registering the `Sender` to be woken up when the buffer
+    /// is full. Returns the waker to be woken alongside the result.
+    fn poll_send(
+        &mut self,
+        slot: &mut Option<T>,
+        cx: &mut task::Context<'_>,
+    ) -> (task::Poll<SendOutput<T>>, Option<task::Waker>) {
+        let value = slot
+            .take()
+            .expect("`Send` should not be polled after completion");
+        if self.receiver_closed {
+            return (task::Poll::Ready(Err(SendError(value))), None);
+        }
+        match self.push(value) {
+            Ok(waker) => (task::Poll::Ready(Ok(())), waker),
+            Err(value) => {
+                self.send_waker = Some(cx.waker().clone());
+                *slot = Some(value);
+                (task::Poll::Pending, None)
+            }
+        }
+    }
+
+    /// Tries to receive a value, registering the `Receiver` to be woken up when the buffer is empty. Returns the
+    /// waker to be woken alongside the result.
+    fn poll_recv(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> (task::Poll<RecvOutput<T>>, Option<task::Waker>) {
+        match self.pop() {
+            Some((value, waker)) => (task::Poll::Ready(Some(value)), waker),
+            None if self.sender_closed => (task::Poll::Ready(None), None),
+            None => {
+                self.recv_waker = Some(cx.waker().clone());
+                (task::Poll::Pending, None)
+            }
+        }
+    }
+
+    /// Marks the `Sender` as dropped and returns the waker of the `Receiver` waiting for a value.
+    fn close_sender(&mut self) -> Option<task::Waker> {
+        self.sender_closed = true;
+        self.recv_waker.take()
+    }
+
+    /// Marks the `Receiver` as dropped and returns the waker of the `Sender` waiting for a free slot.
+    fn close_receiver(&mut self) -> Option<task::Waker> {
+        self.receiver_closed = true;
+        self.send_waker.take()
+    }
+}
+
+/// Wakes up the given `waker`, if any, which has been taken out of the `Shared` state released beforehand.
+fn wake(waker: Option<task::Waker>) {
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// Represents the error returned when the `Receiver` has been dropped, which gives the unsent value back.
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "SendError {{ .. }}")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "channel closed")
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+/// The sending half of the channel.
+pub struct Sender<T> {
+    /// Holds the state shared with the `Receiver`.
+    shared: sync::Arc<sync::Mutex<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends the given `value` into the channel, waiting for a free slot if the buffer is full. Returns the
+    /// `value` back as the `SendError` if the `Receiver` has been dropped.
+    pub fn send(&mut self, value: T) -> Send<'_, T> {
+        Send {
+            sender: self,
+            value: Some(value),
+        }
+    }
+
+    /// Returns the `Shared` state guarded by the `Mutex`.
+    fn shared(&self) -> sync::MutexGuard<'_, Shared<T>> {
+        self.shared
+            .lock()
+            .expect("`MutexGuard` of the channel should be locked properly")
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let waker = self.shared().close_sender();
+        wake(waker);
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Sender {{ .. }}")
+    }
+}
+
+/// Sends the items into the channel, which is ready while the buffer has a free slot. The items are buffered
+/// into the channel immediately, so that flushing waits for nothing.
+impl<T> Sink<T> for Sender<T> {
+    type Error = SendError<T>;
+
+    fn poll_ready(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        let mut shared = self.shared();
+        // Note:
+        // A closed channel is reported as ready, so that the subsequent `start_send` gives the item back.
+        if shared.receiver_closed || shared.len < shared.buffer.len() {
+            return task::Poll::Ready(Ok(()));
+        }
+        shared.send_waker = Some(cx.waker().clone());
+        task::Poll::Pending
+    }
+
+    fn start_send(self: pin::Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let waker = {
+            let mut shared = self.shared();
+            if shared.receiver_closed {
+                return Err(SendError(item));
+            }
+            shared.push(item).map_err(SendError)?
+        };
+        wake(waker);
+        Ok(())
+    }
+
+    fn poll_flush(
+        self: pin::Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        self: pin::Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        task::Poll::Ready(Ok(()))
+    }
+}
+
+/// The receiving half of the channel.
+pub struct Receiver<T> {
+    /// Holds the state shared with the `Sender`.
+    shared: sync::Arc<sync::Mutex<Shared<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next value from the channel, waiting for a value if the buffer is empty. Returns `None` once
+    /// the `Sender` has been dropped and all the buffered values have been received.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    /// Returns the `Shared` state guarded by the `Mutex`.
+    fn shared(&self) -> sync::MutexGuard<'_, Shared<T>> {
+        self.shared
+            .lock()
+            .expect("`MutexGuard` of the channel should be locked properly")
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let waker = self.shared().close_receiver();
+        wake(waker);
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Receiver {{ .. }}")
+    }
+}
+
+/// Represents the result of `Sender::send`.
+pub type SendOutput<T> = Result<(), SendError<T>>;
+
+/// Represents a `Future` that sends a value into the channel.
+pub struct Send<'sender, T> {
+    /// Holds the `Sender` to send the value.
+    sender: &'sender mut Sender<T>,
+    /// Holds the value to be sent.
+    value: Option<T>,
+}
+
+impl<T> Unpin for Send<'_, T> {}
+
+impl<T> future::Future for Send<'_, T> {
+    type Output = SendOutput<T>;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.get_mut();
+        let (poll, waker) = this.sender.shared().poll_send(&mut this.value, cx);
+        wake(waker);
+        poll
+    }
+}
+
+/// Yields the values sent into the channel, and ends once the `Sender` has been dropped.
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        let (poll, waker) = self.shared().poll_recv(cx);
+        wake(waker);
+        poll
+    }
+}
+
+/// Represents the result of `Receiver::recv`.
+pub type RecvOutput<T> = Option<T>;
+
+/// Represents a `Future` that receives a value from the channel.
+pub struct Recv<'receiver, T> {
+    /// Holds the `Receiver` to receive the value.
+    receiver: &'receiver mut Receiver<T>,
+}
+
+impl<T> future::Future for Recv<'_, T> {
+    type Output = RecvOutput<T>;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let (poll, waker) = self.receiver.shared().poll_recv(cx);
+        wake(waker);
+        poll
+    }
+}
+
+/// The sending half of the channel created by `local_channel`.
+pub struct LocalSender<T> {
+    /// Holds the state shared with the `LocalReceiver`.
+    shared: rc::Rc<cell::RefCell<Shared<T>>>,
+}
+
+impl<T> LocalSender<T> {
+    /// Sends the given `value` into the channel, waiting for a free slot if the buffer is full. Returns the
+    /// `value` back as the `SendError` if the `LocalReceiver` has been dropped.
+    pub fn send(&mut self, value: T) -> LocalSend<'_, T> {
+        LocalSend {
+            sender: self,
+            value: Some(value),
+        }
+    }
+}
+
+impl<T> Drop for LocalSender<T> {
+    fn drop(&mut self) {
+        let waker = self.shared.borrow_mut().close_sender();
+        wake(waker);
+    }
+}
+
+impl<T> fmt::Debug for LocalSender<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "LocalSender {{ .. }}")
+    }
+}
+
+/// The receiving half of the channel created by `local_channel`.
+pub struct LocalReceiver<T> {
+    /// Holds the state shared with the `LocalSender`.
+    shared: rc::Rc<cell::RefCell<Shared<T>>>,
+}
+
+impl<T> LocalReceiver<T> {
+    /// Receives the next value from the channel, waiting for a value if the buffer is empty. Returns `None` once
+    /// the `LocalSender` has been dropped and all the buffered values have been received.
+    pub fn recv(&mut self) -> LocalRecv<'_, T> {
+        LocalRecv { receiver: self }
+    }
+}
+
+impl<T> Drop for LocalReceiver<T> {
+    fn drop(&mut self) {
+        let waker = self.shared.borrow_mut().close_receiver();
+        wake(waker);
+    }
+}
+
+impl<T> fmt::Debug for LocalReceiver<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "LocalReceiver {{ .. }}")
+    }
+}
+
+/// Represents a `Future` that sends a value into the channel created by `local_channel`.
+pub struct LocalSend<'sender, T> {
+    /// Holds the `LocalSender` to send the value.
+    sender: &'sender mut LocalSender<T>,
+    /// Holds the value to be sent.
+    value: Option<T>,
+}
+
+impl<T> Unpin for LocalSend<'_, T> {}
+
+impl<T> future::Future for LocalSend<'_, T> {
+    type Output = SendOutput<T>;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.get_mut();
+        let (poll, waker) = this
+            .sender
+            .shared
+            .borrow_mut()
+            .poll_send(&mut this.value, cx);
+        wake(waker);
+        poll
+    }
+}
+
+/// Represents a `Future` that receives a value from the channel created by `local_channel`.
+pub struct LocalRecv<'receiver, T> {
+    /// Holds the `LocalReceiver` to receive the value.
+    receiver: &'receiver mut LocalReceiver<T>,
+}
+
+impl<T> future::Future for LocalRecv<'_, T> {
+    type Output = RecvOutput<T>;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let (poll, waker) = self.receiver.shared.borrow_mut().poll_recv(cx);
+        wake(waker);
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn ring_buffer_wraps_around() {
+        let mut shared = Shared::new(3);
+        for round in 0..10 {
+            assert!(shared.push(round * 2).unwrap().is_none());
+            assert!(shared.push(round * 2 + 1).unwrap().is_none());
+            assert_eq!(shared.pop().map(|(value, _)| value), Some(round * 2));
+            assert_eq!(shared.pop().map(|(value, _)| value), Some(round * 2 + 1));
+            assert!(shared.pop().is_none());
+        }
+        assert!(shared.push(0).is_ok());
+        assert!(shared.push(1).is_ok());
+        assert!(shared.push(2).is_ok());
+        assert_eq!(shared.push(3).err(), Some(3));
+    }
+
+    #[test]
+    fn sender_waits_while_the_buffer_is_full() {
+        let _serial = crate::tests::serial();
+        let sent = sync::Arc::new(AtomicUsize::new(0));
+        let received = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let (counter, output) = (sent.clone(), received.clone());
+        crate::block_on(async move {
+            let (mut sender, mut receiver) = channel(2);
+            crate::spawn(async move {
+                for value in 0..7 {
+                    sender.send(value).await.unwrap();
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+            // Note:
+            // The producer is polled on `spawn`, and fills up the buffer before waiting for a free slot.
+            assert_eq!(sent.load(Ordering::SeqCst), 2);
+            while let Some(value) = receiver.recv().await {
+                assert!(sent.load(Ordering::SeqCst) <= value + 1 + 2);
+                output.lock().unwrap().push(value);
+            }
+        });
+        assert_eq!(*received.lock().unwrap(), (0..7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn receiver_drop_gives_the_value_back() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let (mut sender, receiver) = channel(1);
+            drop(receiver);
+            assert_eq!(sender.send(1).await.unwrap_err().0, 1);
+        });
+    }
+
+    #[test]
+    fn local_channel_wraps_around_under_backpressure() {
+        let _serial = crate::tests::serial();
+        let received = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let output = received.clone();
+        crate::block_on(async move {
+            crate::spawn_local(async move {
+                let (mut sender, mut receiver) = local_channel(3);
+                let producer = rc::Rc::new(cell::Cell::new(0));
+                let sent = producer.clone();
+                crate::spawn_local(async move {
+                    for value in 0..10 {
+                        sender.send(value).await.unwrap();
+                        sent.set(sent.get() + 1);
+                    }
+                });
+                assert_eq!(producer.get(), 3);
+                while let Some(value) = receiver.recv().await {
+                    output.lock().unwrap().push(value);
+                }
+            });
+        });
+        assert_eq!(*received.lock().unwrap(), (0..10).collect::<Vec<_>>());
+    }
+}
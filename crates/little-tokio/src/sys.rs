@@ -27,5 +27,5 @@ macro_rules! syscall {
     }};
 }
 
-#[cfg(target_os = "macos")]
+#[cfg(unix)]
 pub(crate) mod unix;
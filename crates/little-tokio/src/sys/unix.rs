@@ -14,5 +14,130 @@
 
 //! This module contains the implementation of UNIX bindings.
 
+#[cfg(target_os = "linux")]
+pub(crate) mod epoll;
 #[cfg(target_os = "macos")]
 pub(crate) mod kqueue;
+pub(crate) mod net;
+
+#[cfg(target_os = "linux")]
+pub(crate) use self::epoll::{Events, Selector};
+#[cfg(target_os = "macos")]
+pub(crate) use self::kqueue::{Events, Selector};
+
+/// Returns `true` if the given `fd` refers to an open file description of this process.
+///
+/// # See also:
+/// [fcntl(2)](https://man7.org/linux/man-pages/man2/fcntl.2.html)
+#[cfg(feature = "trace")]
+pub(crate) fn is_open(fd: std::os::fd::RawFd) -> bool {
+    match syscall!(fcntl(fd, libc::F_GETFD)) {
+        Ok(_) => true,
+        Err(e) => e.raw_os_error() != Some(libc::EBADF),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::interest::Interest;
+    use crate::core::token::Token;
+    use std::io::Write as _;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::{thread, time};
+
+    /// Selects the events ready now, and returns the `Interest`s reported for the given `token`, if any.
+    fn ready(selector: &Selector, token: Token) -> Option<Interest> {
+        let mut events = Events::with_capacity(16);
+        selector
+            .try_select(&mut events, Some(time::Duration::ZERO))
+            .unwrap();
+        events
+            .readiness()
+            .filter(|(selected, _)| *selected == token)
+            .map(|(_, interest)| interest)
+            .reduce(|one, two| one | two)
+    }
+
+    /// Returns a connected pair of non-blocking UNIX sockets.
+    fn pair() -> (UnixStream, UnixStream) {
+        let (one, two) = UnixStream::pair().unwrap();
+        one.set_nonblocking(true).unwrap();
+        two.set_nonblocking(true).unwrap();
+        (one, two)
+    }
+
+    #[test]
+    fn deregistering_readable_keeps_the_writable_events() {
+        let selector = Selector::try_new().unwrap();
+        let (one, mut two) = pair();
+        let token = Token::with_generation(one.as_raw_fd(), 1);
+        let both = Interest::READABLE | Interest::WRITABLE;
+        selector.try_register(one.as_raw_fd(), token, both).unwrap();
+        two.write_all(b"x").unwrap();
+        let interest = ready(&selector, token).unwrap();
+        assert!(interest.is_readable() && interest.is_writable());
+        selector
+            .try_deregister(one.as_raw_fd(), Interest::READABLE)
+            .unwrap();
+        // Note:
+        // Another write makes the socket readable again, which must not be reported anymore, while re-registering
+        // the writable interest re-arms its edge.
+        two.write_all(b"y").unwrap();
+        selector
+            .try_register(one.as_raw_fd(), token, Interest::WRITABLE)
+            .unwrap();
+        let interest = ready(&selector, token).unwrap();
+        assert!(interest.is_writable());
+        assert!(!interest.is_readable());
+    }
+
+    #[test]
+    fn readiness_is_edge_triggered() {
+        let selector = Selector::try_new().unwrap();
+        let (one, mut two) = pair();
+        let token = Token::with_generation(one.as_raw_fd(), 1);
+        selector
+            .try_register(one.as_raw_fd(), token, Interest::READABLE)
+            .unwrap();
+        assert!(ready(&selector, token).is_none());
+        two.write_all(b"x").unwrap();
+        assert!(ready(&selector, token).unwrap().is_readable());
+        // Note:
+        // The readiness is latched until the next edge, i.e., it is not reported again while the byte is left unread.
+        assert!(ready(&selector, token).is_none());
+        two.write_all(b"y").unwrap();
+        assert!(ready(&selector, token).unwrap().is_readable());
+    }
+
+    #[test]
+    fn hang_up_is_reported_to_both_interests() {
+        let selector = Selector::try_new().unwrap();
+        let (one, two) = pair();
+        let token = Token::with_generation(one.as_raw_fd(), 1);
+        selector
+            .try_register(one.as_raw_fd(), token, Interest::READABLE)
+            .unwrap();
+        assert!(ready(&selector, token).is_none());
+        drop(two);
+        let interest = ready(&selector, token).unwrap();
+        assert!(interest.is_readable());
+    }
+
+    #[test]
+    fn try_wake_interrupts_a_select_from_another_thread() {
+        let selector = Selector::try_new().unwrap();
+        let mut events = Events::with_capacity(16);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(time::Duration::from_millis(20));
+                selector.try_wake().unwrap();
+            });
+            selector
+                .try_select(&mut events, Some(time::Duration::from_secs(10)))
+                .unwrap();
+        });
+        assert!(events.readiness().any(|(token, _)| token == Token::WAKE));
+    }
+}
@@ -0,0 +1,570 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of Linux `epoll` bindings.
+//!
+//! The `Selector` offers the same surface as the `kqueue` based one, so that the `Reactor` is independent of the
+//! underlying backend:
+//!  - The file descriptors are registered edge-triggered, which corresponds to `EV_CLEAR`.
+//!  - The `epoll` instance is woken up via an `eventfd`, which corresponds to `EVFILT_USER`.
+//!  - The timers are backed by `timerfd`s registered one-shot, which correspond to `EVFILT_TIMER`.
+//!  - The signals are forwarded by their handlers to `eventfd`s, which correspond to `EVFILT_SIGNAL`.
+
+use crate::core::interest::Interest;
+use crate::core::token::Token;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::atomic::{self, AtomicI32};
+use std::{cmp, collections, default, io, mem, ops, ptr, sync, time};
+
+/// The number of signals which can be forwarded, i.e., the signal numbers have to be less than this.
+const SIGNALS: usize = 65;
+
+/// The initial value of the `SIGNAL_FDS`, which specifies the signal not forwarded.
+#[allow(clippy::declare_interior_mutable_const)]
+const UNFORWARDED: AtomicI32 = AtomicI32::new(-1);
+
+/// Holds the `eventfd`s which the signal handlers forward the signals to, indexed by the signal numbers, or `-1` if
+/// the signal is not forwarded.
+static SIGNAL_FDS: [AtomicI32; SIGNALS] = [UNFORWARDED; SIGNALS];
+
+/// The number of `epoll_event`s `Events` can hold by default, which grows whenever a single turn fills it.
+const EVENTS_CAPACITY: usize = 1024;
+
+/// Represents the number of `epoll_event`s.
+///
+/// # See also:
+/// [epoll_wait(2)](https://man7.org/linux/man-pages/man2/epoll_wait.2.html)
+type Count = libc::c_int;
+
+/// Represents `epoll_event` event flags.
+///
+/// # See also:
+/// [epoll_ctl(2)](https://man7.org/linux/man-pages/man2/epoll_ctl.2.html)
+type Flags = u32;
+
+// Wraps `libc::epoll_event` so that the arguments will be coerced as its FFI defined.
+macro_rules! new_epoll_event {
+    ($flags: expr, $token: expr) => {
+        libc::epoll_event {
+            events: $flags as Flags,
+            u64: $token.to_ptr() as u64,
+        }
+    };
+}
+
+/// Represents the Rust wrapper arround a libc `epoll_event`. This wrapper is essentially equivalent to
+/// `libc::epoll_event`. It implements `Deref` and `DerefMut` to delegate the underlying struct fields.
+///
+/// # See also:
+/// [epoll_ctl(2)](https://man7.org/linux/man-pages/man2/epoll_ctl.2.html)
+pub(crate) struct Event(libc::epoll_event);
+
+impl Event {
+    /// Returns the `Token` the file descriptor generated the event has been registered with.
+    pub(crate) fn token(&self) -> Token {
+        Token::from_ptr(self.0.u64 as _)
+    }
+
+    /// Returns `true` if the `epoll_event` representing there is data available to read.
+    pub(crate) fn is_readable(&self) -> bool {
+        self.flags() & (libc::EPOLLIN | libc::EPOLLPRI) as Flags != 0
+    }
+
+    /// Returns `true` if the `epoll_event` representing it is possible to write to the associated file
+    /// descriptor.
+    pub(crate) fn is_writable(&self) -> bool {
+        self.flags() & libc::EPOLLOUT as Flags != 0
+    }
+
+    /// Returns `true` if an error has occurred on the associated file descriptor.
+    pub(crate) fn is_error(&self) -> bool {
+        self.flags() & libc::EPOLLERR as Flags != 0
+    }
+
+    /// Returns `true` if the peer has shut down the writing half of the connection, or the associated file
+    /// descriptor has been hung up.
+    pub(crate) fn is_read_closed(&self) -> bool {
+        self.flags() & libc::EPOLLHUP as Flags != 0
+            || self.flags() & (libc::EPOLLIN | libc::EPOLLRDHUP) as Flags
+                == (libc::EPOLLIN | libc::EPOLLRDHUP) as Flags
+    }
+
+    /// Returns `true` if the associated file descriptor has been hung up, or the writing half is broken.
+    pub(crate) fn is_write_closed(&self) -> bool {
+        self.flags() & libc::EPOLLHUP as Flags != 0
+            || self.flags() & (libc::EPOLLOUT | libc::EPOLLERR) as Flags
+                == (libc::EPOLLOUT | libc::EPOLLERR) as Flags
+    }
+
+    /// Returns the event flags of the `epoll_event`.
+    fn flags(&self) -> Flags {
+        // Note:
+        // We can't use references to packed structures, so we need copy the data out before use.
+        self.0.events
+    }
+}
+
+impl ops::Deref for Event {
+    type Target = libc::epoll_event;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ops::DerefMut for Event {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl default::Default for Event {
+    fn default() -> Self {
+        Self(libc::epoll_event { events: 0, u64: 0 })
+    }
+}
+
+/// Represents the Rust wrapper around a libc `epoll_event`. This wrapper is essentially equivalent to
+/// Rust's `Vec` and consists of `epoll_event` elements. It implements `Deref` and `DerefMut` to delegate
+/// the underlying `Vec` methods.
+///
+/// # See also:
+/// [epoll_wait(2)](https://man7.org/linux/man-pages/man2/epoll_wait.2.html)
+pub(crate) struct Events(Vec<libc::epoll_event>);
+
+impl Events {
+    /// Creates `Events` with a given `capacity`.
+    pub(crate) fn with_capacity(capacity: usize) -> Events {
+        Events(Vec::with_capacity(capacity))
+    }
+
+    /// Returns the `Token`s of the selected events together with the `Interest`s they are ready for, in the order
+    /// they have been selected. An error or a hang-up is reported as ready for both of the `Interest`s, so that
+    /// both of the readers and the writers observe it.
+    pub(crate) fn readiness(&self) -> impl Iterator<Item = (Token, Interest)> + '_ {
+        self.0.iter().map(|event| {
+            let event = Event(*event);
+            let interest = match (event.is_readable(), event.is_writable()) {
+                _ if event.is_error() || event.is_read_closed() || event.is_write_closed() => {
+                    Interest::READABLE | Interest::WRITABLE
+                }
+                (true, true) => Interest::READABLE | Interest::WRITABLE,
+                (false, true) => Interest::WRITABLE,
+                _ => Interest::READABLE,
+            };
+            (event.token(), interest)
+        })
+    }
+}
+
+impl ops::Deref for Events {
+    type Target = Vec<libc::epoll_event>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ops::DerefMut for Events {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl default::Default for Events {
+    fn default() -> Self {
+        Self::with_capacity(EVENTS_CAPACITY)
+    }
+}
+
+/// Returns the `epoll_event` flags corresponding to the given `interest`. The file descriptors are always
+/// registered edge-triggered, so that the readiness is reported only once until it gets consumed.
+fn interest_to_flags(interest: Interest) -> Flags {
+    let mut flags = libc::EPOLLET | libc::EPOLLRDHUP;
+    if interest.is_readable() {
+        flags |= libc::EPOLLIN;
+    }
+    if interest.is_writable() {
+        flags |= libc::EPOLLOUT;
+    }
+    flags as Flags
+}
+
+/// Converts the given `duration` into the `libc::timespec`.
+fn to_timespec(duration: time::Duration) -> libc::timespec {
+    libc::timespec {
+        tv_sec: cmp::min(duration.as_secs(), libc::time_t::MAX as u64) as libc::time_t,
+        // Note:
+        // `Duration::subsec_nanos` is guaranteed to be less than one billion (the number of
+        // nanoseconds in a second), making the cast to `i32` safe. The cast itself is needed for
+        // platforms where C's long is only 32 bits.
+        tv_nsec: libc::c_long::from(duration.subsec_nanos() as i32),
+    }
+}
+
+/// The Linux `epoll` based IO Mux/Demux.
+pub(crate) struct Selector {
+    /// Holds the `epoll` file descriptor.
+    pub(crate) ep: RawFd,
+    /// Holds the `eventfd` which is utilized to wake up the `epoll` from another thread.
+    wake_fd: OwnedFd,
+    /// Holds the correspondence between registered file descriptors and their `Interest`s and `Token`s, since
+    /// `epoll` monitors a file descriptor with a single registration whereas `kqueue` does per filter.
+    registered: sync::Mutex<collections::HashMap<RawFd, (Interest, Token)>>,
+    /// Holds the correspondence between pending timers' tokens and their `timerfd`s.
+    timers: sync::Mutex<collections::HashMap<Token, OwnedFd>>,
+    /// Holds the correspondence between forwarded signals' numbers and their `eventfd`s.
+    signals: sync::Mutex<collections::HashMap<libc::c_int, OwnedFd>>,
+}
+
+impl Selector {
+    /// Tries to create the `epoll` based IO Mux/Demux.
+    pub(crate) fn try_new() -> io::Result<Self> {
+        let wake_fd = syscall!(eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK))?;
+        // Safety:
+        // This is safe because `eventfd` has returned a valid file descriptor owned by no one else.
+        let wake_fd = unsafe { OwnedFd::from_raw_fd(wake_fd) };
+        let ep = syscall!(epoll_create1(libc::EPOLL_CLOEXEC))?;
+        let selector = Self {
+            ep,
+            wake_fd,
+            registered: sync::Mutex::default(),
+            timers: sync::Mutex::default(),
+            signals: sync::Mutex::default(),
+        };
+        let mut event = new_epoll_event!(libc::EPOLLIN | libc::EPOLLET, Token::WAKE);
+        syscall!(epoll_ctl(
+            ep,
+            libc::EPOLL_CTL_ADD,
+            selector.wake_fd.as_raw_fd(),
+            &mut event
+        ))?;
+        Ok(selector)
+    }
+
+    /// Tries to select/mux ready `epoll_event`s into `eventlist` with a maximal interval `timeout` to wait for an
+    /// event.
+    ///
+    /// # See also:
+    /// [epoll_wait(2)](https://man7.org/linux/man-pages/man2/epoll_wait.2.html)
+    pub(crate) fn try_select(
+        &self,
+        eventlist: &mut Events,
+        timeout: Option<time::Duration>,
+    ) -> io::Result<()> {
+        // Note:
+        // The `timeout` is rounded up to milliseconds, so that a sub-millisecond timeout does not turn into a busy
+        // loop polling without waiting.
+        let timeout = timeout
+            .map(|to| {
+                let millis = to.as_nanos().div_ceil(1_000_000);
+                cmp::min(millis, libc::c_int::MAX as u128) as libc::c_int
+            })
+            .unwrap_or(-1);
+        eventlist.clear();
+        let nevents = syscall!(epoll_wait(
+            self.ep,
+            eventlist.as_mut_ptr(),
+            eventlist.capacity() as Count,
+            timeout,
+        ))?;
+        // Safety:
+        // This is safe because `epoll_wait` ensures that `nevents` are assigned.
+        unsafe { eventlist.set_len(nevents as usize) };
+        // Note:
+        // A full `eventlist` may leave the other ready events unreported until the next turn, hence it grows and
+        // the events still ready get selected without waiting. Since an error of this additional selection would
+        // lose the events already selected, it just stops growing, leaving the error to the next turn.
+        while !eventlist.is_empty() && eventlist.len() == eventlist.capacity() {
+            let selected = eventlist.len();
+            eventlist.reserve(selected);
+            let spare = eventlist.spare_capacity_mut();
+            let Ok(nevents) = syscall!(epoll_wait(
+                self.ep,
+                spare.as_mut_ptr() as *mut libc::epoll_event,
+                spare.len() as Count,
+                0,
+            )) else {
+                break;
+            };
+            // Safety:
+            // This is safe because `epoll_wait` ensures that `nevents` following the `selected` are assigned.
+            unsafe { eventlist.set_len(selected + nevents as usize) };
+        }
+        Ok(())
+    }
+
+    /// Tries to register the given `fd` into `epoll` to monitor, adding the given `interest` to the `Interest` the
+    /// `fd` has been registered with so far. An empty `interest` registers nothing.
+    ///
+    /// # See also:
+    /// [epoll_ctl(2)](https://man7.org/linux/man-pages/man2/epoll_ctl.2.html)
+    pub(crate) fn try_register(
+        &self,
+        fd: RawFd,
+        token: Token,
+        interest: Interest,
+    ) -> io::Result<()> {
+        if interest.is_empty() {
+            return Ok(());
+        }
+        let mut registered = self.lock_registered();
+        let added = match registered.get(&fd) {
+            Some((current, _)) => {
+                let mut event = new_epoll_event!(interest_to_flags(*current | interest), token);
+                match syscall!(epoll_ctl(self.ep, libc::EPOLL_CTL_MOD, fd, &mut event)) {
+                    Ok(_) => *current | interest,
+                    // Note:
+                    // The ENOENT error informs us that the file descriptor has been closed and its number has been
+                    // reused since the last registration, so that the file descriptor is registered from scratch.
+                    Err(e) if e.raw_os_error() == Some(libc::ENOENT) => {
+                        let mut event = new_epoll_event!(interest_to_flags(interest), token);
+                        syscall!(epoll_ctl(self.ep, libc::EPOLL_CTL_ADD, fd, &mut event))?;
+                        interest
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            None => {
+                let mut event = new_epoll_event!(interest_to_flags(interest), token);
+                syscall!(epoll_ctl(self.ep, libc::EPOLL_CTL_ADD, fd, &mut event))?;
+                interest
+            }
+        };
+        registered.insert(fd, (added, token));
+        Ok(())
+    }
+
+    /// Tries to wake up the `epoll` blocked in `try_select`, which may be called from any thread. The event is
+    /// delivered with `Token::WAKE`.
+    ///
+    /// # See also:
+    /// [eventfd(2)](https://man7.org/linux/man-pages/man2/eventfd.2.html)
+    pub(crate) fn try_wake(&self) -> io::Result<()> {
+        let buffer = 1u64.to_ne_bytes();
+        match syscall!(write(
+            self.wake_fd.as_raw_fd(),
+            buffer.as_ptr() as *const libc::c_void,
+            buffer.len()
+        )) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                // Note:
+                // The counter of the `eventfd` is never read since the edge-triggered registration reports every
+                // write, so that the counter is reset here once it gets saturated.
+                let mut buffer = [0u8; 8];
+                syscall!(read(
+                    self.wake_fd.as_raw_fd(),
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len()
+                ))?;
+                self.try_wake()
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Tries to register a one-shot timer into `epoll`, which fires once the given `duration` elapses. The event is
+    /// delivered with the given `token`, which also identifies the timer itself.
+    ///
+    /// # See also:
+    /// [timerfd_create(2)](https://man7.org/linux/man-pages/man2/timerfd_create.2.html)
+    pub(crate) fn try_register_timer(
+        &self,
+        token: Token,
+        duration: time::Duration,
+    ) -> io::Result<()> {
+        let fd = syscall!(timerfd_create(
+            libc::CLOCK_MONOTONIC,
+            libc::TFD_CLOEXEC | libc::TFD_NONBLOCK
+        ))?;
+        // Safety:
+        // This is safe because `timerfd_create` has returned a valid file descriptor owned by no one else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        // Note:
+        // The zero `it_value` disarms the timer, so that the shortest delay is substituted for it.
+        let spec = libc::itimerspec {
+            it_interval: to_timespec(time::Duration::ZERO),
+            it_value: to_timespec(cmp::max(duration, time::Duration::from_nanos(1))),
+        };
+        syscall!(timerfd_settime(fd.as_raw_fd(), 0, &spec, ptr::null_mut()))?;
+        let mut event = new_epoll_event!(libc::EPOLLIN | libc::EPOLLONESHOT, token);
+        syscall!(epoll_ctl(
+            self.ep,
+            libc::EPOLL_CTL_ADD,
+            fd.as_raw_fd(),
+            &mut event
+        ))?;
+        self.lock_timers().insert(token, fd);
+        Ok(())
+    }
+
+    /// Tries to deregister the timer identified by the given `token` from `epoll`. Closing the `timerfd` removes
+    /// it from the `epoll` interest list as well.
+    ///
+    /// # See also:
+    /// [timerfd_create(2)](https://man7.org/linux/man-pages/man2/timerfd_create.2.html)
+    pub(crate) fn try_deregister_timer(&self, token: Token) -> io::Result<()> {
+        let fd = self.lock_timers().remove(&token);
+        drop(fd);
+        Ok(())
+    }
+
+    /// Tries to register the signal identified by the given `signum` into `epoll`, delivering the event with the
+    /// given `token` whenever the signal arrives. The signal handler installed here forwards the signal to an
+    /// `eventfd`, i.e., the default disposition of the signal, e.g., terminating the process, is replaced. The
+    /// registration is kept until the `Selector` gets dropped, and registering the same signal again does nothing.
+    ///
+    /// # See also:
+    /// [sigaction(2)](https://man7.org/linux/man-pages/man2/sigaction.2.html)
+    pub(crate) fn try_register_signal(&self, signum: libc::c_int, token: Token) -> io::Result<()> {
+        let Some(slot) = usize::try_from(signum).ok().and_then(|i| SIGNAL_FDS.get(i)) else {
+            return Err(io::Error::from_raw_os_error(libc::EINVAL));
+        };
+        let mut signals = self.lock_signals();
+        if signals.contains_key(&signum) {
+            return Ok(());
+        }
+        let fd = syscall!(eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK))?;
+        // Safety:
+        // This is safe because `eventfd` has returned a valid file descriptor owned by no one else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mut event = new_epoll_event!(libc::EPOLLIN | libc::EPOLLET, token);
+        syscall!(epoll_ctl(
+            self.ep,
+            libc::EPOLL_CTL_ADD,
+            fd.as_raw_fd(),
+            &mut event
+        ))?;
+        slot.store(fd.as_raw_fd(), atomic::Ordering::Release);
+        // Safety:
+        // This is safe because the `sigaction` is fully initialized, and the handler only performs the
+        // async-signal-safe operations.
+        let mut action: libc::sigaction = unsafe { mem::zeroed() };
+        action.sa_sigaction = forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        syscall!(sigemptyset(&mut action.sa_mask))?;
+        if let Err(e) = syscall!(sigaction(signum, &action, ptr::null_mut())) {
+            slot.store(-1, atomic::Ordering::Release);
+            return Err(e);
+        }
+        signals.insert(signum, fd);
+        Ok(())
+    }
+
+    /// Tries to deregister the given `fd` from `epoll` to monitor the IO events specified by the `interest`. The
+    /// `fd` is removed from the interest list once no `Interest` is left, and an empty `interest` deregisters
+    /// nothing.
+    ///
+    /// # See also:
+    /// [epoll_ctl(2)](https://man7.org/linux/man-pages/man2/epoll_ctl.2.html)
+    pub(crate) fn try_deregister(&self, fd: RawFd, interest: Interest) -> io::Result<()> {
+        if interest.is_empty() {
+            return Ok(());
+        }
+        let mut registered = self.lock_registered();
+        let Some((current, token)) = registered.get(&fd).copied() else {
+            return Ok(());
+        };
+        let remaining = current.remove(interest);
+        let ret = if remaining.is_empty() {
+            registered.remove(&fd);
+            syscall!(epoll_ctl(self.ep, libc::EPOLL_CTL_DEL, fd, ptr::null_mut()))
+        } else {
+            let mut event = new_epoll_event!(interest_to_flags(remaining), token);
+            registered.insert(fd, (remaining, token));
+            syscall!(epoll_ctl(self.ep, libc::EPOLL_CTL_MOD, fd, &mut event))
+        };
+        // Note:
+        // The ENOENT error informs us that the file descriptor wasn't there in first place, e.g., it has been
+        // closed already, but we don't really care about that since our goal is to remove it.
+        match ret {
+            Err(e)
+                if e.raw_os_error() == Some(libc::ENOENT)
+                    || e.raw_os_error() == Some(libc::EBADF) =>
+            {
+                Ok(())
+            }
+            ret => ret.map(|_| ()),
+        }
+    }
+
+    /// Returns the `MutexGuard` of the registered file descriptors.
+    fn lock_registered(
+        &self,
+    ) -> sync::MutexGuard<'_, collections::HashMap<RawFd, (Interest, Token)>> {
+        self.registered
+            .lock()
+            .expect("`MutexGuard` of the registered file descriptors should be locked properly")
+    }
+
+    /// Returns the `MutexGuard` of the forwarded signals.
+    fn lock_signals(&self) -> sync::MutexGuard<'_, collections::HashMap<libc::c_int, OwnedFd>> {
+        self.signals
+            .lock()
+            .expect("`MutexGuard` of the forwarded signals should be locked properly")
+    }
+
+    /// Returns the `MutexGuard` of the pending timers.
+    fn lock_timers(&self) -> sync::MutexGuard<'_, collections::HashMap<Token, OwnedFd>> {
+        self.timers
+            .lock()
+            .expect("`MutexGuard` of the pending timers should be locked properly")
+    }
+}
+
+impl default::Default for Selector {
+    fn default() -> Self {
+        Self::try_new().expect("should instanciate epoll properly")
+    }
+}
+
+impl Drop for Selector {
+    fn drop(&mut self) {
+        // Note:
+        // The signals keep being handled but are no longer forwarded, so that the handlers never write into the
+        // `eventfd`s closed here, or into the file descriptors reusing the same numbers.
+        for signum in self.lock_signals().keys() {
+            SIGNAL_FDS[*signum as usize].store(-1, atomic::Ordering::Release);
+        }
+        match syscall!(close(self.ep)) {
+            Ok(..) => (),
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// Forwards the signal identified by the given `signum` to its `eventfd`, which is the signal handler installed by
+/// `Selector::try_register_signal`. Only the async-signal-safe operations are allowed here.
+extern "C" fn forward_signal(signum: libc::c_int) {
+    let fd = SIGNAL_FDS[signum as usize].load(atomic::Ordering::Acquire);
+    if fd < 0 {
+        return;
+    }
+    let value: u64 = 1;
+    // Note:
+    // The write fails only when the counter of the `eventfd` would overflow, in which case the signal has been
+    // forwarded already. The `errno` is restored since the handler may interrupt any code inspecting it.
+    // Safety:
+    // This is safe because `write` is async-signal-safe and the `value` outlives the call.
+    unsafe {
+        let errno = *libc::__errno_location();
+        libc::write(
+            fd,
+            &value as *const u64 as *const libc::c_void,
+            mem::size_of::<u64>(),
+        );
+        *libc::__errno_location() = errno;
+    }
+}
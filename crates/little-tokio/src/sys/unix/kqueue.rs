@@ -16,7 +16,7 @@
 
 use crate::core::interest::Interest;
 use crate::core::token::Token;
-use std::{cmp, default, io, mem, ops, os, ptr, slice, time};
+use std::{cmp, default, io, marker, mem, ops, os, ptr, slice, time};
 
 /// Represents raw OS error codes returned by system calls.
 type RawOsError = i32;
@@ -27,6 +27,12 @@ type RawOsError = i32;
 /// [kevent(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kevent.2.html)
 type Id = libc::uintptr_t;
 
+/// The identifier of the `EVFILT_USER` event which is utilized to wake up the `kqueue` from another thread.
+const WAKE_ID: Id = 0;
+
+/// The number of `kevent`s `Events` can hold by default, which grows whenever a single turn fills it.
+const EVENTS_CAPACITY: usize = 1024;
+
 /// Represents the number of `kevent`s.
 ///
 /// # See also:
@@ -84,27 +90,23 @@ impl Event {
 
     /// Returns `true` if the `kevent` representing it is possible to write to the associated file
     /// descriptor.
-    #[allow(dead_code)]
     pub(crate) fn is_writable(&self) -> bool {
         self.filter == libc::EVFILT_WRITE
     }
 
     /// Returns `true` if an error occurs while processing an element of the `changes`.
-    #[allow(dead_code)]
     pub(crate) fn is_error(&self) -> bool {
         (self.flags & libc::EV_ERROR) != 0 || (self.flags & libc::EV_EOF) != 0 && self.fflags != 0
     }
 
     /// Returns `true` if the `kevent` is waiting for a reading event and the associated data is closed
     /// before it reaches to the EOF.
-    #[allow(dead_code)]
     pub(crate) fn is_read_closed(&self) -> bool {
         self.filter == libc::EVFILT_READ && self.flags & libc::EV_EOF != 0
     }
 
     /// Returns `true` if the `kevent` is waiting for a writing event and the associated data is closed
     /// before it reaches to the EOF.
-    #[allow(dead_code)]
     pub(crate) fn is_write_closed(&self) -> bool {
         self.filter == libc::EVFILT_WRITE && self.flags & libc::EV_EOF != 0
     }
@@ -140,12 +142,34 @@ pub(crate) struct Events(Vec<libc::kevent>);
 
 impl Events {
     /// Creates `Events` with a given `capacity`.
-    #[allow(dead_code)]
     pub(crate) fn with_capacity(capacity: usize) -> Events {
         Events(Vec::with_capacity(capacity))
     }
+
+    /// Returns the `Token`s of the selected events together with the `Interest`s they are ready for, in the order
+    /// they have been selected. An error or an EOF, e.g., the peer closes the connection without sending anything,
+    /// is reported as ready for both of the `Interest`s, so that both of the readers and the writers observe it.
+    pub(crate) fn readiness(&self) -> impl Iterator<Item = (Token, Interest)> + '_ {
+        self.0.iter().map(|event| {
+            let event = Event(*event);
+            let interest = if event.is_error() || event.is_read_closed() || event.is_write_closed()
+            {
+                Interest::READABLE | Interest::WRITABLE
+            } else if event.is_writable() {
+                Interest::WRITABLE
+            } else {
+                Interest::READABLE
+            };
+            (Token::from_ptr(event.udata as _), interest)
+        })
+    }
 }
 
+// Safety:
+// The `udata` pointers carried by the `kevent`s are `Token`s rather than pointers to anything, i.e., they are never
+// dereferenced, so that the buffer kept by the `Reactor` singleton can be moved across threads.
+unsafe impl marker::Send for Events {}
+
 impl ops::Deref for Events {
     type Target = Vec<libc::kevent>;
 
@@ -162,7 +186,7 @@ impl ops::DerefMut for Events {
 
 impl default::Default for Events {
     fn default() -> Self {
-        Self(vec![*Event::default()])
+        Self::with_capacity(EVENTS_CAPACITY)
     }
 }
 
@@ -228,6 +252,14 @@ impl Selector {
         let kq = syscall!(kqueue())?;
         let selector = Self { kq };
         syscall!(fcntl(kq, libc::F_SETFD, libc::FD_CLOEXEC))?;
+        let flags = libc::EV_ADD | libc::EV_CLEAR | libc::EV_RECEIPT;
+        let mut changelist = [new_kevent!(
+            WAKE_ID,
+            libc::EVFILT_USER,
+            flags,
+            Token::WAKE.to_ptr()
+        )];
+        register_kevents(kq, &mut changelist, &[])?;
         Ok(selector)
     }
 
@@ -253,22 +285,48 @@ impl Selector {
             .map(|s| s as *const _)
             .unwrap_or(ptr::null_mut());
         eventlist.clear();
-        syscall!(kevent(
+        let nevents = syscall!(kevent(
             self.kq,
             ptr::null(),
             0,
             eventlist.as_mut_ptr(),
             eventlist.capacity() as Count,
             timeout,
-        ))
-        .map(|nevents| {
+        ))?;
+        // Safety:
+        // This is safe because `kevent` ensures that `nevents` are assigned.
+        unsafe { eventlist.set_len(nevents as usize) };
+        // Note:
+        // A full `eventlist` may leave the other ready events unreported until the next turn, hence it grows and
+        // the events still ready get selected without waiting. Since an error of this additional selection would
+        // lose the events already selected, it just stops growing, leaving the error to the next turn.
+        let zero = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        while !eventlist.is_empty() && eventlist.len() == eventlist.capacity() {
+            let selected = eventlist.len();
+            eventlist.reserve(selected);
+            let spare = eventlist.spare_capacity_mut();
+            let Ok(nevents) = syscall!(kevent(
+                self.kq,
+                ptr::null(),
+                0,
+                spare.as_mut_ptr() as *mut libc::kevent,
+                spare.len() as Count,
+                &zero,
+            )) else {
+                break;
+            };
             // Safety:
-            // This is safe because `kevent` ensures that `nevents` are assigned.
-            unsafe { eventlist.set_len(nevents as usize) };
-        })
+            // This is safe because `kevent` ensures that `nevents` following the `selected` are assigned.
+            unsafe { eventlist.set_len(selected + nevents as usize) };
+        }
+        Ok(())
     }
 
-    /// Tries to register the given `fd` into `kqueue` to monitor.
+    /// Tries to register the given `fd` into `kqueue` to monitor. An empty `interest` registers nothing, i.e., no
+    /// `kevent` gets submitted.
     ///
     /// # See also:
     /// [kevent(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kevent.2.html)
@@ -278,6 +336,9 @@ impl Selector {
         token: Token,
         interest: Interest,
     ) -> io::Result<()> {
+        if interest.is_empty() {
+            return Ok(());
+        }
         let flags = libc::EV_CLEAR | libc::EV_RECEIPT | libc::EV_ADD;
         let mut changelist: [mem::MaybeUninit<libc::kevent>; 2] =
             [mem::MaybeUninit::uninit(), mem::MaybeUninit::uninit()];
@@ -298,20 +359,91 @@ impl Selector {
         register_kevents(self.kq, changelist, &[libc::EPIPE as RawOsError])
     }
 
-    /// Tries to deregister the given `fd` from `kqueue` to monitor.
+    /// Tries to register a one-shot timer into `kqueue`, which fires once the given `duration` elapses. The event is
+    /// delivered with the given `token`, which also identifies the timer itself.
+    ///
+    /// # See also:
+    /// [kevent(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kevent.2.html)
+    pub(crate) fn try_register_timer(
+        &self,
+        token: Token,
+        duration: time::Duration,
+    ) -> io::Result<()> {
+        let flags = libc::EV_ADD | libc::EV_ONESHOT | libc::EV_RECEIPT;
+        let mut kevent = new_kevent!(i64::from(token), libc::EVFILT_TIMER, flags, token.to_ptr());
+        kevent.fflags = libc::NOTE_NSECONDS;
+        kevent.data = cmp::min(duration.as_nanos(), libc::intptr_t::MAX as u128) as libc::intptr_t;
+        register_kevents(self.kq, &mut [kevent], &[])
+    }
+
+    /// Tries to deregister the timer identified by the given `token` from `kqueue`.
     ///
     /// # See also:
     /// [kevent(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kevent.2.html)
-    pub(crate) fn try_deregister(&self, fd: os::fd::RawFd) -> io::Result<()> {
+    pub(crate) fn try_deregister_timer(&self, token: Token) -> io::Result<()> {
         let flags = libc::EV_DELETE | libc::EV_RECEIPT;
-        let mut changelist: [libc::kevent; 2] = [
-            new_kevent!(fd, libc::EVFILT_WRITE, flags, 0),
-            new_kevent!(fd, libc::EVFILT_READ, flags, 0),
-        ];
+        let kevent = new_kevent!(i64::from(token), libc::EVFILT_TIMER, flags, 0);
+        // Note:
+        // A one-shot timer is deleted by the kernel once it fires, so that the ENOENT error is ignored here.
+        register_kevents(self.kq, &mut [kevent], &[libc::ENOENT as RawOsError])
+    }
+
+    /// Tries to register the signal identified by the given `signum` into `kqueue`, delivering the event with the
+    /// given `token` whenever the signal arrives. Since `EVFILT_SIGNAL` records the signal even if it is ignored, the
+    /// signal is set to be ignored, i.e., the default disposition of the signal, e.g., terminating the process, is
+    /// replaced. The registration is kept until the `Selector` gets dropped.
+    ///
+    /// # See also:
+    /// [kevent(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kevent.2.html)
+    pub(crate) fn try_register_signal(&self, signum: libc::c_int, token: Token) -> io::Result<()> {
+        // Safety:
+        // This is safe because `SIG_IGN` installs no handler.
+        if unsafe { libc::signal(signum, libc::SIG_IGN) } == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+        let flags = libc::EV_ADD | libc::EV_CLEAR | libc::EV_RECEIPT;
+        let kevent = new_kevent!(signum, libc::EVFILT_SIGNAL, flags, token.to_ptr());
+        register_kevents(self.kq, &mut [kevent], &[])
+    }
+
+    /// Tries to wake up the `kqueue` blocked in `try_select`, which may be called from any thread. The event is
+    /// delivered with `Token::WAKE`.
+    ///
+    /// # See also:
+    /// [kevent(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kevent.2.html)
+    pub(crate) fn try_wake(&self) -> io::Result<()> {
+        let flags = libc::EV_ADD | libc::EV_RECEIPT;
+        let mut kevent = new_kevent!(WAKE_ID, libc::EVFILT_USER, flags, Token::WAKE.to_ptr());
+        kevent.fflags = libc::NOTE_TRIGGER;
+        register_kevents(self.kq, &mut [kevent], &[])
+    }
+
+    /// Tries to deregister the given `fd` from `kqueue` to monitor the IO events specified by the `interest`.
+    ///
+    /// # See also:
+    /// [kevent(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kevent.2.html)
+    pub(crate) fn try_deregister(&self, fd: os::fd::RawFd, interest: Interest) -> io::Result<()> {
+        let flags = libc::EV_DELETE | libc::EV_RECEIPT;
+        let mut changelist: [mem::MaybeUninit<libc::kevent>; 2] =
+            [mem::MaybeUninit::uninit(), mem::MaybeUninit::uninit()];
+        let mut nchanges = 0;
+        if interest.is_writable() {
+            let kevent = new_kevent!(fd, libc::EVFILT_WRITE, flags, 0);
+            changelist[nchanges] = mem::MaybeUninit::new(kevent);
+            nchanges += 1;
+        }
+        if interest.is_readable() {
+            let kevent = new_kevent!(fd, libc::EVFILT_READ, flags, 0);
+            changelist[nchanges] = mem::MaybeUninit::new(kevent);
+            nchanges += 1;
+        }
+        // Safety:
+        // This is safe because we ensure that at least `nchanges` are in the array.
+        let changelist = unsafe { slice::from_raw_parts_mut(changelist[0].as_mut_ptr(), nchanges) };
         // Note:
         // the ENOENT error when it comes up. The ENOENT error informs us that the filter wasn't
         // there in first place, but we don't really care about that since our goal is to remove it.
-        register_kevents(self.kq, &mut changelist, &[libc::ENOENT as RawOsError])
+        register_kevents(self.kq, changelist, &[libc::ENOENT as RawOsError])
     }
 }
 
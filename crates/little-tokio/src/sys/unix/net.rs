@@ -0,0 +1,321 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of UNIX socket bindings.
+
+use std::{io, mem, net, os};
+
+/// The socket option which specifies the linger on close in seconds. The `SO_LINGER` of macOS is measured in clock
+/// ticks, so that `SO_LINGER_SEC` is used instead.
+#[cfg(target_os = "macos")]
+pub(crate) const SO_LINGER: libc::c_int = libc::SO_LINGER_SEC;
+
+/// The socket option which specifies the linger on close in seconds.
+#[cfg(not(target_os = "macos"))]
+pub(crate) const SO_LINGER: libc::c_int = libc::SO_LINGER;
+
+/// Accepts a new incoming connection from the given `listener`. The accepted socket is non-blocking
+/// from its creation since `accept4(2)` is called with `SOCK_NONBLOCK`, i.e., no extra `fcntl(2)` call
+/// is needed.
+///
+/// # See also:
+/// [accept4(2)](https://man7.org/linux/man-pages/man2/accept.2.html)
+#[cfg(any(target_os = "android", target_os = "freebsd", target_os = "linux"))]
+pub(crate) fn accept(listener: &net::TcpListener) -> io::Result<(net::TcpStream, net::SocketAddr)> {
+    use std::os::fd::{AsRawFd as _, FromRawFd as _};
+    // Safety:
+    // `sockaddr_storage` is a plain C struct, so that it is safe to fill out with zeros.
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut length = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let fd = syscall!(accept4(
+        listener.as_raw_fd(),
+        &mut storage as *mut _ as *mut libc::sockaddr,
+        &mut length,
+        libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+    ))?;
+    // Safety:
+    // This is safe because `accept4` ensures that `fd` is a newly created socket owned by nobody else.
+    let stream = unsafe { net::TcpStream::from_raw_fd(fd) };
+    Ok((stream, to_socket_addr(&storage)?))
+}
+
+/// Accepts a new incoming connection from the given `listener` and sets it non-blocking mode. The platform
+/// lacks `accept4(2)`, so that the accepted socket is briefly in blocking mode until `fcntl(2)` is called.
+///
+/// # See also:
+/// [accept(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/accept.2.html)
+#[cfg(not(any(target_os = "android", target_os = "freebsd", target_os = "linux")))]
+pub(crate) fn accept(listener: &net::TcpListener) -> io::Result<(net::TcpStream, net::SocketAddr)> {
+    let (stream, addr) = listener.accept()?;
+    stream.set_nonblocking(true)?;
+    Ok((stream, addr))
+}
+
+/// Creates a new non-blocking socket and initiates a connection to the given `addr`. Since the socket is
+/// non-blocking, the connection is typically still in progress when this function returns, i.e., the caller needs
+/// to wait for the socket to become writable and check `SO_ERROR` to know the result.
+///
+/// # See also:
+/// [connect(2)](https://man7.org/linux/man-pages/man2/connect.2.html)
+pub(crate) fn connect(addr: &net::SocketAddr) -> io::Result<net::TcpStream> {
+    use std::os::fd::AsRawFd as _;
+    let domain = match addr {
+        net::SocketAddr::V4(..) => libc::AF_INET,
+        net::SocketAddr::V6(..) => libc::AF_INET6,
+    };
+    let socket = new_socket(domain)?;
+    let (storage, length) = from_socket_addr(addr);
+    match syscall!(connect(
+        socket.as_raw_fd(),
+        &storage as *const _ as *const libc::sockaddr,
+        length,
+    )) {
+        Ok(_) => {}
+        Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {}
+        Err(e) => return Err(e),
+    }
+    Ok(net::TcpStream::from(socket))
+}
+
+/// Creates a new non-blocking stream socket of the given `domain`, which is closed on `exec(2)`.
+///
+/// # See also:
+/// [socket(2)](https://man7.org/linux/man-pages/man2/socket.2.html)
+#[cfg(any(target_os = "android", target_os = "freebsd", target_os = "linux"))]
+fn new_socket(domain: libc::c_int) -> io::Result<os::fd::OwnedFd> {
+    use std::os::fd::FromRawFd as _;
+    let fd = syscall!(socket(
+        domain,
+        libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+        0,
+    ))?;
+    // Safety:
+    // This is safe because `socket` ensures that `fd` is a newly created socket owned by nobody else.
+    Ok(unsafe { os::fd::OwnedFd::from_raw_fd(fd) })
+}
+
+/// Creates a new non-blocking stream socket of the given `domain`, which is closed on `exec(2)`. The platform
+/// lacks `SOCK_NONBLOCK` and `SOCK_CLOEXEC`, so that the flags are set via `fcntl(2)` afterwards.
+///
+/// # See also:
+/// [socket(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/socket.2.html)
+#[cfg(not(any(target_os = "android", target_os = "freebsd", target_os = "linux")))]
+fn new_socket(domain: libc::c_int) -> io::Result<os::fd::OwnedFd> {
+    use std::os::fd::{AsRawFd as _, FromRawFd as _};
+    let fd = syscall!(socket(domain, libc::SOCK_STREAM, 0))?;
+    // Safety:
+    // This is safe because `socket` ensures that `fd` is a newly created socket owned by nobody else. Owning it
+    // right away closes it when setting the flags below fails.
+    let socket = unsafe { os::fd::OwnedFd::from_raw_fd(fd) };
+    let flags = syscall!(fcntl(socket.as_raw_fd(), libc::F_GETFL))?;
+    syscall!(fcntl(
+        socket.as_raw_fd(),
+        libc::F_SETFL,
+        flags | libc::O_NONBLOCK
+    ))?;
+    syscall!(fcntl(socket.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC))?;
+    Ok(socket)
+}
+
+/// Returns the number of bytes in the send buffer of the given `fd` which have not been acknowledged by the peer
+/// yet.
+///
+/// # See also:
+/// [getsockopt(2)](https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/getsockopt.2.html)
+#[cfg(target_os = "macos")]
+pub(crate) fn unsent(fd: os::fd::RawFd) -> io::Result<usize> {
+    // Safety:
+    // `SO_NWRITE` is a `c_int` valued option.
+    let unsent: libc::c_int = unsafe { getsockopt(fd, libc::SOL_SOCKET, libc::SO_NWRITE)? };
+    Ok(unsent as usize)
+}
+
+/// Returns the number of bytes in the send buffer of the given `fd` which have not been acknowledged by the peer
+/// yet.
+///
+/// # See also:
+/// [tcp(7)](https://man7.org/linux/man-pages/man7/tcp.7.html)
+#[cfg(all(
+    not(target_os = "macos"),
+    any(target_os = "android", target_os = "linux")
+))]
+pub(crate) fn unsent(fd: os::fd::RawFd) -> io::Result<usize> {
+    let mut unsent: libc::c_int = 0;
+    syscall!(ioctl(fd, libc::TIOCOUTQ, &mut unsent))?;
+    Ok(unsent as usize)
+}
+
+/// Returns the number of bytes in the send buffer of the given `fd` which have not been acknowledged by the peer
+/// yet, which is not supported on this platform.
+#[cfg(not(any(target_os = "android", target_os = "linux", target_os = "macos")))]
+pub(crate) fn unsent(_fd: os::fd::RawFd) -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "querying the unsent bytes is not supported on this platform",
+    ))
+}
+
+/// Gets the value of the socket option specified by the `level` and the `name` of the given `fd`.
+///
+/// # Safety
+/// The caller must ensure that `T` matches the type the kernel writes for the option, and that any bit pattern
+/// written by the kernel is a valid value of `T`.
+///
+/// # See also:
+/// [getsockopt(2)](https://man7.org/linux/man-pages/man2/getsockopt.2.html)
+pub(crate) unsafe fn getsockopt<T>(
+    fd: os::fd::RawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+) -> io::Result<T>
+where
+    T: Copy,
+{
+    let mut value = mem::MaybeUninit::<T>::zeroed();
+    let mut length = mem::size_of::<T>() as libc::socklen_t;
+    syscall!(getsockopt(
+        fd,
+        level,
+        name,
+        value.as_mut_ptr() as *mut libc::c_void,
+        &mut length,
+    ))?;
+    if length as usize != mem::size_of::<T>() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "size of the socket option does not match",
+        ));
+    }
+    // Safety:
+    // The kernel has filled out the whole `value` and the caller ensures that it is a valid value of `T`.
+    Ok(value.assume_init())
+}
+
+/// Sets the value of the socket option specified by the `level` and the `name` of the given `fd`.
+///
+/// # Safety
+/// The caller must ensure that `T` matches the type the kernel reads for the option.
+///
+/// # See also:
+/// [setsockopt(2)](https://man7.org/linux/man-pages/man2/setsockopt.2.html)
+pub(crate) unsafe fn setsockopt<T>(
+    fd: os::fd::RawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: T,
+) -> io::Result<()>
+where
+    T: Copy,
+{
+    syscall!(setsockopt(
+        fd,
+        level,
+        name,
+        &value as *const T as *const libc::c_void,
+        mem::size_of::<T>() as libc::socklen_t,
+    ))?;
+    Ok(())
+}
+
+/// Converts the given `addr` into a `sockaddr_storage` and its length to pass to the kernel.
+fn from_socket_addr(addr: &net::SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // Safety:
+    // `sockaddr_storage` is a plain C struct, so that it is safe to fill out with zeros.
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let length = match addr {
+        net::SocketAddr::V4(addr) => {
+            // Safety:
+            // This is safe because `sockaddr_storage` is large and aligned enough to hold any `sockaddr`.
+            let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr = libc::in_addr {
+                s_addr: u32::from(*addr.ip()).to_be(),
+            };
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        net::SocketAddr::V6(addr) => {
+            // Safety:
+            // This is safe because `sockaddr_storage` is large and aligned enough to hold any `sockaddr`.
+            let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_flowinfo = addr.flowinfo();
+            sin6.sin6_addr = libc::in6_addr {
+                s6_addr: addr.ip().octets(),
+            };
+            sin6.sin6_scope_id = addr.scope_id();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, length as libc::socklen_t)
+}
+
+/// Converts the given `storage` filled out by the kernel into a `SocketAddr`.
+#[cfg(any(target_os = "android", target_os = "freebsd", target_os = "linux"))]
+fn to_socket_addr(storage: &libc::sockaddr_storage) -> io::Result<net::SocketAddr> {
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET => {
+            // Safety:
+            // This is safe because the kernel ensures that `storage` holds `sockaddr_in` for `AF_INET`.
+            let addr = unsafe { &*(storage as *const _ as *const libc::sockaddr_in) };
+            Ok(net::SocketAddr::V4(net::SocketAddrV4::new(
+                net::Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                u16::from_be(addr.sin_port),
+            )))
+        }
+        libc::AF_INET6 => {
+            // Safety:
+            // This is safe because the kernel ensures that `storage` holds `sockaddr_in6` for `AF_INET6`.
+            let addr = unsafe { &*(storage as *const _ as *const libc::sockaddr_in6) };
+            Ok(net::SocketAddr::V6(net::SocketAddrV6::new(
+                net::Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                u16::from_be(addr.sin6_port),
+                addr.sin6_flowinfo,
+                addr.sin6_scope_id,
+            )))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid address family",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::fd::AsRawFd as _;
+
+    #[test]
+    fn accept_returns_a_non_blocking_close_on_exec_socket() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let e = accept(&listener).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+        let client = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, addr) = accept(&listener).unwrap();
+        assert_eq!(addr, client.local_addr().unwrap());
+        // Safety:
+        // `fcntl(2)` only reads the flags of the file descriptor owned by `stream`.
+        let (status, descriptor) = unsafe {
+            (
+                libc::fcntl(stream.as_raw_fd(), libc::F_GETFL),
+                libc::fcntl(stream.as_raw_fd(), libc::F_GETFD),
+            )
+        };
+        assert_ne!(status & libc::O_NONBLOCK, 0);
+        assert_ne!(descriptor & libc::FD_CLOEXEC, 0);
+    }
+}
@@ -0,0 +1,284 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of a `JoinSet`, which manages a dynamic group of tasks spawned onto the
+//! Little Tokio runtime and awaits their outputs in the order of completion, `join_handles`, which awaits a fixed
+//! group of tasks in the order of spawning, and `current_id`, which identifies the task being polled.
+
+use crate::core::scheduler::Scheduler;
+use crate::runtime::JoinHandle;
+use std::{fmt, future, marker, pin, task};
+
+pub use crate::core::task::Id;
+
+/// Returns the `Id` of the task being polled, or `None` if called outside of a task, e.g., from another thread.
+/// The future passed to `block_on` runs as a task as well.
+pub fn current_id() -> Option<Id> {
+    Scheduler::current()
+}
+
+/// Awaits all the tasks of the given `handles` and resolves to the vector of their outputs, in the order of the
+/// `handles`, or to the first error in that order, e.g., `Error::Cancelled` of an aborted task. The future resolves
+/// only once every task has completed or been dropped, i.e., an error does not abandon the other tasks halfway.
+///
+/// ```
+/// little_tokio::block_on(async {
+///     let handles = (0..3)
+///         .map(|n| little_tokio::spawn_with_output(async move { n * 10 }))
+///         .collect();
+///     let outputs = little_tokio::task::join_handles(handles).await.unwrap();
+///     assert_eq!(outputs, vec![0, 10, 20]);
+/// });
+/// ```
+///
+/// # See also:
+/// `JoinSet` which awaits the tasks in the order of completion instead.
+pub fn join_handles<T>(
+    handles: Vec<JoinHandle<T>>,
+) -> impl future::Future<Output = JoinHandlesOutput<T>> {
+    let outputs = crate::utils::join_all(handles);
+    async move { outputs.await.into_iter().collect() }
+}
+
+/// The output type of `join_handles`.
+pub type JoinHandlesOutput<T> = crate::Result<Vec<T>>;
+
+/// Represents a group of tasks spawned onto the Little Tokio runtime, all of which complete with an output of the
+/// type `T`.
+///
+/// ```
+/// use little_tokio::task::JoinSet;
+/// use std::time::Duration;
+///
+/// little_tokio::block_on(async {
+///     let mut set = JoinSet::new();
+///     for millis in [30, 10, 20] {
+///         set.spawn(async move {
+///             little_tokio::time::sleep(Duration::from_millis(millis)).await;
+///             millis
+///         });
+///     }
+///     let mut outputs = Vec::new();
+///     while let Some(output) = set.join_next().await {
+///         outputs.push(output.unwrap());
+///     }
+///     assert_eq!(outputs, vec![10, 20, 30]);
+/// });
+/// ```
+///
+/// # Note:
+/// Dropping a `JoinSet` does not cancel the tasks, i.e., the tasks keep running and their outputs are dropped.
+/// A panicking task is joined with `Error::Panicked` holding the message of the panic, i.e., the panic neither
+/// unwinds the runtime nor keeps the other tasks from being joined.
+pub struct JoinSet<T> {
+    /// Holds the `JoinHandle`s of the tasks which have not been joined yet.
+    handles: Vec<JoinHandle<T>>,
+    /// Holds the index of the `JoinHandle` polled first by the next `join_next`.
+    cursor: usize,
+}
+
+impl<T> JoinSet<T> {
+    /// Creates a new empty `JoinSet`.
+    pub fn new() -> Self {
+        Self {
+            handles: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    /// Returns the number of tasks which have not been joined yet.
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Returns `true` if there are no tasks to be joined.
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Polls the `JoinHandle`s round-robin, starting from the one next to the handle joined last, so that a task
+    /// which completes frequently cannot starve the others.
+    fn poll_join_next(&mut self, cx: &mut task::Context<'_>) -> task::Poll<JoinNextOutput<T>> {
+        let len = self.handles.len();
+        if len == 0 {
+            return task::Poll::Ready(None);
+        }
+        for offset in 0..len {
+            let index = (self.cursor + offset) % len;
+            if let task::Poll::Ready(output) =
+                future::Future::poll(pin::Pin::new(&mut self.handles[index]), cx)
+            {
+                self.handles.swap_remove(index);
+                self.cursor = index;
+                return task::Poll::Ready(Some(output));
+            }
+        }
+        task::Poll::Pending
+    }
+
+    /// Awaits the first of the tasks to complete and returns its output, or `None` if the `JoinSet` is empty. A task
+    /// dropped without completing, e.g., by the runtime shutting down, is joined with the error.
+    pub fn join_next(&mut self) -> impl future::Future<Output = JoinNextOutput<T>> + '_ {
+        future::poll_fn(move |cx| self.poll_join_next(cx))
+    }
+}
+
+impl<T> JoinSet<T>
+where
+    T: marker::Send + 'static,
+{
+    /// Spawns a future onto the Little Tokio runtime and adds it to this `JoinSet`.
+    ///
+    /// # See also:
+    /// `spawn_with_output` which spawns a single task.
+    pub fn spawn(&mut self, task: impl future::Future<Output = T> + marker::Send + 'static) {
+        self.handles.push(crate::spawn_catching_panics(task));
+    }
+}
+
+/// The output type of `JoinSet::join_next`.
+pub type JoinNextOutput<T> = Option<crate::Result<T>>;
+
+impl<T> Default for JoinSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for JoinSet<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "JoinSet(len: {})", self.len())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync;
+
+    #[test]
+    fn join_handles_collects_the_outputs_in_order() {
+        let _serial = crate::tests::serial();
+        let sums = sync::Arc::new(sync::Mutex::new(None));
+        let slot = sums.clone();
+        crate::block_on(async move {
+            let handles = [(0u64, 10u64), (10, 1000), (1000, 1001)]
+                .into_iter()
+                .map(|(start, end)| crate::spawn_with_output(async move { (start..end).sum() }))
+                .collect();
+            *slot.lock().unwrap() = Some(join_handles(handles).await.unwrap());
+        });
+        assert_eq!(*sums.lock().unwrap(), Some(vec![45u64, 499455, 1000]));
+    }
+
+    #[test]
+    fn join_handles_surfaces_the_cancellation() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let completed = crate::spawn_with_output(async { 1 });
+            let aborted = crate::spawn_with_output(future::pending::<i32>());
+            aborted.abort();
+            let ret = join_handles(vec![completed, aborted]).await;
+            assert!(matches!(ret, Err(crate::Error::Cancelled)));
+        });
+    }
+
+    #[test]
+    fn current_id_identifies_the_task_being_polled() {
+        let _serial = crate::tests::serial();
+        assert_eq!(current_id(), None);
+        let ids = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let log = ids.clone();
+        crate::block_on(async move {
+            let main = current_id().unwrap();
+            let spawned = (0..2)
+                .map(|_| crate::spawn_with_output(async { current_id().unwrap() }))
+                .collect();
+            let spawned = join_handles(spawned).await.unwrap();
+            assert_eq!(current_id(), Some(main));
+            *log.lock().unwrap() = [main].into_iter().chain(spawned).collect();
+        });
+        let ids = ids.lock().unwrap();
+        assert_eq!(format!("{:?}", *ids), "[0, 1, 2]");
+        assert_eq!(current_id(), None);
+    }
+
+    #[test]
+    fn join_set_joins_the_tasks_in_the_order_of_completion() {
+        let _serial = crate::tests::serial();
+        let joined = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let log = joined.clone();
+        crate::block_on(async move {
+            let mut set = JoinSet::new();
+            assert!(set.is_empty());
+            for millis in [60u64, 20, 40] {
+                set.spawn(async move {
+                    crate::time::sleep(std::time::Duration::from_millis(millis)).await;
+                    millis
+                });
+            }
+            assert_eq!(set.len(), 3);
+            while let Some(output) = set.join_next().await {
+                log.lock().unwrap().push((output.unwrap(), set.len()));
+            }
+            assert!(set.join_next().await.is_none());
+        });
+        assert_eq!(*joined.lock().unwrap(), [(20, 2), (40, 1), (60, 0)]);
+    }
+
+    #[test]
+    fn join_set_joins_an_aborted_task_with_the_cancellation() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut set = JoinSet::new();
+            set.spawn(future::pending::<i32>());
+            set.handles[0].abort();
+            assert!(matches!(
+                set.join_next().await,
+                Some(Err(crate::Error::Cancelled))
+            ));
+            assert!(set.is_empty());
+        });
+    }
+
+    #[test]
+    fn join_set_joins_a_panicked_task_with_the_panic_and_the_others_still_join() {
+        let _serial = crate::tests::serial();
+        let joined = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let log = joined.clone();
+        crate::block_on(async move {
+            let mut set = JoinSet::new();
+            for millis in [40u64, 20, 60] {
+                set.spawn(async move {
+                    crate::time::sleep(std::time::Duration::from_millis(millis)).await;
+                    if millis == 20 {
+                        panic!("boom after {}ms", millis);
+                    }
+                    millis
+                });
+            }
+            while let Some(output) = set.join_next().await {
+                log.lock().unwrap().push(output);
+            }
+        });
+        let joined = joined.lock().unwrap();
+        assert_eq!(joined.len(), 3);
+        assert!(
+            matches!(&joined[0], Err(crate::Error::Panicked(message)) if message == "boom after 20ms")
+        );
+        assert!(matches!(joined[1], Ok(40)));
+        assert!(matches!(joined[2], Ok(60)));
+    }
+}
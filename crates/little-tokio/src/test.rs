@@ -0,0 +1,211 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains testing utilities of the Little Tokio runtime, which records how the
+//! runtime polls tasks and delivers I/O events so that the scheduling behavior can be asserted.
+
+use crate::core::task::Id as TaskId;
+use crate::core::token::Token;
+use once_cell::sync::Lazy;
+use std::{fmt, future, marker, mem, sync};
+
+/// Provides the interface to access a `Tracer` singleton instance. Since the runtime is
+/// designed solely for single-threaded environments, all access to the tracer needs to occur
+/// via this singleton instance.
+struct Singleton;
+
+impl Singleton {
+    /// Returns the [`MutexGuard`](https://doc.rust-lang.org/std/sync/struct.MutexGuard.html) of the
+    /// `Tracer` singleton instance.
+    #[inline(always)]
+    fn instance() -> sync::MutexGuard<'static, Tracer> {
+        static INSTANCE: Lazy<sync::Mutex<Tracer>> =
+            Lazy::new(|| sync::Mutex::new(Tracer::default()));
+        INSTANCE
+            .lock()
+            .expect("`MutexGuard` of the `Tracer` singleton should be locked properly")
+    }
+}
+
+/// Represents an entry of a `Trace`, i.e., an observable action taken by the runtime.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    /// Specifies that the task associated with the given identifier has been polled.
+    Polled(i64),
+    /// Specifies that an I/O event associated with the given token has been delivered by the reactor.
+    Delivered(i64),
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Polled(id) => write!(fmt, "Entry::Polled({})", id)?,
+            Self::Delivered(token) => write!(fmt, "Entry::Delivered({})", token)?,
+        }
+        Ok(())
+    }
+}
+
+/// Represents the sequence of `Entry`s recorded while running a `Future` via `run_traced`.
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct Trace {
+    entries: Vec<Entry>,
+}
+
+impl Trace {
+    /// Returns the recorded entries in the order the runtime produced them.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Returns the identifiers of the polled tasks in the order the runtime polled them.
+    pub fn polled(&self) -> Vec<i64> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Polled(id) => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the tokens of the delivered events in the order the reactor delivered them.
+    pub fn delivered(&self) -> Vec<i64> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Delivered(token) => Some(*token),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for Trace {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_list().entries(self.entries.iter()).finish()
+    }
+}
+
+/// The Little Tokio tracer which is responsible for recording the runtime's actions.
+#[derive(Default)]
+struct Tracer {
+    /// Holds the entries recorded so far, or `None` if the tracer is disabled.
+    entries: Option<Vec<Entry>>,
+}
+
+/// Records that the task associated with the given `id` has been polled.
+pub(crate) fn record_poll(id: TaskId) {
+    if let Some(entries) = Singleton::instance().entries.as_mut() {
+        entries.push(Entry::Polled(id.into()));
+    }
+}
+
+/// Records that an I/O event associated with the given `token` has been delivered.
+pub(crate) fn record_event(token: Token) {
+    if let Some(entries) = Singleton::instance().entries.as_mut() {
+        entries.push(Entry::Delivered(token.into()));
+    }
+}
+
+/// Runs a `Future` to completion on the Little Tokio runtime and returns its output alongside the
+/// `Trace` of task polls and event deliveries which happened during the run.
+pub fn run_traced<F>(fut: F) -> (F::Output, Trace)
+where
+    F: future::Future + marker::Send + 'static,
+    F::Output: marker::Send + 'static,
+{
+    let slot = sync::Arc::new(sync::Mutex::new(None));
+    let output = slot.clone();
+    Singleton::instance().entries = Some(Vec::new());
+    crate::block_on(async move {
+        let ret = fut.await;
+        *output
+            .lock()
+            .expect("`MutexGuard` of the output slot should be locked properly") = Some(ret);
+    });
+    let entries = mem::take(&mut Singleton::instance().entries).unwrap_or_default();
+    let ret = slot
+        .lock()
+        .expect("`MutexGuard` of the output slot should be locked properly")
+        .take()
+        .expect("the traced future should be run to completion");
+    (ret, Trace { entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read as _, Write as _};
+    use std::os::{fd::AsRawFd as _, unix::net::UnixStream};
+    use std::{thread, time};
+
+    #[test]
+    fn run_traced_records_the_tasks_in_the_order_they_are_polled() {
+        let _serial = crate::tests::serial();
+        let (ret, trace) = run_traced(async {
+            crate::spawn(async {});
+            crate::spawn(async {});
+            7
+        });
+        assert_eq!(ret, 7);
+        assert_eq!(trace.polled(), [0, 1, 2]);
+        assert!(trace.delivered().is_empty());
+    }
+
+    #[test]
+    fn run_traced_records_the_event_waking_a_task() {
+        let _serial = crate::tests::serial();
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let fd = stream.as_raw_fd();
+        let (ret, trace) = run_traced(async move {
+            let async_fd = crate::io::AsyncFd::new(stream);
+            let mut guard = async_fd.readable().await.unwrap();
+            let mut buffer = [0u8; 4];
+            assert!(guard.try_io(|inner| (&*inner).read(&mut buffer)).is_err());
+            thread::spawn(move || {
+                thread::sleep(time::Duration::from_millis(20));
+                peer.write_all(b"ping").unwrap();
+            });
+            let mut guard = async_fd.readable().await.unwrap();
+            guard
+                .try_io(|inner| (&*inner).read(&mut buffer))
+                .unwrap()
+                .unwrap()
+        });
+        assert_eq!(ret, 4);
+        assert_eq!(trace.delivered(), [i64::from(fd)]);
+        let delivered = trace
+            .entries()
+            .iter()
+            .position(|entry| *entry == Entry::Delivered(i64::from(fd)))
+            .unwrap();
+        assert_eq!(trace.entries()[delivered + 1], Entry::Polled(0));
+    }
+
+    #[test]
+    fn run_traced_records_nothing_outside_of_the_run() {
+        let _serial = crate::tests::serial();
+        let ((), first) = run_traced(async {});
+        crate::block_on(async {
+            crate::spawn(async {});
+        });
+        let ((), second) = run_traced(async {});
+        assert_eq!(first.polled(), [0]);
+        // Note:
+        // The tasks 1 and 2 have been polled by the untraced run in between.
+        assert_eq!(second.polled(), [3]);
+    }
+}
@@ -0,0 +1,402 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of the timer utilities of the Little Tokio runtime, which delay tasks
+//! with one-shot timers registered to the reactor. Periodic timers are built on top of them, i.e., an `Interval`
+//! re-arms its one-shot timer after each tick.
+
+use crate::core::reactor::Reactor;
+use crate::core::token::Token;
+use pin_project::pin_project;
+use std::{error, fmt, future, pin, task};
+
+pub use std::time::{Duration, Instant};
+
+/// The delay considered as the far future, which substitutes for the deadlines which are not representable by
+/// `Instant`, i.e., roughly 30 years.
+const FAR_FUTURE: Duration = Duration::from_secs(86400 * 365 * 30);
+
+/// Waits until the given `duration` has elapsed.
+///
+/// # Note:
+/// The `Sleep` future registers a timer only when it gets polled, i.e., the `duration` starts when this function
+/// is called but the task is woken up only after the first poll.
+pub fn sleep(duration: Duration) -> Sleep {
+    let now = Instant::now();
+    let deadline = now
+        .checked_add(duration)
+        .unwrap_or_else(|| now + FAR_FUTURE);
+    sleep_until(deadline)
+}
+
+/// Waits until the given `deadline` is reached.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        token: None,
+    }
+}
+
+/// A `Future` which completes once its deadline is reached. This struct is created by `sleep` and `sleep_until`,
+/// and deregisters its timer when it gets dropped before completion.
+pub struct Sleep {
+    deadline: Instant,
+    token: Option<Token>,
+}
+
+impl Sleep {
+    /// Returns the `Instant` at which this future completes.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Returns `true` if the deadline has been reached.
+    pub fn is_elapsed(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+}
+
+impl future::Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            if let Some(token) = self.token.take() {
+                Reactor::deregister_timer(token);
+            }
+            return task::Poll::Ready(());
+        }
+        // Note:
+        // The timer is registered again when it has fired before the deadline is observed to be reached, e.g., due
+        // to the granularity of the clocks.
+        match self.token {
+            Some(token) if Reactor::block_timer(token, cx.waker().clone()) => {}
+            _ => {
+                if let Some(token) = self.token.take() {
+                    Reactor::deregister_timer(token);
+                }
+                let token = Reactor::register_timer(self.deadline - now, cx.waker().clone());
+                self.token = Some(token);
+            }
+        }
+        task::Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(token) = self.token.take() {
+            Reactor::deregister_timer(token);
+        }
+    }
+}
+
+impl fmt::Debug for Sleep {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Sleep({:?}, {:?})", self.deadline, self.token)?;
+        Ok(())
+    }
+}
+
+/// Creates an `Interval` which ticks every `period`, the first tick completing immediately.
+///
+/// # Panics:
+/// Panics if the given `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+    interval_at(Instant::now(), period)
+}
+
+/// Creates an `Interval` which ticks every `period`, the first tick completing at the given `start`.
+///
+/// # Panics:
+/// Panics if the given `period` is zero.
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    assert!(
+        !period.is_zero(),
+        "period of the interval should be positive"
+    );
+    Interval {
+        sleep: sleep_until(start),
+        period,
+    }
+}
+
+/// Represents a periodic timer, which is created by `interval` and `interval_at`. Each tick re-arms the one-shot
+/// timer of the inner `Sleep` for the next deadline.
+///
+/// # Note:
+/// Missed ticks are skipped rather than burst, i.e., when the task awaits `tick` late by more than a `period`,
+/// the late tick completes once, and the next deadline is the first one after now on the original schedule, so
+/// that a slow task observes at most one pending tick and never accumulates wakeups.
+pub struct Interval {
+    sleep: Sleep,
+    period: Duration,
+}
+
+impl Interval {
+    /// Returns the period of this `Interval`.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Completes once the next tick is reached and re-arms the timer for the one after it.
+    fn poll_tick(&mut self, cx: &mut task::Context<'_>) -> task::Poll<()> {
+        if future::Future::poll(pin::Pin::new(&mut self.sleep), cx).is_pending() {
+            return task::Poll::Pending;
+        }
+        let now = Instant::now();
+        let deadline = self.sleep.deadline();
+        let next = match deadline.checked_add(self.period) {
+            Some(next) if next > now => Some(next),
+            // Note:
+            // Skips the missed ticks, keeping the phase of the original schedule.
+            Some(_) => {
+                let behind = (now - deadline).as_nanos() % self.period.as_nanos();
+                now.checked_add(self.period - Duration::from_nanos(behind as u64))
+            }
+            None => None,
+        };
+        self.sleep = sleep_until(next.unwrap_or_else(|| now + FAR_FUTURE));
+        task::Poll::Ready(())
+    }
+
+    /// Waits until the next tick is reached.
+    pub fn tick(&mut self) -> impl future::Future<Output = TickOutput> + '_ {
+        future::poll_fn(move |cx| self.poll_tick(cx))
+    }
+}
+
+/// The output type of `Interval::tick`.
+pub type TickOutput = ();
+
+impl fmt::Debug for Interval {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "Interval({:?}, {:?})",
+            self.sleep.deadline, self.period
+        )?;
+        Ok(())
+    }
+}
+
+/// Requires the given `future` to complete before the given `duration` has elapsed, and returns a `Timeout` struct
+/// which resolves to `Err(Elapsed)` otherwise. The `future` gets dropped once the `duration` has elapsed, which
+/// cancels it, e.g., deregisters the file descriptors registered by it.
+pub fn timeout<F>(duration: Duration, future: F) -> Timeout<F>
+where
+    F: future::Future,
+{
+    Timeout {
+        future: Some(future),
+        sleep: sleep(duration),
+    }
+}
+
+/// Requires the given `future` to complete before the given `deadline` is reached. See `timeout` for the details.
+pub fn timeout_at<F>(deadline: Instant, future: F) -> Timeout<F>
+where
+    F: future::Future,
+{
+    Timeout {
+        future: Some(future),
+        sleep: sleep_until(deadline),
+    }
+}
+
+/// A `Future` which races its inner future against a `Sleep`. This struct is created by `timeout` and `timeout_at`,
+/// and drops the inner future as soon as it resolves, i.e., once the inner future completes or the deadline is
+/// reached, rather than when the `Timeout` itself gets dropped.
+#[pin_project]
+pub struct Timeout<F> {
+    #[pin]
+    future: Option<F>,
+    sleep: Sleep,
+}
+
+impl<F> Timeout<F> {
+    /// Returns the reference to the inner future, or `None` if the `Timeout` has resolved.
+    pub fn get_ref(&self) -> Option<&F> {
+        self.future.as_ref()
+    }
+
+    /// Returns the inner future, dropping the timer, or `None` if the `Timeout` has resolved.
+    pub fn into_inner(self) -> Option<F> {
+        self.future
+    }
+}
+
+pub type TimeoutOutput<T> = Result<T, Elapsed>;
+
+impl<F> future::Future for Timeout<F>
+where
+    F: future::Future,
+{
+    type Output = TimeoutOutput<F::Output>;
+
+    /// # Panics:
+    /// Panics if the `Timeout` gets polled after it has resolved.
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut this = self.project();
+        let future = this
+            .future
+            .as_mut()
+            .as_pin_mut()
+            .expect("`Timeout` should not be polled after completion");
+        // Note:
+        // The inner future is polled first, so that a future which completes right at the deadline still wins.
+        if let task::Poll::Ready(output) = future.poll(cx) {
+            this.future.set(None);
+            return task::Poll::Ready(Ok(output));
+        }
+        match pin::Pin::new(this.sleep).poll(cx) {
+            task::Poll::Ready(()) => {
+                this.future.set(None);
+                task::Poll::Ready(Err(Elapsed(())))
+            }
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+impl<F> fmt::Debug for Timeout<F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Timeout({:?})", self.sleep.deadline)?;
+        Ok(())
+    }
+}
+
+/// Represents the failure of a `Timeout` whose deadline has been reached before the inner future completes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(());
+
+impl fmt::Debug for Elapsed {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Elapsed")?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "deadline has elapsed")?;
+        Ok(())
+    }
+}
+
+impl error::Error for Elapsed {}
+
+impl From<Elapsed> for crate::Error {
+    fn from(_: Elapsed) -> Self {
+        Self::Elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{self, atomic};
+
+    /// Represents a future which never completes and records when it gets dropped.
+    struct Forever(sync::Arc<atomic::AtomicBool>);
+
+    impl future::Future for Forever {
+        type Output = ();
+
+        fn poll(self: pin::Pin<&mut Self>, _cx: &mut task::Context<'_>) -> task::Poll<()> {
+            task::Poll::Pending
+        }
+    }
+
+    impl Drop for Forever {
+        fn drop(&mut self) {
+            self.0.store(true, atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn sleep_waits_for_the_duration() {
+        let _serial = crate::tests::serial();
+        let started = Instant::now();
+        crate::block_on(sleep(Duration::from_millis(50)));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn timeout_resolves_with_the_output_in_time() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let ret = timeout(Duration::from_secs(1), async { 42 }).await;
+            assert_eq!(ret, Ok(42));
+        });
+    }
+
+    #[test]
+    fn timeout_drops_the_inner_future_once_elapsed() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let dropped = sync::Arc::new(atomic::AtomicBool::new(false));
+            let mut timeout =
+                pin::pin!(timeout(Duration::from_millis(20), Forever(dropped.clone())));
+            assert_eq!(timeout.as_mut().await, Err(Elapsed(())));
+            assert!(dropped.load(atomic::Ordering::SeqCst));
+            assert!(timeout.get_ref().is_none());
+        });
+    }
+
+    #[test]
+    fn interval_ticks_once_per_period() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut interval = interval(Duration::from_millis(50));
+            let mut ticks = 0;
+            let ret = timeout(Duration::from_millis(275), async {
+                loop {
+                    interval.tick().await;
+                    ticks += 1;
+                }
+            })
+            .await;
+            assert!(ret.is_err());
+            // Note:
+            // The first tick completes immediately, and the others at 50, 100, 150, 200 and 250 milliseconds.
+            assert!((5..=6).contains(&ticks), "ticked {} times", ticks);
+        });
+    }
+
+    #[test]
+    fn interval_skips_the_missed_ticks() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut interval = interval(Duration::from_millis(50));
+            interval.tick().await;
+            std::thread::sleep(Duration::from_millis(175));
+            let late = Instant::now();
+            interval.tick().await;
+            assert!(late.elapsed() < Duration::from_millis(10));
+            // Note:
+            // The ticks at 50, 100 and 150 milliseconds have been missed, and the next one is at 200 milliseconds.
+            let next = Instant::now();
+            interval.tick().await;
+            assert!(next.elapsed() >= Duration::from_millis(10));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "period of the interval should be positive")]
+    fn interval_rejects_a_zero_period() {
+        interval(Duration::ZERO);
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the diagnostics of the Little Tokio runtime, which are compiled only when the
+//! `trace` feature is enabled. Besides the warnings, the runtime reports its internal state transitions, i.e.,
+//! the polls of the tasks, the registrations of and blocks on the file descriptors, and the wakes of the tasks, as
+//! events timestamped relative to the first event, which allows watching the event loop turn.
+
+use once_cell::sync::Lazy;
+use std::{fmt, time};
+
+/// Holds the `Instant` which the timestamps of the events are relative to.
+static START: Lazy<time::Instant> = Lazy::new(time::Instant::now);
+
+/// Holds the warnings reported so far, so that the tests can assert them.
+#[cfg(test)]
+static WARNINGS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+/// Reports the given `message` as a warning to the standard error.
+pub(crate) fn warn(message: fmt::Arguments<'_>) {
+    #[cfg(test)]
+    WARNINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(message.to_string());
+    eprintln!("[little-tokio] WARN {}", message);
+}
+
+/// Returns the warnings reported since the last call.
+#[cfg(test)]
+pub(crate) fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut *WARNINGS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Holds the events reported so far, without their timestamps, so that the tests can assert them.
+#[cfg(test)]
+static EVENTS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+/// Reports the given `message` as an event of the runtime to the standard error.
+pub(crate) fn event(message: fmt::Arguments<'_>) {
+    #[cfg(test)]
+    EVENTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(message.to_string());
+    let elapsed = START.elapsed();
+    eprintln!(
+        "[little-tokio] {:>6}.{:06} {}",
+        elapsed.as_secs(),
+        elapsed.subsec_micros(),
+        message
+    );
+}
+
+/// Returns the events reported since the last call.
+#[cfg(test)]
+pub(crate) fn take_events() -> Vec<String> {
+    std::mem::take(&mut *EVENTS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::AsyncFd;
+    use std::io::{Read as _, Write as _};
+    use std::os::fd::AsRawFd as _;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn events_trace_a_task_blocking_on_a_file_descriptor_until_woken() {
+        let _serial = crate::tests::serial();
+        let (reader, mut writer) = UnixStream::pair().unwrap();
+        reader.set_nonblocking(true).unwrap();
+        let fd = reader.as_raw_fd();
+        take_events();
+        crate::block_on(async move {
+            let reader = AsyncFd::new(reader);
+            std::thread::spawn(move || {
+                std::thread::sleep(time::Duration::from_millis(20));
+                writer.write_all(b"x").unwrap();
+            });
+            let mut buffer = [0u8; 1];
+            loop {
+                let mut guard = reader.readable().await.unwrap();
+                if let Ok(size) = guard.try_io(|reader| (&*reader).read(&mut buffer)) {
+                    assert_eq!(size.unwrap(), 1);
+                    break;
+                }
+            }
+        });
+        let expected = [
+            format!("register fd {} for READABLE", fd),
+            format!("block task Some(0) on fd {} for READABLE", fd),
+            "poll task 0: pending".to_string(),
+            "wake task 0".to_string(),
+            "poll task 0: ready".to_string(),
+        ];
+        // Note:
+        // The events are matched in order by their prefixes, skipping the ones in between, and each poll is reported
+        // once it has returned, together with its elapsed time.
+        let events = take_events();
+        let mut remaining = expected.iter().peekable();
+        for event in &events {
+            if remaining
+                .peek()
+                .is_some_and(|prefix| event.starts_with(prefix.as_str()))
+            {
+                remaining.next();
+            }
+        }
+        assert!(
+            remaining.peek().is_none(),
+            "{:?} not found in {:?}",
+            remaining.collect::<Vec<_>>(),
+            events
+        );
+    }
+}
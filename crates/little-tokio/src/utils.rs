@@ -25,5 +25,478 @@ macro_rules! ready {
     };
 }
 
+/// Awaits the given futures concurrently and resolves to the tuple of their outputs once all of them have
+/// completed. Each future is wrapped into a `MaybeDone`, and all the futures which have not completed yet are
+/// polled on each wakeup. This macro can only be used inside of an async context.
+///
+/// ```
+/// use std::time::{Duration, Instant};
+///
+/// little_tokio::block_on(async {
+///     let started = Instant::now();
+///     let (one, (), two) = little_tokio::join!(
+///         async { 1 },
+///         little_tokio::time::sleep(Duration::from_millis(50)),
+///         async { 2 },
+///     );
+///     assert_eq!((one, two), (1, 2));
+///     assert!(started.elapsed() >= Duration::from_millis(50));
+/// });
+/// ```
+#[macro_export]
+macro_rules! join {
+    // Note:
+    // Since `macro_rules` cannot generate identifiers, each future is normalized together with the `_`s preceding
+    // it in the tuple of the futures, which are utilized to destructure the tuple down to the future.
+    (@ { ( $($count: tt)* ) $( ( $($skip: tt)* ) $future: expr, )* } $next: expr, $($rest: expr,)*) => {
+        $crate::join!(@ { ( $($count)* _ ) $( ( $($skip)* ) $future, )* ( $($count)* ) $next, } $($rest,)*)
+    };
+    (@ { ( $($count: tt)* ) $( ( $($skip: tt)* ) $future: expr, )* }) => {{
+        let mut futures = ( $( $crate::utils::maybe_done::maybe_done($future), )* );
+        std::future::poll_fn(move |cx| {
+            let mut done = true;
+            $(
+                let ( $($skip,)* future, .. ) = &mut futures;
+                // Safety:
+                // The `futures` are owned by the closure, which is pinned by `poll_fn` and never moves them.
+                let future = unsafe { std::pin::Pin::new_unchecked(future) };
+                if std::future::Future::poll(future, cx).is_pending() {
+                    done = false;
+                }
+            )*
+            if !done {
+                return std::task::Poll::Pending;
+            }
+            std::task::Poll::Ready(( $({
+                let ( $($skip,)* future, .. ) = &mut futures;
+                // Safety:
+                // The `futures` are owned by the closure, which is pinned by `poll_fn` and never moves them.
+                let future = unsafe { std::pin::Pin::new_unchecked(future) };
+                future.take_output().expect("`MaybeDone` should hold the output of the done future")
+            }, )* ))
+        }).await
+    }};
+    ( $($future: expr),+ $(,)? ) => {
+        $crate::join!(@ { () } $($future,)+)
+    };
+}
+
+/// Awaits the given fallible futures concurrently and resolves to `Ok` with the tuple of their outputs once all of
+/// them have completed with `Ok`, or to the first `Err` as soon as any of them completes with `Err`. All the
+/// futures are required to fail with the same error type. This macro can only be used inside of an async context.
+///
+/// ```
+/// little_tokio::block_on(async {
+///     let ret = little_tokio::try_join!(async { Ok::<_, &str>(1) }, async { Ok(2) });
+///     assert_eq!(ret, Ok((1, 2)));
+///     let ret = little_tokio::try_join!(async { Ok::<(), _>(()) }, async { Err("refused") });
+///     assert_eq!(ret, Err("refused"));
+/// });
+/// ```
+///
+/// # Note:
+/// Once a future completes with `Err`, all the other futures are dropped, which cancels them as `select!` does:
+/// the file descriptors registered by them are deregistered, and the data they have half processed is lost, e.g.,
+/// a `write_all` dropped in the middle leaves it unknown how many bytes have been written.
+#[macro_export]
+macro_rules! try_join {
+    // Note:
+    // As `join!` does, each future is normalized together with the `_`s preceding it in the tuple of the futures,
+    // which are utilized to destructure the tuple down to the future.
+    (@ { ( $($count: tt)* ) $( ( $($skip: tt)* ) $future: expr, )* } $next: expr, $($rest: expr,)*) => {
+        $crate::try_join!(@ { ( $($count)* _ ) $( ( $($skip)* ) $future, )* ( $($count)* ) $next, } $($rest,)*)
+    };
+    (@ { ( $($count: tt)* ) $( ( $($skip: tt)* ) $future: expr, )* }) => {{
+        let mut futures = ( $( $crate::utils::maybe_done::maybe_done($future), )* );
+        std::future::poll_fn(move |cx| {
+            let mut done = true;
+            $(
+                let ( $($skip,)* future, .. ) = &mut futures;
+                // Safety:
+                // The `futures` are owned by the closure, which is pinned by `poll_fn` and never moves them.
+                let mut future = unsafe { std::pin::Pin::new_unchecked(future) };
+                if std::future::Future::poll(future.as_mut(), cx).is_pending() {
+                    done = false;
+                } else if let Some(Err(_)) = future.output() {
+                    match future.take_output() {
+                        Some(Err(e)) => return std::task::Poll::Ready(Err(e)),
+                        _ => unreachable!("`MaybeDone` should hold the error of the failed future"),
+                    }
+                }
+            )*
+            if !done {
+                return std::task::Poll::Pending;
+            }
+            std::task::Poll::Ready(Ok(( $({
+                let ( $($skip,)* future, .. ) = &mut futures;
+                // Safety:
+                // The `futures` are owned by the closure, which is pinned by `poll_fn` and never moves them.
+                let future = unsafe { std::pin::Pin::new_unchecked(future) };
+                match future.take_output() {
+                    Some(Ok(output)) => output,
+                    _ => unreachable!("`MaybeDone` should hold the output of the succeeded future"),
+                }
+            }, )* )))
+        }).await
+    }};
+    ( $($future: expr),+ $(,)? ) => {
+        $crate::try_join!(@ { () } $($future,)+)
+    };
+}
+
+/// Awaits the given branches concurrently and evaluates the handler of the first branch whose future completes,
+/// binding the output of the future to the pattern of the branch. The branches are written as
+/// `pattern = future => handler`, and this macro can only be used inside of an async context.
+///
+/// ```
+/// little_tokio::block_on(async {
+///     let (_sender, mut receiver) = little_tokio::sync::channel::<u32>();
+///     let ret = little_tokio::select! {
+///         value = receiver.recv() => value.ok_or("closed"),
+///         () = little_tokio::time::sleep(std::time::Duration::from_millis(10)) => Err("timed out"),
+///     };
+///     assert_eq!(ret, Err("timed out"));
+/// });
+/// ```
+///
+/// # Note:
+/// The futures are polled in the order of the branches on each wakeup, i.e., an earlier branch takes priority
+/// when several futures are ready at once. Once a future completes, all the other futures are dropped before the
+/// handler is evaluated, which cancels them: the file descriptors registered by them are deregistered, and the
+/// data they have half processed is lost, e.g., a `write_all` dropped in the middle leaves it unknown how many
+/// bytes have been written. The pattern of each branch must match any output of its future, otherwise this
+/// macro panics once the branch completes.
+///
+/// The last branch may be written as `default => handler`, in which case the futures are polled only once, and
+/// the `default` handler is evaluated without waiting if none of them is ready, e.g., to drain whatever is
+/// readily available without parking the task:
+///
+/// ```
+/// little_tokio::block_on(async {
+///     let polled = little_tokio::select! {
+///         () = std::future::pending() => "pending",
+///         () = std::future::pending() => "pending",
+///         default => "default",
+///     };
+///     assert_eq!(polled, "default");
+/// });
+/// ```
+#[macro_export]
+macro_rules! select {
+    (@default $default: expr) => {
+        $default
+    };
+    (@default) => {
+        unreachable!("the pattern of the completed branch should match its output")
+    };
+    (@has_default $default: expr) => {
+        true
+    };
+    (@has_default) => {
+        false
+    };
+    // Note:
+    // As `join!` does, each branch is normalized together with the `_`s preceding its future in the tuple of the
+    // futures, which are utilized to destructure the tuple down to the future. The `default` handler, if any, is
+    // carried in the brackets following the branches.
+    (@ { ( $($count: tt)* ) $( ( ( $($skip: tt)* ) $bind: pat = $future: expr => $handler: expr ) )* } [ $($default: expr)? ]) => {{
+        let mut outputs;
+        {
+            let mut futures = ( $( $future, )* );
+            outputs = ( $({
+                let ( $($skip,)* future, .. ) = &futures;
+                $crate::utils::output_slot(future)
+            }, )* );
+            std::future::poll_fn(|cx| {
+                $(
+                    let ( $($skip,)* future, .. ) = &mut futures;
+                    // Safety:
+                    // The `futures` are never moved until they get dropped at the end of the enclosing block.
+                    let future = unsafe { std::pin::Pin::new_unchecked(future) };
+                    if let std::task::Poll::Ready(output) = std::future::Future::poll(future, cx) {
+                        let ( $($skip,)* slot, .. ) = &mut outputs;
+                        *slot = Some(output);
+                        return std::task::Poll::Ready(());
+                    }
+                )*
+                if $crate::select!(@has_default $($default)?) {
+                    return std::task::Poll::Ready(());
+                }
+                std::task::Poll::Pending
+            }).await;
+        }
+        $(
+            if let ( $($skip,)* Some($bind), .. ) = outputs {
+                $handler
+            } else
+        )* {
+            $crate::select!(@default $($default)?)
+        }
+    }};
+    (@ { $($branches: tt)* }) => {
+        $crate::select!(@ { $($branches)* } [])
+    };
+    (@ { $($branches: tt)* } default => $default: expr $(,)?) => {
+        $crate::select!(@ { $($branches)* } [ $default ])
+    };
+    (@ { ( $($count: tt)* ) $($branches: tt)* } $bind: pat = $future: expr => $handler: expr $(, $($rest: tt)*)?) => {
+        $crate::select!(@ {
+            ( $($count)* _ )
+            $($branches)*
+            ( ( $($count)* ) $bind = $future => $handler )
+        } $($($rest)*)?)
+    };
+    ( $($branches: tt)+ ) => {
+        $crate::select!(@ { () } $($branches)+)
+    };
+}
+
+/// Returns the empty slot for the output of the given `future`, which is utilized by `select!` in order to infer
+/// the type of the output.
+#[doc(hidden)]
+pub fn output_slot<F>(_: &F) -> Option<F::Output>
+where
+    F: std::future::Future,
+{
+    None
+}
+
+pub mod futures_unordered;
+pub mod join_all;
 pub mod maybe_done;
 pub(crate) mod misc;
+pub mod retry;
+pub mod select_all;
+
+pub use self::futures_unordered::FuturesUnordered;
+pub use self::join_all::join_all;
+pub use self::retry::{retry, retry_exponential};
+pub use self::select_all::select_all;
+
+/// Creates a `Future` which resolves once the given closure returns `Poll::Ready`, calling the closure with the
+/// `Context` of the task on each poll. This is the standard combinator of `std`, re-exported here in order to
+/// prototype readiness logic inline without writing a dedicated struct. For example, a future which yields to the
+/// other tasks once can be written as follows:
+///
+/// ```
+/// async fn yield_now() {
+///     let mut yielded = false;
+///     little_tokio::utils::poll_fn(|cx| {
+///         if yielded {
+///             return std::task::Poll::Ready(());
+///         }
+///         yielded = true;
+///         cx.waker().wake_by_ref();
+///         std::task::Poll::Pending
+///     })
+///     .await
+/// }
+///
+/// let flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+/// let other = flag.clone();
+/// little_tokio::block_on(async move {
+///     // Note:
+///     // The spawned task completes while the main task yields.
+///     little_tokio::spawn(async move {
+///         yield_now().await;
+///         other.store(true, std::sync::atomic::Ordering::SeqCst);
+///     });
+///     yield_now().await;
+///     yield_now().await;
+///     assert!(flag.load(std::sync::atomic::Ordering::SeqCst));
+/// });
+/// ```
+///
+/// # Note:
+/// The closure itself is not pinned, i.e., a future captured by the closure needs to be pinned, e.g., by
+/// `Box::pin` or `std::pin::pin!`, in order to be polled from it.
+pub use std::future::poll_fn;
+
+/// Creates a `Future` which never resolves. Awaiting it parks the task on nothing that could ever wake it up, so
+/// that the runtime fails as deadlocked unless something else keeps it running, e.g., `try_block_on(pending())`
+/// returns an error. It is also handy as a branch of `select!` which never wins.
+///
+/// # See also:
+/// `ready` which resolves immediately.
+pub use std::future::pending;
+
+/// Creates a `Future` which resolves to the given value on its first poll, without any bound on the type of the
+/// value. It is handy as a deterministic branch of `join!` and `select!`.
+///
+/// # Panics:
+/// Panics if the future gets polled after it has resolved.
+pub use std::future::ready;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::yield_now;
+    use std::{sync, task};
+
+    /// Runs the given `test` on the Little Tokio runtime.
+    fn run(test: impl std::future::Future<Output = ()> + Send + 'static) {
+        let _serial = crate::tests::serial();
+        crate::block_on(test);
+    }
+
+    #[test]
+    fn select_evaluates_the_handler_of_the_first_ready_branch() {
+        run(async {
+            let polled = crate::select! {
+                () = pending() => 0,
+                value = async { yield_now().await; 2 } => value,
+                value = ready(3) => value,
+            };
+            assert_eq!(polled, 3);
+        });
+    }
+
+    #[test]
+    fn select_runs_the_default_when_no_branch_is_ready() {
+        run(async {
+            let polled = crate::select! {
+                () = pending() => "first",
+                () = yield_now() => "second",
+                default => "default",
+            };
+            assert_eq!(polled, "default");
+        });
+    }
+
+    #[test]
+    fn select_prefers_a_ready_branch_over_the_default() {
+        run(async {
+            let polled = crate::select! {
+                () = pending() => 1,
+                value = ready(2) => value,
+                default => 0,
+            };
+            assert_eq!(polled, 2);
+        });
+    }
+
+    #[test]
+    fn select_drops_the_other_futures_before_the_default() {
+        run(async {
+            let shared = sync::Arc::new(());
+            let held = shared.clone();
+            let polled = crate::select! {
+                () = async move { let _held = held; pending::<()>().await } => false,
+                default => sync::Arc::strong_count(&shared) == 1,
+            };
+            assert!(polled);
+        });
+    }
+
+    #[test]
+    fn join_awaits_the_futures_concurrently() {
+        run(async {
+            let started = std::time::Instant::now();
+            let ((), ()) = crate::join!(
+                crate::time::sleep(std::time::Duration::from_millis(100)),
+                crate::time::sleep(std::time::Duration::from_millis(100)),
+            );
+            let elapsed = started.elapsed();
+            assert!(elapsed >= std::time::Duration::from_millis(100));
+            assert!(elapsed < std::time::Duration::from_millis(200));
+        });
+    }
+
+    #[test]
+    fn join_resolves_to_the_outputs_in_the_order_of_the_futures() {
+        run(async {
+            let log = sync::Arc::new(sync::Mutex::new(Vec::new()));
+            let slow = log.clone();
+            let fast = log.clone();
+            let (one, two, three) = crate::join!(
+                async move {
+                    yield_now().await;
+                    yield_now().await;
+                    slow.lock().unwrap().push(1);
+                    "one"
+                },
+                async move {
+                    fast.lock().unwrap().push(2);
+                    2
+                },
+                async { 3.0 },
+            );
+            assert_eq!((one, two, three), ("one", 2, 3.0));
+            assert_eq!(*log.lock().unwrap(), [2, 1]);
+        });
+    }
+
+    #[test]
+    fn try_join_resolves_to_the_outputs_once_all_succeed() {
+        run(async {
+            let ret = crate::try_join!(
+                async {
+                    yield_now().await;
+                    Ok::<_, std::io::Error>("one")
+                },
+                async { Ok(2) },
+            );
+            assert_eq!(ret.unwrap(), ("one", 2));
+        });
+    }
+
+    #[test]
+    fn try_join_short_circuits_on_the_first_error() {
+        /// Records when the cancelled future gets dropped.
+        struct Guard(sync::Arc<sync::atomic::AtomicBool>);
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.store(true, sync::atomic::Ordering::SeqCst);
+            }
+        }
+        run(async {
+            let dropped = sync::Arc::new(sync::atomic::AtomicBool::new(false));
+            let guard = Guard(dropped.clone());
+            let started = std::time::Instant::now();
+            let ret = crate::try_join!(
+                async move {
+                    let _guard = guard;
+                    crate::time::sleep(std::time::Duration::from_secs(10)).await;
+                    Ok(())
+                },
+                async {
+                    yield_now().await;
+                    Err::<(), _>("refused")
+                },
+            );
+            assert_eq!(ret, Err("refused"));
+            assert!(dropped.load(sync::atomic::Ordering::SeqCst));
+            assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        });
+    }
+
+    #[test]
+    fn ready_resolves_on_the_first_poll_without_default() {
+        /// Represents a value without `Default`.
+        #[derive(Debug, PartialEq)]
+        struct Value(&'static str);
+
+        run(async {
+            let mut ready = std::pin::pin!(ready(Value("ready")));
+            let polled =
+                poll_fn(|cx| task::Poll::Ready(std::future::Future::poll(ready.as_mut(), cx)))
+                    .await;
+            assert_eq!(polled, task::Poll::Ready(Value("ready")));
+        });
+    }
+
+    #[test]
+    fn ready_is_the_deterministic_branch_against_pending() {
+        run(async {
+            for _ in 0..8 {
+                let polled = crate::select! {
+                    () = pending() => None,
+                    value = ready(3) => Some(value),
+                };
+                assert_eq!(polled, Some(3));
+            }
+            let (one, two) = crate::join!(ready(1), ready("two"));
+            assert_eq!((one, two), (1, "two"));
+        });
+    }
+}
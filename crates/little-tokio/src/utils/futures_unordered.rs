@@ -0,0 +1,347 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of `FuturesUnordered` combinator.
+
+use crate::utils::maybe_done::{maybe_done, MaybeDone};
+use std::{collections, fmt, future, pin, sync, task};
+
+/// Represents a growing set of futures driven within a single task, which yields the outputs of the futures in
+/// the order of completion. Each future is wrapped into a `MaybeDone` with its own `Waker` tagged with the index
+/// of the future, so that only the futures which have been woken up get polled again.
+///
+/// ```
+/// use little_tokio::stream::StreamExt;
+/// use std::time::Duration;
+///
+/// little_tokio::block_on(async {
+///     let mut futures = little_tokio::utils::FuturesUnordered::new();
+///     for millis in [30, 10, 20] {
+///         futures.push(async move {
+///             little_tokio::time::sleep(Duration::from_millis(millis)).await;
+///             millis
+///         });
+///     }
+///     let mut completed = Vec::new();
+///     while let Some(millis) = futures.next().await {
+///         completed.push(millis);
+///     }
+///     assert_eq!(completed, [10, 20, 30]);
+/// });
+/// ```
+///
+/// # Note:
+/// The number of futures polled per `poll_next` is bounded by the number of the futures woken up before it, so
+/// that a future which keeps waking itself up cannot starve the task.
+pub struct FuturesUnordered<F>
+where
+    F: future::Future,
+{
+    /// Holds the futures, where `None` marks a free entry which can be reused by `push`.
+    entries: Vec<Option<Entry<F>>>,
+    /// Holds the indices of the free entries.
+    vacant: Vec<usize>,
+    /// Holds the state shared with the `Waker`s of the futures.
+    shared: sync::Arc<sync::Mutex<Shared>>,
+}
+
+/// Represents a future of a `FuturesUnordered` together with its tagged `Waker`.
+struct Entry<F>
+where
+    F: future::Future,
+{
+    future: pin::Pin<Box<MaybeDone<F>>>,
+    waker: task::Waker,
+}
+
+/// Represents the state shared between a `FuturesUnordered` and the `Waker`s of its futures.
+#[derive(Default)]
+struct Shared {
+    /// Holds the indices of the futures woken up, in the order of the wakes.
+    woken: collections::VecDeque<usize>,
+    /// Holds the indices in `woken`, so that a future woken several times gets polled once.
+    queued: collections::HashSet<usize>,
+    /// Holds the waker of the task polling the `FuturesUnordered`.
+    waker: Option<task::Waker>,
+}
+
+/// Represents the `Waker` of the future at `index`, which queues the index and wakes up the polling task.
+struct Tagged {
+    index: usize,
+    shared: sync::Arc<sync::Mutex<Shared>>,
+}
+
+impl task::Wake for Tagged {
+    fn wake(self: sync::Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &sync::Arc<Self>) {
+        let waker = {
+            let mut shared = lock(&self.shared);
+            if shared.queued.insert(self.index) {
+                shared.woken.push_back(self.index);
+            }
+            shared.waker.clone()
+        };
+        // Note:
+        // The waker is woken after the lock is released since waking may poll the task eagerly.
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Locks the given `shared` state.
+fn lock(shared: &sync::Mutex<Shared>) -> sync::MutexGuard<'_, Shared> {
+    shared
+        .lock()
+        .expect("`MutexGuard` of the `FuturesUnordered` should be locked properly")
+}
+
+impl<F> FuturesUnordered<F>
+where
+    F: future::Future,
+{
+    /// Creates a new empty `FuturesUnordered`.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            vacant: Vec::new(),
+            shared: sync::Arc::default(),
+        }
+    }
+
+    /// Returns the number of futures which have not completed yet.
+    pub fn len(&self) -> usize {
+        self.entries.len() - self.vacant.len()
+    }
+
+    /// Returns `true` if there are no futures which have not completed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pushes the given `future` into the set. The `future` is polled for the first time by the next `poll_next`.
+    pub fn push(&mut self, future: F) {
+        let index = self.vacant.pop().unwrap_or_else(|| {
+            self.entries.push(None);
+            self.entries.len() - 1
+        });
+        let waker = task::Waker::from(sync::Arc::new(Tagged {
+            index,
+            shared: sync::Arc::clone(&self.shared),
+        }));
+        waker.wake_by_ref();
+        self.entries[index] = Some(Entry {
+            future: Box::pin(maybe_done(future)),
+            waker,
+        });
+    }
+}
+
+impl<F> crate::stream::Stream for FuturesUnordered<F>
+where
+    F: future::Future,
+{
+    type Item = F::Output;
+
+    fn poll_next(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        // Note:
+        // `FuturesUnordered` holds its futures in `Box`es, i.e., it is `Unpin`, so that the fields can be borrowed
+        // separately without any pin projection.
+        let this = self.get_mut();
+        if this.is_empty() {
+            return task::Poll::Ready(None);
+        }
+        let budget = {
+            let mut shared = lock(&this.shared);
+            shared.waker = Some(cx.waker().clone());
+            shared.woken.len()
+        };
+        for _ in 0..budget {
+            let index = {
+                let mut shared = lock(&this.shared);
+                let Some(index) = shared.woken.pop_front() else {
+                    break;
+                };
+                shared.queued.remove(&index);
+                index
+            };
+            // Note:
+            // A wake may refer to a future which has completed in the meantime, or to a later one reusing its entry,
+            // in which case the latter is merely polled spuriously.
+            let Some(entry) = this.entries[index].as_mut() else {
+                continue;
+            };
+            let mut cx = task::Context::from_waker(&entry.waker);
+            if future::Future::poll(entry.future.as_mut(), &mut cx).is_pending() {
+                continue;
+            }
+            let output = entry.future.as_mut().take_output();
+            this.entries[index] = None;
+            this.vacant.push(index);
+            return task::Poll::Ready(output);
+        }
+        // Note:
+        // The futures woken up while polling the others are left for the next `poll_next`, which is requested here.
+        if !lock(&this.shared).woken.is_empty() {
+            cx.waker().wake_by_ref();
+        }
+        task::Poll::Pending
+    }
+}
+
+impl<F> Default for FuturesUnordered<F>
+where
+    F: future::Future,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> fmt::Debug for FuturesUnordered<F>
+where
+    F: future::Future,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "FuturesUnordered(len: {})", self.len())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::StreamExt as _;
+    use std::sync::atomic;
+
+    /// Represents a future which counts its polls, and completes once it gets polled after the given `ready` is set.
+    struct Counted {
+        polls: sync::Arc<atomic::AtomicUsize>,
+        ready: sync::Arc<atomic::AtomicBool>,
+        waker: sync::Arc<sync::Mutex<Option<task::Waker>>>,
+    }
+
+    impl future::Future for Counted {
+        type Output = ();
+
+        fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<()> {
+            self.polls.fetch_add(1, atomic::Ordering::SeqCst);
+            if self.ready.load(atomic::Ordering::SeqCst) {
+                return task::Poll::Ready(());
+            }
+            *self.waker.lock().unwrap() = Some(cx.waker().clone());
+            task::Poll::Pending
+        }
+    }
+
+    #[test]
+    fn empty_set_ends_immediately() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut futures = FuturesUnordered::<future::Ready<()>>::new();
+            assert!(futures.is_empty());
+            assert_eq!(futures.next().await, None);
+        });
+    }
+
+    #[test]
+    fn only_the_woken_futures_are_polled_again() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let counters: Vec<_> = (0..3)
+                .map(|_| {
+                    (
+                        sync::Arc::new(atomic::AtomicUsize::new(0)),
+                        sync::Arc::new(atomic::AtomicBool::new(false)),
+                        sync::Arc::new(sync::Mutex::new(None)),
+                    )
+                })
+                .collect();
+            let mut futures = FuturesUnordered::new();
+            for (polls, ready, waker) in &counters {
+                futures.push(Counted {
+                    polls: polls.clone(),
+                    ready: ready.clone(),
+                    waker: waker.clone(),
+                });
+            }
+            let mut next = pin::pin!(futures.next());
+            let pending = future::poll_fn(|cx| {
+                task::Poll::Ready(future::Future::poll(next.as_mut(), cx).is_pending())
+            })
+            .await;
+            assert!(pending);
+            let (_, ready, waker) = &counters[1];
+            ready.store(true, atomic::Ordering::SeqCst);
+            waker.lock().unwrap().take().unwrap().wake();
+            assert_eq!(next.await, Some(()));
+            let polls: Vec<_> = counters
+                .iter()
+                .map(|(polls, _, _)| polls.load(atomic::Ordering::SeqCst))
+                .collect();
+            assert_eq!(polls, [1, 2, 1]);
+            assert_eq!(futures.len(), 2);
+        });
+    }
+
+    #[test]
+    fn push_reuses_the_entries_of_the_completed_futures() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut futures = FuturesUnordered::new();
+            futures.push(future::ready(1));
+            futures.push(future::ready(2));
+            assert_eq!(futures.next().await, Some(1));
+            futures.push(future::ready(3));
+            assert_eq!(futures.entries.len(), 2);
+            assert_eq!(futures.len(), 2);
+            let mut outputs = vec![futures.next().await, futures.next().await];
+            outputs.sort();
+            assert_eq!(outputs, [Some(2), Some(3)]);
+            assert_eq!(futures.next().await, None);
+        });
+    }
+
+    #[test]
+    fn self_waking_future_does_not_starve_the_task() {
+        let _serial = crate::tests::serial();
+        let ret = crate::block_on_timeout(
+            async {
+                let mut futures = FuturesUnordered::new();
+                futures.push(future::poll_fn(|cx| {
+                    cx.waker().wake_by_ref();
+                    task::Poll::<()>::Pending
+                }));
+                let mut next = pin::pin!(futures.next());
+                // Note:
+                // The future woken up while being polled is left for the next `poll_next`, i.e., `poll_next` returns.
+                for _ in 0..3 {
+                    let pending = future::poll_fn(|cx| {
+                        task::Poll::Ready(future::Future::poll(next.as_mut(), cx).is_pending())
+                    })
+                    .await;
+                    assert!(pending);
+                }
+            },
+            std::time::Duration::from_secs(5),
+        );
+        assert_eq!(ret, Some(()));
+    }
+}
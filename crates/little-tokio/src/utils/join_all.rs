@@ -0,0 +1,168 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of `JoinAll` combinator.
+
+use crate::utils::maybe_done::{maybe_done, MaybeDone};
+use std::{fmt, future, pin, task};
+
+/// Awaits the given `futures` concurrently and resolves to the vector of their outputs, in the order of the
+/// `futures`, once all of them have completed. An empty vector of `futures` resolves immediately.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// little_tokio::block_on(async {
+///     let delays = little_tokio::utils::join_all(
+///         [30, 10, 20]
+///             .into_iter()
+///             .map(|millis| async move {
+///                 little_tokio::time::sleep(Duration::from_millis(millis)).await;
+///                 millis
+///             })
+///             .collect(),
+///     )
+///     .await;
+///     assert_eq!(delays, [30, 10, 20]);
+/// });
+/// ```
+///
+/// # See also:
+/// `join!` which awaits a fixed number of futures of different types.
+pub fn join_all<F>(futures: Vec<F>) -> JoinAll<F>
+where
+    F: future::Future,
+{
+    JoinAll {
+        futures: futures
+            .into_iter()
+            .map(maybe_done)
+            .collect::<Box<_>>()
+            .into(),
+    }
+}
+
+/// A `Future` which awaits a vector of futures of the same type. This struct is created by `join_all`, and each
+/// future is wrapped into a `MaybeDone`, so that all the futures which have not completed yet are polled on each
+/// wakeup.
+pub struct JoinAll<F>
+where
+    F: future::Future,
+{
+    futures: pin::Pin<Box<[MaybeDone<F>]>>,
+}
+
+impl<F> JoinAll<F>
+where
+    F: future::Future,
+{
+    /// Returns the pinned references to the futures.
+    fn futures(&mut self) -> impl Iterator<Item = pin::Pin<&mut MaybeDone<F>>> {
+        // Safety:
+        // The futures are owned by the pinned `Box` and never moved out of it, i.e., each of them stays pinned.
+        unsafe {
+            self.futures
+                .as_mut()
+                .get_unchecked_mut()
+                .iter_mut()
+                .map(|future| pin::Pin::new_unchecked(future))
+        }
+    }
+}
+
+pub type JoinAllOutput<T> = Vec<T>;
+
+impl<F> future::Future for JoinAll<F>
+where
+    F: future::Future,
+{
+    type Output = JoinAllOutput<F::Output>;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        // Note:
+        // `JoinAll` holds its futures in a `Box`, i.e., it is `Unpin`, so that it can be borrowed mutably without
+        // any pin projection.
+        let this = self.get_mut();
+        let mut done = true;
+        for future in this.futures() {
+            if future::Future::poll(future, cx).is_pending() {
+                done = false;
+            }
+        }
+        if !done {
+            return task::Poll::Pending;
+        }
+        task::Poll::Ready(
+            this.futures()
+                .map(|future| {
+                    future
+                        .take_output()
+                        .expect("`MaybeDone` should hold the output of the done future")
+                })
+                .collect(),
+        )
+    }
+}
+
+impl<F> fmt::Debug for JoinAll<F>
+where
+    F: future::Future,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "JoinAll(len: {})", self.futures.len())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync, time};
+
+    #[test]
+    fn join_all_keeps_the_order_of_the_futures() {
+        let _serial = crate::tests::serial();
+        let completed = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let log = completed.clone();
+        crate::block_on(async move {
+            let started = time::Instant::now();
+            let outputs = join_all(
+                [60, 20, 40]
+                    .into_iter()
+                    .map(|millis| {
+                        let log = log.clone();
+                        async move {
+                            crate::time::sleep(time::Duration::from_millis(millis)).await;
+                            log.lock().unwrap().push(millis);
+                            millis * 2
+                        }
+                    })
+                    .collect(),
+            )
+            .await;
+            assert_eq!(outputs, [120, 40, 80]);
+            assert!(started.elapsed() < time::Duration::from_millis(120));
+        });
+        assert_eq!(*completed.lock().unwrap(), [20, 40, 60]);
+    }
+
+    #[test]
+    fn join_all_of_no_futures_resolves_immediately() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let outputs = join_all(Vec::<future::Ready<()>>::new()).await;
+            assert!(outputs.is_empty());
+        });
+    }
+}
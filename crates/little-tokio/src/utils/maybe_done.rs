@@ -15,7 +15,7 @@
 //! This module contains the implementation of `MaybeDone` combinator.
 
 use crate::utils::misc::assert_future;
-use std::{future, pin, task};
+use std::{future, mem, pin, task};
 
 /// Represents a `Future` that may have done.
 pub enum MaybeDone<F>
@@ -29,6 +29,40 @@ where
 
 impl<F> Unpin for MaybeDone<F> where F: future::Future + Unpin {}
 
+impl<F> MaybeDone<F>
+where
+    F: future::Future,
+{
+    /// Returns `true` if the `Future` has done and its output has not been taken yet.
+    pub fn is_done(&self) -> bool {
+        matches!(self, Self::Done(_))
+    }
+
+    /// Returns the reference to the output of the `Future` if it has done and the output has not been taken yet.
+    pub fn output(&self) -> Option<&F::Output> {
+        match self {
+            Self::Done(output) => Some(output),
+            _ => None,
+        }
+    }
+
+    /// Takes the output of the `Future` out of the `MaybeDone` if it has done, leaving `MaybeDone::Gone` in its
+    /// place. Returns `None` if the `Future` has not done yet, or the output has already been taken.
+    pub fn take_output(self: pin::Pin<&mut Self>) -> Option<F::Output> {
+        if !self.is_done() {
+            return None;
+        }
+        // Safety:
+        // The `MaybeDone` is `Done`, i.e., it holds no pinned `Future` which would be moved out by the replacement.
+        unsafe {
+            match mem::replace(self.get_unchecked_mut(), Self::Gone) {
+                Self::Done(output) => Some(output),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
 impl<Fut> future::Future for MaybeDone<Fut>
 where
     Fut: future::Future,
@@ -59,3 +93,38 @@ where
 {
     assert_future::<(), _>(MaybeDone::Future(future))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_is_taken_exactly_once_after_the_future_has_done() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let mut yielded = false;
+            let mut done = pin::pin!(maybe_done(future::poll_fn(move |cx| {
+                if yielded {
+                    return task::Poll::Ready(7);
+                }
+                yielded = true;
+                cx.waker().wake_by_ref();
+                task::Poll::Pending
+            })));
+            let pending = future::poll_fn(|cx| {
+                task::Poll::Ready(future::Future::poll(done.as_mut(), cx).is_pending())
+            })
+            .await;
+            assert!(pending);
+            assert!(!done.is_done());
+            assert_eq!(done.as_mut().take_output(), None);
+            done.as_mut().await;
+            assert!(done.is_done());
+            assert_eq!(done.output(), Some(&7));
+            assert_eq!(done.as_mut().take_output(), Some(7));
+            assert!(!done.is_done());
+            assert!(matches!(*done, MaybeDone::Gone));
+            assert_eq!(done.as_mut().take_output(), None);
+        });
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of `retry` combinators, which re-run a fallible operation with a
+//! backoff between the attempts.
+
+use crate::time;
+use std::{future, io};
+
+/// Runs the future created by the given `operation` until it succeeds, up to `max_attempts` times, and resolves to
+/// its first success or its last failure. The task sleeps for the given `backoff` after each failure before the
+/// next attempt, i.e., an operation succeeding on the first attempt never sleeps.
+///
+/// ```
+/// little_tokio::block_on(async {
+///     let mut attempts = 0;
+///     let ret: std::io::Result<usize> = little_tokio::utils::retry(
+///         3,
+///         std::time::Duration::from_millis(1),
+///         || {
+///             attempts += 1;
+///             let attempt = attempts;
+///             async move {
+///                 match attempt {
+///                     3 => Ok(attempt),
+///                     _ => Err(std::io::ErrorKind::ConnectionRefused.into()),
+///                 }
+///             }
+///         },
+///     )
+///     .await;
+///     assert_eq!(ret.unwrap(), 3);
+/// });
+/// ```
+///
+/// # Note:
+/// Zero `max_attempts` fails immediately with `io::ErrorKind::InvalidInput`, converted into the error type of the
+/// `operation`, without running the `operation` at all.
+///
+/// # See also:
+/// `retry_exponential` which doubles the `backoff` after each failure.
+pub fn retry<F, Fut, T, E>(
+    max_attempts: usize,
+    backoff: time::Duration,
+    operation: F,
+) -> impl future::Future<Output = RetryOutput<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: future::Future<Output = Result<T, E>>,
+    E: From<io::Error>,
+{
+    run(max_attempts, backoff, 1, operation)
+}
+
+/// Runs the future created by the given `operation` until it succeeds, up to `max_attempts` times, as `retry`
+/// does, except that the `backoff` doubles after each failure, e.g., 10ms, 20ms, 40ms and so on.
+pub fn retry_exponential<F, Fut, T, E>(
+    max_attempts: usize,
+    backoff: time::Duration,
+    operation: F,
+) -> impl future::Future<Output = RetryOutput<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: future::Future<Output = Result<T, E>>,
+    E: From<io::Error>,
+{
+    run(max_attempts, backoff, 2, operation)
+}
+
+/// The output type of `retry` and `retry_exponential`.
+pub type RetryOutput<T, E> = Result<T, E>;
+
+/// Runs the future created by the given `operation` up to `max_attempts` times, multiplying the `backoff` by the
+/// given `factor` after each failure.
+async fn run<F, Fut, T, E>(
+    max_attempts: usize,
+    mut backoff: time::Duration,
+    factor: u32,
+    mut operation: F,
+) -> RetryOutput<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: future::Future<Output = Result<T, E>>,
+    E: From<io::Error>,
+{
+    if max_attempts == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "retry should make at least one attempt",
+        )
+        .into());
+    }
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(output) => return Ok(output),
+            Err(e) if attempt == max_attempts => return Err(e),
+            Err(_) => {
+                time::sleep(backoff).await;
+                backoff = backoff.saturating_mul(factor);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{self, atomic};
+
+    /// Runs `retry`, or `retry_exponential` if `exponential` is set, on the Little Tokio runtime over an operation
+    /// which fails the given number of `failures` times before it succeeds, and returns the result, the number of
+    /// attempts made and the elapsed time.
+    fn run_flaky(
+        max_attempts: usize,
+        backoff: time::Duration,
+        failures: usize,
+        exponential: bool,
+    ) -> (io::Result<usize>, usize, time::Duration) {
+        let _serial = crate::tests::serial();
+        let attempts = sync::Arc::new(atomic::AtomicUsize::new(0));
+        let result = sync::Arc::new(sync::Mutex::new(None));
+        let (counter, slot) = (attempts.clone(), result.clone());
+        let started = time::Instant::now();
+        crate::block_on(async move {
+            let operation = || {
+                let attempt = counter.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+                async move {
+                    if attempt <= failures {
+                        Err(io::Error::from(io::ErrorKind::ConnectionRefused))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            };
+            let ret = if exponential {
+                retry_exponential(max_attempts, backoff, operation).await
+            } else {
+                retry(max_attempts, backoff, operation).await
+            };
+            *slot.lock().unwrap() = Some(ret);
+        });
+        let elapsed = started.elapsed();
+        let ret = result.lock().unwrap().take().unwrap();
+        (ret, attempts.load(atomic::Ordering::SeqCst), elapsed)
+    }
+
+    #[test]
+    fn retry_succeeds_after_two_failures() {
+        let backoff = time::Duration::from_millis(20);
+        let (ret, attempts, elapsed) = run_flaky(5, backoff, 2, false);
+        assert_eq!(ret.unwrap(), 3);
+        assert_eq!(attempts, 3);
+        assert!(elapsed >= backoff * 2);
+    }
+
+    #[test]
+    fn retry_does_not_sleep_on_the_first_success() {
+        let backoff = time::Duration::from_secs(10);
+        let (ret, attempts, elapsed) = run_flaky(5, backoff, 0, false);
+        assert_eq!(ret.unwrap(), 1);
+        assert_eq!(attempts, 1);
+        assert!(elapsed < backoff);
+    }
+
+    #[test]
+    fn retry_gives_up_with_the_last_failure() {
+        let (ret, attempts, _) = run_flaky(3, time::Duration::from_millis(1), 10, false);
+        assert_eq!(ret.unwrap_err().kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn retry_with_zero_attempts_fails_immediately() {
+        let (ret, attempts, elapsed) = run_flaky(0, time::Duration::from_secs(10), 0, false);
+        assert_eq!(ret.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(attempts, 0);
+        assert!(elapsed < time::Duration::from_secs(10));
+    }
+
+    #[test]
+    fn retry_exponential_doubles_the_backoff() {
+        let backoff = time::Duration::from_millis(20);
+        let (ret, attempts, elapsed) = run_flaky(5, backoff, 2, true);
+        assert_eq!(ret.unwrap(), 3);
+        assert_eq!(attempts, 3);
+        assert!(elapsed >= backoff * 3);
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2024 Shingo OKAWA and a number of other contributors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module contains the implementation of `SelectAll` combinator.
+
+use std::{fmt, future, mem, pin, task};
+
+/// Awaits the given `futures` concurrently and resolves to the output of the first one which completes, together
+/// with its index and the remaining futures in their original order, so that the caller can keep awaiting them.
+/// The futures are polled in order on each wakeup, i.e., an earlier future takes priority when several futures are
+/// ready at once.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// little_tokio::block_on(async {
+///     let mut upstreams: Vec<_> = [30, 10, 20]
+///         .into_iter()
+///         .map(|millis| {
+///             Box::pin(async move {
+///                 little_tokio::time::sleep(Duration::from_millis(millis)).await;
+///                 millis
+///             })
+///         })
+///         .collect();
+///     let mut responses = Vec::new();
+///     while !upstreams.is_empty() {
+///         let (response, _index, remaining) = little_tokio::utils::select_all(upstreams).await;
+///         responses.push(response);
+///         upstreams = remaining;
+///     }
+///     assert_eq!(responses, [10, 20, 30]);
+/// });
+/// ```
+///
+/// # Note:
+/// The remaining futures are handed back rather than dropped, i.e., they keep their registrations to the runtime.
+/// The futures are required to be `Unpin` since they are moved into the returned vector, hence a future which is
+/// not `Unpin` needs to be pinned into a `Box` by `Box::pin` first.
+///
+/// # Panics:
+/// Panics if the given `futures` are empty.
+pub fn select_all<F>(futures: Vec<F>) -> SelectAll<F>
+where
+    F: future::Future + Unpin,
+{
+    assert!(
+        !futures.is_empty(),
+        "futures of the `select_all` should not be empty"
+    );
+    SelectAll { futures }
+}
+
+/// A `Future` which races a vector of futures of the same type. This struct is created by `select_all`.
+pub struct SelectAll<F> {
+    futures: Vec<F>,
+}
+
+pub type SelectAllOutput<F> = (<F as future::Future>::Output, usize, Vec<F>);
+
+impl<F> future::Future for SelectAll<F>
+where
+    F: future::Future + Unpin,
+{
+    type Output = SelectAllOutput<F>;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.get_mut();
+        let ready = this
+            .futures
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, future)| match pin::Pin::new(future).poll(cx) {
+                task::Poll::Ready(output) => Some((index, output)),
+                task::Poll::Pending => None,
+            });
+        let Some((index, output)) = ready else {
+            return task::Poll::Pending;
+        };
+        let mut remaining = mem::take(&mut this.futures);
+        drop(remaining.remove(index));
+        task::Poll::Ready((output, index, remaining))
+    }
+}
+
+impl<F> fmt::Debug for SelectAll<F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "SelectAll(len: {})", self.futures.len())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_all_resolves_to_the_first_ready_future_and_hands_back_the_others() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let futures = vec![future::ready(1), future::ready(2), future::ready(3)];
+            let (output, index, remaining) = select_all(futures).await;
+            // Note:
+            // All the futures are ready at once, i.e., the earliest one takes priority.
+            assert_eq!((output, index), (1, 0));
+            assert_eq!(remaining.len(), 2);
+            let (output, index, remaining) = select_all(remaining).await;
+            assert_eq!((output, index), (2, 0));
+            assert_eq!(remaining.len(), 1);
+        });
+    }
+
+    #[test]
+    fn select_all_keeps_the_remaining_futures_in_their_original_order() {
+        let _serial = crate::tests::serial();
+        crate::block_on(async {
+            let futures: Vec<pin::Pin<Box<dyn future::Future<Output = u32> + Send>>> = vec![
+                Box::pin(future::pending()),
+                Box::pin(future::ready(2)),
+                Box::pin(async { 3 }),
+            ];
+            let (output, index, mut remaining) = select_all(futures).await;
+            assert_eq!((output, index), (2, 1));
+            assert!(
+                future::poll_fn(|cx| task::Poll::Ready(
+                    remaining[0].as_mut().poll(cx).is_pending()
+                ))
+                .await
+            );
+            assert_eq!(remaining.remove(1).await, 3);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "futures of the `select_all` should not be empty")]
+    fn select_all_rejects_no_futures() {
+        select_all(Vec::<future::Ready<()>>::new());
+    }
+}